@@ -0,0 +1,268 @@
+//! Local log of captured activity, one encrypted record per line in a file per local day:
+//! `activity/2024-01-05.log` in app local data. Each line is the base64 of a record's
+//! JSON, encrypted like the screenshots. The app appends to it, and the `recount` CLI
+//! reads it after unlocking the vault with its password.
+
+use base64::Engine;
+use chrono::{Days, Local, NaiveDate, TimeZone};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use zeroize::Zeroizing;
+
+use crate::commands::ActivityKind;
+
+const ACTIVITY_DIR: &str = "activity";
+const LOG_EXTENSION: &str = "log";
+
+/// Keeps appends out of a file while it is rewritten.
+static LOG_LOCK: Mutex<()> = Mutex::new(());
+
+/// What a snapshot recorded, without its screenshots.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ActivityRecord {
+    /// Seconds since the epoch.
+    pub timestamp: u32,
+    pub kind: ActivityKind,
+    pub application_name: Option<String>,
+    pub window_title: Option<String>,
+    pub url: Option<String>,
+}
+
+fn local_date(timestamp: u32) -> Option<NaiveDate> {
+    Local
+        .timestamp_opt(timestamp.into(), 0)
+        .single()
+        .map(|time| time.date_naive())
+}
+
+fn log_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join(ACTIVITY_DIR)
+}
+
+fn day_path(data_dir: &Path, date: NaiveDate) -> PathBuf {
+    log_dir(data_dir).join(format!("{}.{}", date.format("%Y-%m-%d"), LOG_EXTENSION))
+}
+
+fn lock() -> io::Result<std::sync::MutexGuard<'static, ()>> {
+    LOG_LOCK
+        .lock()
+        .map_err(|_| io::Error::other("Activity log lock poisoned"))
+}
+
+/// The day files, with their dates. Files with an unexpected name are skipped.
+fn day_files(data_dir: &Path) -> io::Result<Vec<(NaiveDate, PathBuf)>> {
+    let entries = match fs::read_dir(log_dir(data_dir)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != LOG_EXTENSION) {
+            continue;
+        }
+        let date = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| NaiveDate::parse_from_str(stem, "%Y-%m-%d").ok());
+        if let Some(date) = date {
+            files.push((date, path));
+        }
+    }
+    Ok(files)
+}
+
+fn seal_line(
+    plaintext: &[u8],
+    seal: impl FnOnce(&[u8]) -> io::Result<Vec<u8>>,
+) -> io::Result<Vec<u8>> {
+    let mut line = base64::engine::general_purpose::STANDARD
+        .encode(seal(plaintext)?)
+        .into_bytes();
+    line.push(b'\n');
+    Ok(line)
+}
+
+fn open_line(
+    line: &str,
+    open: impl Fn(&[u8]) -> io::Result<Zeroizing<Vec<u8>>>,
+) -> Option<Zeroizing<Vec<u8>>> {
+    let sealed = base64::engine::general_purpose::STANDARD
+        .decode(line.trim())
+        .ok()?;
+    open(&sealed).ok()
+}
+
+/// Append a record to the file of its day, encrypted with `seal`.
+pub fn append(
+    data_dir: &Path,
+    record: &ActivityRecord,
+    seal: impl FnOnce(&[u8]) -> io::Result<Vec<u8>>,
+) -> io::Result<()> {
+    let date = local_date(record.timestamp)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid timestamp"))?;
+    let path = day_path(data_dir, date);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = Zeroizing::new(
+        serde_json::to_vec(record).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+    );
+    let line = seal_line(&json, seal)?;
+
+    let _guard = lock()?;
+    // A single write, so a reader never sees half a line unless the app crashed
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&line)
+}
+
+/// Records from `from` up to `to`, in seconds since the epoch, oldest first, decrypted
+/// with `open`. Lines that do not open or parse, like one cut off by a crash, are skipped.
+pub fn read(
+    data_dir: &Path,
+    from: u32,
+    to: u32,
+    open: impl Fn(&[u8]) -> io::Result<Zeroizing<Vec<u8>>>,
+) -> io::Result<Vec<ActivityRecord>> {
+    let (Some(mut date), Some(last)) = (local_date(from), local_date(to)) else {
+        return Ok(Vec::new());
+    };
+    let mut records = Vec::new();
+    while date <= last {
+        match fs::read_to_string(day_path(data_dir, date)) {
+            Ok(contents) => records.extend(
+                contents
+                    .lines()
+                    .filter_map(|line| open_line(line, &open))
+                    .filter_map(|json| serde_json::from_slice::<ActivityRecord>(&json).ok())
+                    .filter(|record| record.timestamp >= from && record.timestamp < to),
+            ),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        date = match date.checked_add_days(Days::new(1)) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    records.sort_by_key(|record| record.timestamp);
+    Ok(records)
+}
+
+/// Re-encrypt every line with `seal`, e.g. when the key changes. Lines that do not open
+/// could not be read afterwards either, and are dropped. Returns the number of files.
+pub fn reseal(
+    data_dir: &Path,
+    open: impl Fn(&[u8]) -> io::Result<Zeroizing<Vec<u8>>>,
+    seal: impl Fn(&[u8]) -> io::Result<Vec<u8>>,
+) -> io::Result<u32> {
+    let _guard = lock()?;
+    let mut resealed = 0;
+    for (_, path) in day_files(data_dir)? {
+        let mut contents = Vec::new();
+        for line in fs::read_to_string(&path)?.lines() {
+            if let Some(plaintext) = open_line(line, &open) {
+                contents.extend(seal_line(&plaintext, &seal)?);
+            }
+        }
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, contents)?;
+        fs::rename(&tmp_path, &path)?;
+        resealed += 1;
+    }
+    Ok(resealed)
+}
+
+/// Delete the files of days before `date`. Returns the number of deleted files.
+pub fn remove_before(data_dir: &Path, date: NaiveDate) -> io::Result<u32> {
+    let _guard = lock()?;
+    let mut removed = 0;
+    for (day, path) in day_files(data_dir)? {
+        if day < date {
+            fs::remove_file(path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("recount-activity-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Stand-ins for the vault: a XOR with a one byte key.
+    fn seal(key: u8) -> impl Fn(&[u8]) -> io::Result<Vec<u8>> {
+        move |data| {
+            Ok(std::iter::once(key)
+                .chain(data.iter().map(|b| b ^ key))
+                .collect())
+        }
+    }
+
+    fn open(key: u8) -> impl Fn(&[u8]) -> io::Result<Zeroizing<Vec<u8>>> {
+        move |data| match data.split_first() {
+            Some((&k, rest)) if k == key => {
+                Ok(Zeroizing::new(rest.iter().map(|b| b ^ key).collect()))
+            }
+            _ => Err(io::Error::other("wrong key")),
+        }
+    }
+
+    fn record(timestamp: u32, title: &str) -> ActivityRecord {
+        ActivityRecord {
+            timestamp,
+            kind: ActivityKind::Active,
+            application_name: Some("Editor".to_string()),
+            window_title: Some(title.to_string()),
+            url: None,
+        }
+    }
+
+    #[test]
+    fn stores_records_encrypted() {
+        let dir = temp_dir("encrypted");
+        let now = Local::now().timestamp() as u32;
+        append(&dir, &record(now, "secret plans.md"), seal(7)).unwrap();
+        append(&dir, &record(now - 1, "notes.md"), seal(7)).unwrap();
+
+        let (_, path) = day_files(&dir).unwrap().remove(0);
+        assert!(!fs::read_to_string(path).unwrap().contains("secret"));
+        assert_eq!(
+            read(&dir, now - 1, now + 1, open(7)).unwrap(),
+            [record(now - 1, "notes.md"), record(now, "secret plans.md")]
+        );
+        assert!(read(&dir, now - 1, now + 1, open(8)).unwrap().is_empty());
+
+        assert_eq!(reseal(&dir, open(7), seal(8)).unwrap(), 1);
+        assert_eq!(read(&dir, now - 1, now + 1, open(8)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn removes_days_before_a_date() {
+        let dir = temp_dir("retention");
+        let today = Local::now().timestamp() as u32;
+        let last_week = today - 7 * 24 * 60 * 60;
+        append(&dir, &record(last_week, "old"), seal(1)).unwrap();
+        append(&dir, &record(today, "new"), seal(1)).unwrap();
+
+        let cutoff = local_date(today - 24 * 60 * 60).unwrap();
+        assert_eq!(remove_before(&dir, cutoff).unwrap(), 1);
+        let records = read(&dir, last_week, today + 1, open(1)).unwrap();
+        assert_eq!(records, [record(today, "new")]);
+    }
+}
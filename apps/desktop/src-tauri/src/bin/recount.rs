@@ -0,0 +1,5 @@
+// The `recount` command line tool, see `cli`.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    std::process::exit(tauri_app_lib::cli::run(&args));
+}
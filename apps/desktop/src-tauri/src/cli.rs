@@ -0,0 +1,612 @@
+//! The `recount` command line tool.
+//!
+//! Timer and tracking commands go to the running app over the instance socket, see
+//! `instance_ipc`. When the app is not running, `status`, `start` and `stop` work on the
+//! timer files in app local data directly, and `start` only takes project ids, since
+//! project names are only known to the app. `pause` and `resume` need the app, as nothing
+//! is tracked without it.
+//!
+//! `activity` and `export` always read the encrypted activity log directly, after
+//! unlocking the vault with its password, so the socket never hands out activity.
+
+use chrono::{DateTime, Days, Local, NaiveDate, TimeZone};
+use std::io::{self, BufRead, IsTerminal};
+use zeroize::Zeroizing;
+
+use crate::{
+    activity_log::{self, ActivityRecord},
+    commands::{
+        is_uuid, read_timer_file, stop_timer_offline, switch_timer_offline, ActivityKind,
+        DataCipher, StoppedTimeEntry, VaultState,
+    },
+    instance_ipc::{
+        self, app_local_data_dir, InstanceRequest, InstanceResponse, EXIT_FAILED, EXIT_OK,
+        EXIT_USAGE,
+    },
+};
+
+const USAGE: &str = "\
+Usage: recount <command>
+
+Commands:
+  status [--json]                     The running timer, and whether tracking is paused
+  start <project>                     Start a timer for a recent project, by name or id
+  stop                                Stop the running timer
+  pause [<duration>]                  Pause tracking, e.g. `pause 30m` or `pause 1h30m`,
+                                      or until resumed
+  resume                              Resume tracking
+  activity [<day>] [--json]           What was captured on a day: `today` (the default),
+                                      `yesterday` or a date like 2024-01-05
+  export [--from <day>] [--to <day>] [--format csv|json]
+                                      Captured activity, today's by default
+
+`activity` and `export` ask for the vault password, or read it from stdin.";
+
+/// Spans of the same activity further apart than this are not joined up in `activity`.
+const MAX_GAP_SECONDS: u32 = 10 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Status {
+        json: bool,
+    },
+    Start {
+        project: String,
+    },
+    Stop,
+    Pause {
+        minutes: Option<u32>,
+    },
+    Resume,
+    Activity {
+        day: NaiveDate,
+        json: bool,
+    },
+    Export {
+        from: NaiveDate,
+        to: NaiveDate,
+        format: ExportFormat,
+    },
+}
+
+/// `today`, `yesterday` or `2024-01-05`.
+fn parse_day(day: &str) -> Result<NaiveDate, String> {
+    let today = Local::now().date_naive();
+    match day {
+        "today" => Ok(today),
+        "yesterday" => today
+            .checked_sub_days(Days::new(1))
+            .ok_or_else(|| "Invalid date".to_string()),
+        day => NaiveDate::parse_from_str(day, "%Y-%m-%d")
+            .map_err(|_| format!("Invalid day `{}`, expected e.g. 2024-01-05", day)),
+    }
+}
+
+/// `30m`, `2h`, `1h30m` or a number of minutes.
+fn parse_minutes(duration: &str) -> Result<u32, String> {
+    let invalid = || {
+        format!(
+            "Invalid duration `{}`, expected e.g. 30m or 1h30m",
+            duration
+        )
+    };
+    if let Ok(minutes) = duration.parse::<u32>() {
+        return (minutes > 0).then_some(minutes).ok_or_else(invalid);
+    }
+    let mut minutes: u32 = 0;
+    let mut number = String::new();
+    for c in duration.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' => {
+                let n: u32 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                minutes = n
+                    .checked_mul(if c == 'h' { 60 } else { 1 })
+                    .and_then(|n| minutes.checked_add(n))
+                    .ok_or_else(invalid)?;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    if !number.is_empty() || minutes == 0 {
+        return Err(invalid());
+    }
+    Ok(minutes)
+}
+
+fn parse(args: &[String]) -> Result<Command, String> {
+    let (command, rest) = args.split_first().ok_or_else(|| USAGE.to_string())?;
+    let json = rest.iter().any(|arg| arg == "--json");
+    let positional: Vec<&String> = rest.iter().filter(|arg| !arg.starts_with("--")).collect();
+
+    match command.as_str() {
+        "status" => Ok(Command::Status { json }),
+        "start" => match positional.as_slice() {
+            [] => Err("start needs a project".to_string()),
+            // Allow unquoted project names with spaces
+            words => Ok(Command::Start {
+                project: words
+                    .iter()
+                    .map(|word| word.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            }),
+        },
+        "stop" => Ok(Command::Stop),
+        "pause" => match positional.as_slice() {
+            [] => Ok(Command::Pause { minutes: None }),
+            [duration] => Ok(Command::Pause {
+                minutes: Some(parse_minutes(duration)?),
+            }),
+            _ => Err("pause takes a single duration".to_string()),
+        },
+        "resume" => Ok(Command::Resume),
+        "activity" => Ok(Command::Activity {
+            day: parse_day(positional.first().map_or("today", |day| day.as_str()))?,
+            json,
+        }),
+        "export" => {
+            let option = |name: &str| -> Result<Option<&String>, String> {
+                match rest.iter().position(|arg| arg == name) {
+                    Some(i) => rest
+                        .get(i + 1)
+                        .map(Some)
+                        .ok_or_else(|| format!("{} needs a value", name)),
+                    None => Ok(None),
+                }
+            };
+            let from =
+                option("--from")?.map_or(Ok(Local::now().date_naive()), |day| parse_day(day))?;
+            let to = option("--to")?.map_or(Ok(from), |day| parse_day(day))?;
+            let format = match option("--format")?.map(|format| format.as_str()) {
+                None | Some("csv") => ExportFormat::Csv,
+                Some("json") => ExportFormat::Json,
+                Some(format) => return Err(format!("Unknown export format `{}`", format)),
+            };
+            if to < from {
+                return Err("--to is before --from".to_string());
+            }
+            Ok(Command::Export { from, to, format })
+        }
+        "help" | "--help" | "-h" => Err(USAGE.to_string()),
+        command => Err(format!("Unknown command `{}`\n\n{}", command, USAGE)),
+    }
+}
+
+/// Seconds since the epoch at the start of a local day.
+fn day_start(day: NaiveDate) -> u32 {
+    day.and_hms_opt(0, 0, 0)
+        .and_then(|midnight| Local.from_local_datetime(&midnight).earliest())
+        .map_or(0, |time| time.timestamp().max(0) as u32)
+}
+
+/// Switch terminal echo on or off while a password is typed.
+#[cfg(unix)]
+fn set_echo(on: bool) {
+    let _ = std::process::Command::new("stty")
+        .arg(if on { "echo" } else { "-echo" })
+        .stdin(std::process::Stdio::inherit())
+        .status();
+}
+
+#[cfg(not(unix))]
+fn set_echo(_on: bool) {}
+
+/// The vault password, from stdin. Not echoed when typed on a terminal.
+fn read_password() -> Result<Zeroizing<String>, String> {
+    let stdin = io::stdin();
+    let terminal = stdin.is_terminal();
+    if terminal {
+        eprint!("Vault password: ");
+        set_echo(false);
+    }
+    let mut password = Zeroizing::new(String::new());
+    let read = stdin.lock().read_line(&mut password);
+    if terminal {
+        set_echo(true);
+        eprintln!();
+    }
+    read.map_err(|e| e.to_string())?;
+    let length = password.trim_end_matches(['\r', '\n']).len();
+    password.truncate(length);
+    Ok(password)
+}
+
+/// Activity records of the days `from` through `to`, read from the activity log with the
+/// vault unlocked by its password.
+fn read_activity(from: NaiveDate, to: NaiveDate) -> Result<Vec<ActivityRecord>, String> {
+    let from = day_start(from);
+    let to = to
+        .checked_add_days(Days::new(1))
+        .map_or(u32::MAX, day_start);
+    let data_dir = app_local_data_dir().map_err(|e| e.to_string())?;
+    let vault = VaultState::default();
+    vault
+        .unlock(&data_dir, &read_password()?)
+        .map_err(|e| e.to_string())?;
+    let cipher = DataCipher::from_unlocked_vault(&vault).map_err(|e| e.to_string())?;
+    activity_log::read(&data_dir, from, to, |data| cipher.open(data)).map_err(|e| e.to_string())
+}
+
+fn local_time(timestamp: u32) -> Option<DateTime<Local>> {
+    Local.timestamp_opt(timestamp.into(), 0).single()
+}
+
+fn kind_name(kind: ActivityKind) -> String {
+    serde_json::to_value(kind)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn describe(record: &ActivityRecord) -> String {
+    let application = record.application_name.as_deref().unwrap_or("Unknown");
+    match (&record.window_title, record.kind) {
+        (_, ActivityKind::Idle) => "Idle".to_string(),
+        (Some(title), _) if !title.is_empty() => format!("{} — {}", application, title),
+        _ => application.to_string(),
+    }
+}
+
+/// Consecutive records of the same activity joined into `start–end  duration  activity`.
+fn format_activity(records: &[ActivityRecord]) -> String {
+    let mut lines = Vec::new();
+    let mut total = 0;
+    let mut i = 0;
+    while i < records.len() {
+        let description = describe(&records[i]);
+        let start = records[i].timestamp;
+        let mut last = start;
+        i += 1;
+        while i < records.len()
+            && describe(&records[i]) == description
+            && records[i].timestamp - last <= MAX_GAP_SECONDS
+        {
+            last = records[i].timestamp;
+            i += 1;
+        }
+        // A span lasts until the next one starts, unless tracking stopped in between
+        let end = match records.get(i) {
+            Some(next) if next.timestamp - last <= MAX_GAP_SECONDS => next.timestamp,
+            _ => last,
+        };
+        if records[i - 1].kind != ActivityKind::Idle {
+            total += end - start;
+        }
+        let time = |timestamp| {
+            local_time(timestamp).map_or_else(String::new, |time| time.format("%H:%M").to_string())
+        };
+        lines.push(format!(
+            "{}–{}  {:>5}  {}",
+            time(start),
+            time(end),
+            format!("{}m", (end - start) / 60),
+            description
+        ));
+    }
+    if lines.is_empty() {
+        return "No activity captured".to_string();
+    }
+    lines.push(format!(
+        "\nActive for {}h{:02}m",
+        total / 3600,
+        total / 60 % 60
+    ));
+    lines.join("\n")
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn format_csv(records: &[ActivityRecord]) -> String {
+    let mut lines = vec!["time,kind,application,window_title,url".to_string()];
+    for record in records {
+        lines.push(
+            [
+                local_time(record.timestamp).map_or_else(String::new, |time| time.to_rfc3339()),
+                kind_name(record.kind),
+                record.application_name.clone().unwrap_or_default(),
+                record.window_title.clone().unwrap_or_default(),
+                record.url.clone().unwrap_or_default(),
+            ]
+            .iter()
+            .map(|field| csv_field(field))
+            .collect::<Vec<_>>()
+            .join(","),
+        );
+    }
+    lines.join("\n")
+}
+
+fn to_json(value: &impl serde::Serialize) -> Result<String, String> {
+    serde_json::to_string_pretty(value).map_err(|e| e.to_string())
+}
+
+/// `status` from the timer file, when the app is not running.
+fn offline_status(json: bool) -> Result<String, String> {
+    let data_dir = app_local_data_dir().map_err(|e| e.to_string())?;
+    let timer = read_timer_file(&data_dir).map_err(|e| e.to_string())?;
+    if json {
+        return to_json(&serde_json::json!({ "timer": timer, "pause": null }));
+    }
+    Ok(match timer {
+        Some(timer) => format!(
+            "Recount is not running. A timer for project {} has been running since {}",
+            timer.project_id,
+            local_time(timer.started_at as u32).map_or_else(String::new, |time| time
+                .format("%Y-%m-%d %H:%M")
+                .to_string())
+        ),
+        None => "Recount is not running. No timer running".to_string(),
+    })
+}
+
+fn describe_stopped(entry: &StoppedTimeEntry) -> String {
+    format!(
+        "Stopped the timer for project {} after {}m",
+        entry.project_id,
+        (entry.stopped_at - entry.started_at) / 60
+    )
+}
+
+/// `start` on the timer files, when the app is not running.
+fn offline_start(project: &str) -> Result<String, String> {
+    if !is_uuid(project) {
+        return Err(
+            "Recount is not running, start it to use project names or pass a project id"
+                .to_string(),
+        );
+    }
+    let data_dir = app_local_data_dir().map_err(|e| e.to_string())?;
+    let stopped =
+        switch_timer_offline(&data_dir, project.to_ascii_lowercase()).map_err(|e| e.to_string())?;
+    let mut lines: Vec<String> = stopped.iter().map(describe_stopped).collect();
+    lines.push(format!(
+        "Recount is not running. Started a timer for project {}",
+        project
+    ));
+    Ok(lines.join("\n"))
+}
+
+/// `stop` on the timer files, when the app is not running. The app creates the time entry
+/// when it next starts.
+fn offline_stop() -> Result<String, String> {
+    let data_dir = app_local_data_dir().map_err(|e| e.to_string())?;
+    let entry = stop_timer_offline(&data_dir).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "Recount is not running. {}, the time entry is created when Recount starts",
+        describe_stopped(&entry)
+    ))
+}
+
+/// Send a request to the app and show its answer, or its data as JSON.
+fn send(request: InstanceRequest, json: bool) -> Result<(i32, String), String> {
+    let response: InstanceResponse = instance_ipc::send(&request)
+        .map_err(|_| "Recount is not running, start the app first".to_string())?;
+    let output = match (json, response.data) {
+        (true, Some(data)) if response.exit_code == EXIT_OK => to_json(&data)?,
+        _ => response.output,
+    };
+    Ok((response.exit_code, output))
+}
+
+fn execute(command: Command) -> Result<(i32, String), String> {
+    match command {
+        Command::Status { json } => match send(InstanceRequest::Status, json) {
+            Ok(result) => Ok(result),
+            Err(_) => Ok((EXIT_OK, offline_status(json)?)),
+        },
+        Command::Start { project } => {
+            match send(
+                InstanceRequest::Start {
+                    project: project.clone(),
+                },
+                false,
+            ) {
+                Ok(result) => Ok(result),
+                Err(_) => Ok((EXIT_OK, offline_start(&project)?)),
+            }
+        }
+        Command::Stop => match send(InstanceRequest::Stop, false) {
+            Ok(result) => Ok(result),
+            Err(_) => Ok((EXIT_OK, offline_stop()?)),
+        },
+        Command::Pause { minutes } => send(InstanceRequest::Pause { minutes }, false)
+            .map_err(|_| "Recount is not running, so nothing is tracked".to_string()),
+        Command::Resume => send(InstanceRequest::Resume, false)
+            .map_err(|_| "Recount is not running, so nothing is tracked".to_string()),
+        Command::Activity { day, json } => {
+            let records = read_activity(day, day)?;
+            let output = match json {
+                true => to_json(&records)?,
+                false => format_activity(&records),
+            };
+            Ok((EXIT_OK, output))
+        }
+        Command::Export { from, to, format } => {
+            let records = read_activity(from, to)?;
+            let output = match format {
+                ExportFormat::Csv => format_csv(&records),
+                ExportFormat::Json => to_json(&records)?,
+            };
+            Ok((EXIT_OK, output))
+        }
+    }
+}
+
+/// Run the CLI with its arguments, without the program name. Returns the exit code.
+pub fn run(args: &[String]) -> i32 {
+    let command = match parse(args) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("{}", message);
+            return EXIT_USAGE;
+        }
+    };
+    match execute(command) {
+        Ok((exit_code, output)) => {
+            if !output.is_empty() {
+                if exit_code == EXIT_OK {
+                    println!("{}", output);
+                } else {
+                    eprintln!("{}", output);
+                }
+            }
+            exit_code
+        }
+        Err(message) => {
+            eprintln!("recount: {}", message);
+            EXIT_FAILED
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn parses_commands() {
+        let cases = [
+            (&["status", "--json"][..], Command::Status { json: true }),
+            (
+                &["start", "Client", "website"],
+                Command::Start {
+                    project: "Client website".to_string(),
+                },
+            ),
+            (&["stop"], Command::Stop),
+            (&["pause"], Command::Pause { minutes: None }),
+            (&["pause", "1h30m"], Command::Pause { minutes: Some(90) }),
+            (&["resume"], Command::Resume),
+            (
+                &["activity", "2024-01-05", "--json"],
+                Command::Activity {
+                    day: date("2024-01-05"),
+                    json: true,
+                },
+            ),
+            (
+                &[
+                    "export",
+                    "--from",
+                    "2024-01-01",
+                    "--to",
+                    "2024-01-05",
+                    "--format",
+                    "json",
+                ],
+                Command::Export {
+                    from: date("2024-01-01"),
+                    to: date("2024-01-05"),
+                    format: ExportFormat::Json,
+                },
+            ),
+            (
+                &["export", "--from", "2024-01-01"],
+                Command::Export {
+                    from: date("2024-01-01"),
+                    to: date("2024-01-01"),
+                    format: ExportFormat::Csv,
+                },
+            ),
+        ];
+        for (arguments, expected) in cases {
+            assert_eq!(parse(&args(arguments)), Ok(expected), "{:?}", arguments);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        let cases: [&[&str]; 10] = [
+            &[],
+            &["frobnicate"],
+            &["start"],
+            &["pause", "0"],
+            &["pause", "30m", "1h"],
+            &["activity", "05-01-2024"],
+            &["export", "--from"],
+            &["export", "--format", "xml"],
+            &["export", "--from", "2024-01-05", "--to", "2024-01-01"],
+            &["--help"],
+        ];
+        for arguments in cases {
+            assert!(parse(&args(arguments)).is_err(), "{:?}", arguments);
+        }
+    }
+
+    #[test]
+    fn parses_durations_in_minutes() {
+        for (duration, minutes) in [("30", 30), ("30m", 30), ("2h", 120), ("1h30m", 90)] {
+            assert_eq!(parse_minutes(duration), Ok(minutes), "{}", duration);
+        }
+        for duration in ["", "0", "0m", "0h0m", "h", "1x", "90s", "1h30", "99999999h"] {
+            assert!(parse_minutes(duration).is_err(), "{}", duration);
+        }
+    }
+
+    #[test]
+    fn joins_consecutive_activity() {
+        let start = day_start(date("2024-01-05")) + 9 * 3600;
+        let record = |minutes: u32, kind, title: &str| ActivityRecord {
+            timestamp: start + minutes * 60,
+            kind,
+            application_name: Some("Editor".to_string()),
+            window_title: Some(title.to_string()),
+            url: None,
+        };
+        let records = [
+            record(0, ActivityKind::Active, "a.rs"),
+            record(5, ActivityKind::Active, "a.rs"),
+            record(10, ActivityKind::Active, "b.rs"),
+            record(15, ActivityKind::Idle, ""),
+            // After a gap the previous span ends where it was last seen
+            record(60, ActivityKind::Active, "b.rs"),
+        ];
+        assert_eq!(
+            format_activity(&records),
+            [
+                "09:00–09:10    10m  Editor — a.rs",
+                "09:10–09:15     5m  Editor — b.rs",
+                "09:15–09:15     0m  Idle",
+                "10:00–10:00     0m  Editor — b.rs",
+                "\nActive for 0h15m",
+            ]
+            .join("\n")
+        );
+        assert_eq!(format_activity(&[]), "No activity captured");
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        let cases = [
+            ("plain", "plain"),
+            ("a, b", "\"a, b\""),
+            ("say \"hi\"", "\"say \"\"hi\"\"\""),
+            ("two\nlines", "\"two\nlines\""),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(csv_field(value), expected);
+        }
+    }
+}
@@ -1,4 +1,54 @@
+pub mod ai;
+pub mod browser_activity;
+pub mod calendar;
 pub mod capture_window_activity;
+pub mod classify_project;
+pub mod deep_link;
+pub mod error;
+pub mod git_repository;
+pub mod issue_references;
+pub mod meeting_detection;
+pub mod permissions;
 pub mod platform;
+pub mod redaction;
+pub mod remote_control;
+pub mod retention;
+pub mod screenshot_dedup;
+pub mod screenshot_encoding;
+pub mod screenshot_vault;
+pub mod search_activity;
+pub mod settings;
+pub mod shortcuts;
+pub mod single_instance;
+pub mod summarize_day;
+pub mod timer;
+pub mod title_context;
+pub mod tracking_pause;
+pub mod tray;
+pub mod vault;
 
+pub use ai::*;
+pub use browser_activity::*;
+pub use calendar::*;
 pub use capture_window_activity::*;
+pub use classify_project::*;
+pub use deep_link::*;
+pub use error::*;
+pub use git_repository::*;
+pub use issue_references::*;
+pub use meeting_detection::*;
+pub use permissions::*;
+pub use remote_control::*;
+pub use retention::*;
+pub use screenshot_dedup::*;
+pub use screenshot_vault::*;
+pub use search_activity::*;
+pub use settings::*;
+pub use shortcuts::*;
+pub use single_instance::*;
+pub use summarize_day::*;
+pub use timer::*;
+pub use title_context::*;
+pub use tracking_pause::*;
+pub use tray::*;
+pub use vault::*;
@@ -1,18 +1,25 @@
-mod inference;
-use inference::generate_text_from_image;
+pub mod inference;
+pub mod runtime;
+
+use crate::commands::{
+    error::{CommandError, CommandResult},
+    screenshot_vault::load_image,
+};
+use runtime::AiRuntime;
+use tauri::Manager;
 
 #[tauri::command]
 #[specta::specta]
 pub async fn call_ai(
+    app: tauri::AppHandle,
     image_path: Option<String>,
     instruction: Option<String>,
-) -> Result<String, String> {
-    tauri::async_runtime::spawn_blocking(move || {
-        generate_text_from_image(
-            &image_path.unwrap_or_default(),
-            &instruction.unwrap_or_default(),
-        )
-    })?
-    .await
-    .map_err(|e| format!("Inference failed: {:?}", e))?
+) -> CommandResult<String> {
+    tauri::async_runtime::spawn_blocking(move || -> anyhow::Result<String> {
+        let image = image_path.map(|path| load_image(&app, &path)).transpose()?;
+        app.state::<AiRuntime>()
+            .with_models(|models| models.generate_text(image.as_ref(), instruction.as_deref()))
+    })
+    .await?
+    .map_err(CommandError::from)
 }
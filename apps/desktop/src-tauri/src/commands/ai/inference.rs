@@ -1,7 +1,9 @@
+use crate::commands::error::CommandError;
 use anyhow::Result;
-use image::ImageReader;
+use image::DynamicImage;
 use ndarray::{Array2, Array3, Array4, Axis};
 use ort::{session::Session, value::Value};
+use std::path::Path;
 use tokenizers::Tokenizer;
 
 const PATH_VISION_MODEL: &str = "assets/models/onnx/vision_encoder_fp16.onnx";
@@ -12,95 +14,162 @@ const PATH_TOKENIZER: &str = "assets/models/tokenizer.json";
 const START_TOKEN_ID: i64 = 0;
 const EOS_TOKEN_ID: i64 = 2;
 const MAX_LENGTH: usize = 50;
+/// Positions available to the encoder; prompts longer than this are truncated by the model.
+pub const MAX_INPUT_TOKENS: usize = 1024;
 
-pub fn call_ai(image_path: Option<&str>, instruction: Option<&str>) -> Result<String> {
-    ort::init()
-        .with_name("recount")
-        .with_execution_providers({
-            #[cfg(target_os = "macos")]
-            {
-                use ort::execution_providers::CoreMLExecutionProvider;
+/// The loaded ONNX sessions and tokenizer.
+/// Loading takes a few seconds, so callers should keep this around (see `AiRuntime`).
+pub struct InferenceModels {
+    vision_model: Session,
+    text_model: Session,
+    decoder_model: Session,
+    tokenizer: Tokenizer,
+}
 
-                [CoreMLExecutionProvider::default().build()]
+impl InferenceModels {
+    pub fn load() -> Result<Self> {
+        for path in [
+            PATH_VISION_MODEL,
+            PATH_TEXT_MODEL,
+            PATH_DECODER_MODEL,
+            PATH_TOKENIZER,
+        ] {
+            if !Path::new(path).exists() {
+                return Err(CommandError::ModelMissing(path.to_string()).into());
             }
+        }
+
+        let environment = ort::init().with_name("recount");
+        // Elsewhere the CPU provider ort always falls back to is used
+        #[cfg(target_os = "macos")]
+        let environment = environment.with_execution_providers([
+            ort::execution_providers::CoreMLExecutionProvider::default().build(),
+        ]);
+        environment.commit()?;
+
+        let vision_model = Session::builder()?
+            .with_optimization_level(ort::session::builder::GraphOptimizationLevel::Level3)?
+            .commit_from_file(PATH_VISION_MODEL)?;
+
+        let text_model = Session::builder()?.commit_from_file(PATH_TEXT_MODEL)?;
+
+        let decoder_model = Session::builder()?
+            .with_optimization_level(ort::session::builder::GraphOptimizationLevel::Level3)?
+            .commit_from_file(PATH_DECODER_MODEL)?;
+
+        let tokenizer = Tokenizer::from_file(PATH_TOKENIZER)
+            .map_err(|e| anyhow::anyhow!("Failed to load tokenizer: {}", e))?;
+
+        Ok(Self {
+            vision_model,
+            text_model,
+            decoder_model,
+            tokenizer,
         })
-        .commit()?;
+    }
 
-    let mut vision_model = Session::builder()?
-        .with_optimization_level(ort::session::builder::GraphOptimizationLevel::Level3)?
-        .commit_from_file(PATH_VISION_MODEL)?;
+    pub fn generate_text(
+        &mut self,
+        image: Option<&DynamicImage>,
+        instruction: Option<&str>,
+    ) -> Result<String> {
+        self.generate_text_with_limit(image, instruction, MAX_LENGTH)
+    }
 
-    let mut text_model = Session::builder()?.commit_from_file(PATH_TEXT_MODEL)?;
+    pub fn generate_text_with_limit(
+        &mut self,
+        image: Option<&DynamicImage>,
+        instruction: Option<&str>,
+        max_length: usize,
+    ) -> Result<String> {
+        // --------------------------
+        // 1️⃣ Process text
+        // --------------------------
+        let text_embeddings = self.encode_text(instruction.unwrap_or(""))?;
 
-    let mut decoder_model = Session::builder()?
-        .with_optimization_level(ort::session::builder::GraphOptimizationLevel::Level3)?
-        .commit_from_file(PATH_DECODER_MODEL)?;
+        // --------------------------
+        // 2️⃣ Image -> visual embeddings, merged after the text (with modality axis)
+        // --------------------------
+        // Text-only prompts go to the decoder as they are
+        let encoder_output = match image {
+            Some(image) => merge_embeddings(&text_embeddings, &self.encode_image(image)?)?,
+            None => text_embeddings,
+        };
 
-    let tokenizer = Tokenizer::from_file(PATH_TOKENIZER)
-        .map_err(|e| anyhow::anyhow!("Failed to load tokenizer: {}", e))?;
+        // --------------------------
+        // 3️⃣ Autoregressive decoding
+        // --------------------------
+        let mut generated_ids = vec![START_TOKEN_ID];
 
-    // --------------------------
-    // 1️⃣ Image -> visual embeddings
-    // --------------------------
-    let visual_embeddings = if let Some(path) = image_path {
-        let img = image::open(path)?
+        for _ in 0..max_length {
+            let decoder_input =
+                Array2::from_shape_vec((1, generated_ids.len()), generated_ids.clone())?;
+            let outputs = self.decoder_model.run(ort::inputs![
+                Value::from_array(decoder_input)?,
+                Value::from_array(encoder_output.clone())?
+            ])?;
+
+            let logits = extract_array3(&outputs[0])?;
+            let next_token = argmax_last_token(&logits);
+            if next_token == EOS_TOKEN_ID {
+                break;
+            }
+            generated_ids.push(next_token);
+        }
+        let generated_ids_u32: Vec<u32> = generated_ids.iter().map(|&id| id as u32).collect();
+
+        let output_text = self
+            .tokenizer
+            .decode(&generated_ids_u32, true)
+            .map_err(|e| anyhow::anyhow!("Failed to decode tokens: {}", e))?;
+        Ok(output_text)
+    }
+
+    pub fn count_tokens(&self, text: &str) -> Result<usize> {
+        let encoding = self
+            .tokenizer
+            .encode(text, true)
+            .map_err(|e| anyhow::anyhow!("Failed to encode text: {}", e))?;
+        Ok(encoding.len())
+    }
+
+    /// Pooled vision encoder output for an image: the mean over the patch axis, L2-normalised.
+    /// Only comparable with other image embeddings.
+    pub fn embed_image(&mut self, image: &DynamicImage) -> Result<Vec<f32>> {
+        let features = self.encode_image(image)?;
+        Ok(mean_pool(&features))
+    }
+
+    /// Pooled input token embeddings for a text, L2-normalised. These are not in a space
+    /// shared with `embed_image`, so only compare them with other text embeddings.
+    pub fn embed_text(&mut self, text: &str) -> Result<Vec<f32>> {
+        let embeddings = self.encode_text(text)?;
+        Ok(mean_pool(&embeddings))
+    }
+
+    fn encode_image(&mut self, image: &DynamicImage) -> Result<Array3<f32>> {
+        let img = image
             .resize_exact(224, 224, image::imageops::FilterType::Triangle)
             .to_rgb8();
         let img_tensor = image_to_tensor(&img)?;
-        let outputs = vision_model.run(ort::inputs![Value::from_array(img_tensor)?])?;
-        extract_array3(&outputs[0])?
-    } else {
-        Array3::zeros((1, 1, 1))
-    };
-
-    // --------------------------
-    // 3️⃣ Process text
-    // --------------------------
-    let input_text = instruction.unwrap_or("");
-    let encoding = tokenizer
-        .encode(input_text, true)
-        .map_err(|e| anyhow::anyhow!("Failed to encode text: {}", e))?;
-    let input_ids: Vec<i64> = encoding.get_ids().iter().map(|&id| id as i64).collect();
-    let input_tensor = Array2::from_shape_vec((1, input_ids.len()), input_ids.clone())?;
-    let outputs = text_model.run(ort::inputs![Value::from_array(input_tensor)?])?;
-    let text_embeddings = extract_array3(&outputs[0])?;
-
-    // --------------------------
-    // 4️⃣ Merge embeddings (with modality axis)
-    // --------------------------
-    let encoder_output = merge_embeddings(&text_embeddings, &visual_embeddings);
-
-    // --------------------------
-    // 5️⃣ Autoregressive decoding
-    // --------------------------
-    let mut generated_ids = vec![START_TOKEN_ID];
-
-    for _ in 0..MAX_LENGTH {
-        let decoder_input =
-            Array2::from_shape_vec((1, generated_ids.len()), generated_ids.clone())?;
-        let outputs = decoder_model.run(ort::inputs![
-            Value::from_array(decoder_input)?,
-            Value::from_array(encoder_output.clone())?
-        ])?;
-
-        let logits = extract_array3(&outputs[0])?;
-        let next_token = argmax_last_token(&logits);
-        if next_token == EOS_TOKEN_ID {
-            break;
-        }
-        generated_ids.push(next_token);
+        let outputs = self
+            .vision_model
+            .run(ort::inputs![Value::from_array(img_tensor)?])?;
+        extract_array3(&outputs[0])
+    }
+
+    fn encode_text(&mut self, text: &str) -> Result<Array3<f32>> {
+        let encoding = self
+            .tokenizer
+            .encode(text, true)
+            .map_err(|e| anyhow::anyhow!("Failed to encode text: {}", e))?;
+        let input_ids: Vec<i64> = encoding.get_ids().iter().map(|&id| id as i64).collect();
+        let input_tensor = Array2::from_shape_vec((1, input_ids.len()), input_ids)?;
+        let outputs = self
+            .text_model
+            .run(ort::inputs![Value::from_array(input_tensor)?])?;
+        extract_array3(&outputs[0])
     }
-    let generated_ids_u32: Vec<u32> = generated_ids.iter().map(|&id| id as u32).collect();
-    let generated_ids_slice: &[u32] = &generated_ids_u32;
-
-    let output_text = tokenizer.decode(
-        generated_ids_slice
-            .iter()
-            .map(|&id| id as u32)
-            .collect::<Vec<_>>(),
-        true,
-    )?;
-    Ok(output_text)
 }
 
 // --------------------------
@@ -118,19 +187,48 @@ fn image_to_tensor(img: &image::RgbImage) -> Result<Array4<f32>> {
 }
 
 fn extract_array3(value: &Value) -> Result<Array3<f32>> {
-    let (_, data) = value.try_extract_tensor::<f32>()?;
-    let shape = value.try_extract_tensor::<f32>()?.0;
+    let (shape, data) = value.try_extract_tensor::<f32>()?;
+    if shape.len() != 3 {
+        return Err(CommandError::ShapeMismatch(format!(
+            "expected a rank 3 tensor, got shape {:?}",
+            shape
+        ))
+        .into());
+    }
     Ok(Array3::from_shape_vec(
         (shape[0] as usize, shape[1] as usize, shape[2] as usize),
         data.to_vec(),
     )?)
 }
 
-fn merge_embeddings(text: &Array3<f32>, visual: &Array3<f32>) -> Array3<f32> {
+/// Append the visual embeddings to the text embeddings along the sequence axis.
+/// Both need the same batch size and hidden size.
+fn merge_embeddings(text: &Array3<f32>, visual: &Array3<f32>) -> Result<Array3<f32>, CommandError> {
     // axis=1 = sequence length axis
     let mut merged = text.clone();
-    merged.append(Axis(1), visual.view()).unwrap();
-    merged
+    merged.append(Axis(1), visual.view()).map_err(|_| {
+        CommandError::ShapeMismatch(format!(
+            "cannot merge visual embeddings of shape {:?} into text embeddings of shape {:?}",
+            visual.shape(),
+            text.shape()
+        ))
+    })?;
+    Ok(merged)
+}
+
+fn mean_pool(sequence: &Array3<f32>) -> Vec<f32> {
+    // (batch, sequence, hidden) -> (hidden) for the first batch item
+    let pooled = sequence
+        .index_axis(Axis(0), 0)
+        .mean_axis(Axis(0))
+        .map(|a| a.to_vec())
+        .unwrap_or_default();
+    let norm = pooled.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        pooled.iter().map(|v| v / norm).collect()
+    } else {
+        pooled
+    }
 }
 
 fn argmax_last_token(logits: &Array3<f32>) -> i64 {
@@ -141,3 +239,28 @@ fn argmax_last_token(logits: &Array3<f32>) -> i64 {
         .unwrap()
         .0 as i64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_visual_after_text_embeddings() {
+        let text = Array3::from_elem((1, 3, 4), 1.0);
+        let visual = Array3::from_elem((1, 2, 4), 2.0);
+        let merged = merge_embeddings(&text, &visual).unwrap();
+        assert_eq!(merged.shape(), [1, 5, 4]);
+        assert_eq!(merged[[0, 2, 0]], 1.0);
+        assert_eq!(merged[[0, 3, 0]], 2.0);
+    }
+
+    #[test]
+    fn rejects_embeddings_of_another_hidden_size() {
+        let text = Array3::zeros((1, 3, 4));
+        let visual = Array3::zeros((1, 1, 1));
+        assert!(matches!(
+            merge_embeddings(&text, &visual),
+            Err(CommandError::ShapeMismatch(_))
+        ));
+    }
+}
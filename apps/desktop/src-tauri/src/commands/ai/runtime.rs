@@ -0,0 +1,26 @@
+use anyhow::Result;
+use std::sync::Mutex;
+
+use super::inference::InferenceModels;
+
+/// Shared, lazily loaded inference models.
+/// Registered as Tauri state so every command reuses the same ONNX sessions.
+#[derive(Default)]
+pub struct AiRuntime {
+    models: Mutex<Option<InferenceModels>>,
+}
+
+impl AiRuntime {
+    pub fn with_models<T>(&self, f: impl FnOnce(&mut InferenceModels) -> Result<T>) -> Result<T> {
+        let mut models = self
+            .models
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Inference runtime lock poisoned"))?;
+
+        if models.is_none() {
+            *models = Some(InferenceModels::load()?);
+        }
+
+        f(models.as_mut().expect("models are loaded above"))
+    }
+}
@@ -0,0 +1,268 @@
+pub mod manifest;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    commands::{
+        error::{CommandError, CommandResult},
+        platform::ApplicationInfo,
+        settings::{BrowserSettings, UrlPrivacy},
+    },
+    native_host::{browser_tab_states, browser_tabs_dir, BrowserTabState},
+};
+
+/// Bundle identifiers (macOS) and lowercase application names or `WM_CLASS`es (Linux)
+/// of the browsers the extension supports.
+const BROWSER_BUNDLE_IDS: [&str; 8] = [
+    "com.google.Chrome",
+    "org.chromium.Chromium",
+    "org.mozilla.firefox",
+    "com.brave.Browser",
+    "com.microsoft.edgemac",
+    "com.vivaldi.Vivaldi",
+    "com.operasoftware.Opera",
+    "company.thebrowser.Browser",
+];
+const BROWSER_NAMES: [&str; 13] = [
+    "google chrome",
+    "google-chrome",
+    "chromium",
+    "chromium-browser",
+    "firefox",
+    "brave browser",
+    "brave-browser",
+    "microsoft edge",
+    "microsoft-edge",
+    "vivaldi",
+    "vivaldi-stable",
+    "opera",
+    "arc",
+];
+
+/// The tab of the focused browser, after the privacy rules are applied.
+pub struct BrowserContext {
+    pub url: Option<String>,
+    pub domain: Option<String>,
+}
+
+fn is_browser(info: &ApplicationInfo) -> bool {
+    info.bundle_id
+        .as_deref()
+        .is_some_and(|id| BROWSER_BUNDLE_IDS.contains(&id))
+        || BROWSER_NAMES.contains(&info.app_name.to_lowercase().as_str())
+}
+
+/// Host part of a URL, without credentials or port.
+pub fn url_domain(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = match host.strip_prefix('[') {
+        // IPv6 literal
+        Some(ipv6) => ipv6.split(']').next()?,
+        None => host.split(':').next()?,
+    };
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
+
+/// The privacy of the most specific rule matching `domain` or one of its parents.
+fn privacy_for(settings: &BrowserSettings, domain: &str) -> UrlPrivacy {
+    settings
+        .domain_rules
+        .iter()
+        .filter(|rule| {
+            let rule_domain = rule.domain.trim_start_matches("*.").to_lowercase();
+            domain == rule_domain || domain.ends_with(&format!(".{}", rule_domain))
+        })
+        .max_by_key(|rule| rule.domain.len())
+        .map_or(settings.default_privacy, |rule| rule.privacy)
+}
+
+/// What of a tab the privacy rules allow to be stored: the full URL with its title, only
+/// the origin, or nothing.
+pub fn private_tab(
+    settings: &BrowserSettings,
+    url: &str,
+    title: String,
+) -> (Option<String>, Option<String>) {
+    let (Some((scheme, _)), Some(domain)) = (url.split_once("://"), url_domain(url)) else {
+        return (None, None);
+    };
+    match privacy_for(settings, &domain) {
+        UrlPrivacy::FullUrl => (Some(url.to_string()), Some(title)),
+        UrlPrivacy::DomainOnly => (Some(format!("{}://{}", scheme, domain)), None),
+        UrlPrivacy::Hidden => (None, None),
+    }
+}
+
+/// What the extension in the focused browser last reported: the host started by the
+/// browser's process when its pid is known, otherwise the latest report. Reports the
+/// extension stopped refreshing are ignored.
+fn focused_browser_state(
+    states: Vec<BrowserTabState>,
+    pid: Option<u32>,
+    now: u64,
+) -> Option<BrowserTabState> {
+    states
+        .into_iter()
+        .filter(|state| state.is_fresh(now))
+        .filter(|state| match (pid, state.browser_pid) {
+            (Some(pid), Some(browser_pid)) => pid == browser_pid,
+            _ => true,
+        })
+        .max_by_key(|state| state.updated_at)
+}
+
+/// The active tab reported by the browser extension, when the focused application is a
+/// browser. Incognito tabs and hidden domains are never returned.
+pub fn active_browser_tab(
+    settings: &BrowserSettings,
+    info: &ApplicationInfo,
+) -> CommandResult<Option<BrowserContext>> {
+    if !is_browser(info) {
+        return Ok(None);
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let states = browser_tab_states(&browser_tabs_dir()?)?;
+    let Some(state) = focused_browser_state(states, info.pid, now) else {
+        return Ok(None);
+    };
+    let Some(url) = state.url.filter(|_| state.focused && !state.incognito) else {
+        return Ok(None);
+    };
+    let Some(domain) = url_domain(&url) else {
+        return Ok(None);
+    };
+
+    Ok(match privacy_for(settings, &domain) {
+        UrlPrivacy::FullUrl => Some(BrowserContext {
+            url: Some(url),
+            domain: Some(domain),
+        }),
+        UrlPrivacy::DomainOnly => Some(BrowserContext {
+            url: None,
+            domain: Some(domain),
+        }),
+        UrlPrivacy::Hidden => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::settings::DomainRule;
+
+    fn state(browser_pid: Option<u32>, url: &str, updated_at: u64) -> BrowserTabState {
+        BrowserTabState {
+            browser_pid,
+            url: Some(url.to_string()),
+            focused: true,
+            updated_at,
+            ..Default::default()
+        }
+    }
+
+    fn focused_url(states: Vec<BrowserTabState>, pid: Option<u32>) -> Option<String> {
+        focused_browser_state(states, pid, 1_000).and_then(|state| state.url)
+    }
+
+    #[test]
+    fn reads_the_state_of_the_focused_browser() {
+        let states = || {
+            vec![
+                state(Some(10), "https://chrome.example", 900),
+                state(Some(20), "https://firefox.example", 990),
+            ]
+        };
+        // Firefox reported last, but Chrome has focus
+        assert_eq!(
+            focused_url(states(), Some(10)).as_deref(),
+            Some("https://chrome.example")
+        );
+        assert_eq!(focused_url(states(), Some(30)), None);
+        assert_eq!(
+            focused_url(states(), None).as_deref(),
+            Some("https://firefox.example")
+        );
+    }
+
+    #[test]
+    fn ignores_stale_states() {
+        let stale = 1_000 - crate::native_host::STATE_MAX_AGE_SECONDS;
+        assert_eq!(
+            focused_url(vec![state(Some(10), "https://a.example", stale)], Some(10)),
+            None
+        );
+    }
+
+    #[test]
+    fn extracts_the_domain_of_urls() {
+        let cases = [
+            ("https://Example.COM/path", Some("example.com")),
+            (
+                "https://user:pw@example.com:8443/a?b#c",
+                Some("example.com"),
+            ),
+            ("http://localhost:3000", Some("localhost")),
+            ("https://[::1]:8080/", Some("::1")),
+            ("https://example.com?q=a@b", Some("example.com")),
+            ("file:///home/me/notes.txt", None),
+            ("about:blank", None),
+            ("example.com/path", None),
+        ];
+        for (url, domain) in cases {
+            assert_eq!(url_domain(url).as_deref(), domain, "{}", url);
+        }
+    }
+
+    #[test]
+    fn applies_the_most_specific_domain_rule() {
+        let rule = |domain: &str, privacy| DomainRule {
+            domain: domain.to_string(),
+            privacy,
+        };
+        let settings = BrowserSettings {
+            default_privacy: UrlPrivacy::DomainOnly,
+            domain_rules: vec![
+                rule("*.Bank.example", UrlPrivacy::Hidden),
+                rule("open.bank.example", UrlPrivacy::FullUrl),
+                rule("github.com", UrlPrivacy::FullUrl),
+            ],
+        };
+        let cases = [
+            ("github.com", UrlPrivacy::FullUrl),
+            ("gist.github.com", UrlPrivacy::FullUrl),
+            ("bank.example", UrlPrivacy::Hidden),
+            ("login.bank.example", UrlPrivacy::Hidden),
+            ("open.bank.example", UrlPrivacy::FullUrl),
+            // Not a subdomain, only the same suffix
+            ("notgithub.com", UrlPrivacy::DomainOnly),
+            ("example.org", UrlPrivacy::DomainOnly),
+        ];
+        for (domain, privacy) in cases {
+            assert_eq!(privacy_for(&settings, domain), privacy, "{}", domain);
+        }
+    }
+
+    #[test]
+    fn keeps_only_what_the_privacy_rules_allow() {
+        let settings = |default_privacy| BrowserSettings {
+            default_privacy,
+            domain_rules: Vec::new(),
+        };
+        let tab = |privacy| private_tab(&settings(privacy), "https://a.example/x?y", "X".into());
+        assert_eq!(
+            tab(UrlPrivacy::FullUrl),
+            (Some("https://a.example/x?y".into()), Some("X".into()))
+        );
+        assert_eq!(
+            tab(UrlPrivacy::DomainOnly),
+            (Some("https://a.example".into()), None)
+        );
+        assert_eq!(tab(UrlPrivacy::Hidden), (None, None));
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    commands::error::{CommandError, CommandResult},
+    native_host::HOST_NAME,
+};
+
+/// Directories Chromium based browsers and Firefox read host manifests from, per user.
+fn manifest_dirs(home: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    #[cfg(target_os = "macos")]
+    {
+        let support = home.join("Library/Application Support");
+        (
+            [
+                "Google/Chrome",
+                "Chromium",
+                "BraveSoftware/Brave-Browser",
+                "Microsoft Edge",
+                "Vivaldi",
+                "Arc/User Data",
+            ]
+            .iter()
+            .map(|browser| support.join(browser).join("NativeMessagingHosts"))
+            .collect(),
+            vec![support.join("Mozilla/NativeMessagingHosts")],
+        )
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let config = home.join(".config");
+        (
+            [
+                "google-chrome",
+                "chromium",
+                "BraveSoftware/Brave-Browser",
+                "microsoft-edge",
+                "vivaldi",
+            ]
+            .iter()
+            .map(|browser| config.join(browser).join("NativeMessagingHosts"))
+            .collect(),
+            vec![home.join(".mozilla/native-messaging-hosts")],
+        )
+    }
+}
+
+fn write_manifest(dir: &Path, manifest: &serde_json::Value) -> CommandResult<String> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}.json", HOST_NAME));
+    let bytes =
+        serde_json::to_vec_pretty(manifest).map_err(|e| CommandError::Internal(e.to_string()))?;
+    fs::write(&path, bytes)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Register the native messaging host with every supported browser for the current user.
+/// Only the given extensions may start it. Returns the written manifest paths.
+#[tauri::command]
+#[specta::specta]
+pub fn install_browser_host(
+    chrome_extension_ids: Vec<String>,
+    firefox_extension_ids: Vec<String>,
+) -> CommandResult<Vec<String>> {
+    // The app binary is the host, see `native_host::is_browser_launch`
+    let host_path = std::env::current_exe()?;
+    let home = dirs::home_dir().ok_or_else(|| {
+        CommandError::BackendUnavailable("No home directory to install into".to_string())
+    })?;
+    let (chromium_dirs, firefox_dirs) = manifest_dirs(&home);
+
+    let chromium_manifest = serde_json::json!({
+        "name": HOST_NAME,
+        "description": "Recount browser activity",
+        "path": host_path,
+        "type": "stdio",
+        "allowed_origins": chrome_extension_ids
+            .iter()
+            .map(|id| format!("chrome-extension://{}/", id))
+            .collect::<Vec<_>>(),
+    });
+    let firefox_manifest = serde_json::json!({
+        "name": HOST_NAME,
+        "description": "Recount browser activity",
+        "path": host_path,
+        "type": "stdio",
+        "allowed_extensions": firefox_extension_ids,
+    });
+
+    // Only browsers that are installed, i.e. whose profile directory exists
+    let installed = |dir: &&PathBuf| dir.parent().is_some_and(Path::exists);
+
+    let mut written = Vec::new();
+    if !chrome_extension_ids.is_empty() {
+        for dir in chromium_dirs.iter().filter(installed) {
+            written.push(write_manifest(dir, &chromium_manifest)?);
+        }
+    }
+    if !firefox_extension_ids.is_empty() {
+        for dir in firefox_dirs.iter().filter(installed) {
+            written.push(write_manifest(dir, &firefox_manifest)?);
+        }
+    }
+    Ok(written)
+}
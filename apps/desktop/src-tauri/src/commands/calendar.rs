@@ -0,0 +1,635 @@
+pub mod ics;
+pub mod recurrence;
+
+use chrono::Duration;
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tauri::Manager;
+
+use crate::commands::{
+    classify_project::ProjectDescription,
+    error::{CommandError, CommandResult},
+};
+use ics::{parse_calendar, IcsEvent};
+use recurrence::parse_rule;
+
+const CALENDARS_DIR: &str = "calendars";
+const SOURCES_FILE: &str = "sources.json";
+/// A feed that does not answer in time is skipped, so it cannot hold up the others.
+const FETCH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Where a calendar comes from.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(tag = "kind", content = "location", rename_all = "snake_case")]
+pub enum CalendarLocation {
+    /// An imported `.ics` file; a copy is kept, so re-import it to pick up changes.
+    File(String),
+    /// An ICS feed, fetched again by `refresh_calendars`.
+    Url(String),
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, specta::Type)]
+pub struct CalendarSource {
+    pub id: String,
+    pub name: String,
+    pub location: CalendarLocation,
+}
+
+/// One occurrence of a calendar event.
+#[derive(Debug, Clone, serde::Serialize, specta::Type)]
+pub struct CalendarEvent {
+    pub calendar_id: String,
+    pub uid: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    /// Seconds since the epoch, like snapshot timestamps.
+    pub start: u32,
+    pub end: u32,
+    pub all_day: bool,
+}
+
+/// The time span of an activity block to align with the calendar.
+#[derive(Debug, Clone, serde::Deserialize, specta::Type)]
+pub struct CalendarBlock {
+    pub start: u32,
+    pub end: u32,
+}
+
+/// The event overlapping a block the most, and the project its title names.
+#[derive(Debug, Clone, serde::Serialize, specta::Type)]
+pub struct CalendarMatch {
+    pub event: CalendarEvent,
+    pub overlap_seconds: u32,
+    pub project_id: Option<String>,
+}
+
+fn calendars_dir(app: &tauri::AppHandle) -> CommandResult<PathBuf> {
+    Ok(app.path().app_local_data_dir()?.join(CALENDARS_DIR))
+}
+
+fn load_sources(app: &tauri::AppHandle) -> CommandResult<Vec<CalendarSource>> {
+    match fs::read(calendars_dir(app)?.join(SOURCES_FILE)) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map_err(|e| CommandError::Internal(format!("Corrupt calendar sources: {}", e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Write through a temporary file, so a crash never leaves a half written file behind.
+pub fn write_atomically(path: PathBuf, bytes: &[u8]) -> CommandResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+fn save_sources(app: &tauri::AppHandle, sources: &[CalendarSource]) -> CommandResult<()> {
+    let bytes =
+        serde_json::to_vec_pretty(sources).map_err(|e| CommandError::Internal(e.to_string()))?;
+    write_atomically(calendars_dir(app)?.join(SOURCES_FILE), &bytes)
+}
+
+fn cache_path(app: &tauri::AppHandle, id: &str) -> CommandResult<PathBuf> {
+    Ok(calendars_dir(app)?.join(format!("{}.ics", id)))
+}
+
+/// The calendar without the byte order mark some servers and editors write, refusing
+/// anything that is not a calendar.
+fn calendar_contents(contents: &str) -> CommandResult<&str> {
+    let contents = contents.trim_start_matches('\u{feff}');
+    if !contents.trim_start().starts_with("BEGIN:VCALENDAR") {
+        return Err(CommandError::Internal(
+            "Not an iCalendar file: it does not start with BEGIN:VCALENDAR".to_string(),
+        ));
+    }
+    Ok(contents)
+}
+
+/// Store a calendar's contents, refusing anything that is not a calendar.
+fn store_calendar(app: &tauri::AppHandle, id: &str, contents: &str) -> CommandResult<()> {
+    let contents = calendar_contents(contents)?;
+    write_atomically(cache_path(app, id)?, contents.as_bytes())
+}
+
+fn add_source(
+    app: &tauri::AppHandle,
+    name: String,
+    location: CalendarLocation,
+    contents: &str,
+) -> CommandResult<CalendarSource> {
+    let id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| format!("{:x}", d.as_nanos()))
+        .unwrap_or_default();
+    store_calendar(app, &id, contents)?;
+
+    let source = CalendarSource { id, name, location };
+    let mut sources = load_sources(app)?;
+    sources.push(source.clone());
+    save_sources(app, &sources)?;
+    Ok(source)
+}
+
+/// `webcal://` is how calendar apps advertise feeds; it is plain HTTPS.
+fn feed_url(url: &str) -> String {
+    match url.strip_prefix("webcal://") {
+        Some(rest) => format!("https://{}", rest),
+        None => url.to_string(),
+    }
+}
+
+async fn fetch_calendar(url: &str) -> CommandResult<String> {
+    let fetch_error = |e: tauri_plugin_http::reqwest::Error| {
+        CommandError::BackendUnavailable(format!("Could not fetch {}: {}", url, e))
+    };
+    tauri_plugin_http::reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .map_err(fetch_error)?
+        .get(feed_url(url))
+        .send()
+        .await
+        .map_err(fetch_error)?
+        .error_for_status()
+        .map_err(fetch_error)?
+        .text()
+        .await
+        .map_err(fetch_error)
+}
+
+fn occurrence(calendar_id: &str, event: &IcsEvent, start: i64) -> Option<CalendarEvent> {
+    let end = start + event.duration.num_seconds();
+    Some(CalendarEvent {
+        calendar_id: calendar_id.to_string(),
+        uid: event.uid.clone(),
+        summary: event.summary.clone(),
+        description: event.description.clone(),
+        location: event.location.clone(),
+        start: u32::try_from(start).ok()?,
+        end: u32::try_from(end).ok()?,
+        all_day: event.all_day,
+    })
+}
+
+/// Occurrences of the events of one calendar that overlap `[from, to)`. Recurring events
+/// are expanded in their own time zone, so they keep their wall clock time across DST.
+fn expand_events(calendar_id: &str, events: &[IcsEvent], from: u32, to: u32) -> Vec<CalendarEvent> {
+    let (from, to) = (from as i64, to as i64);
+    let overlaps = |event: &CalendarEvent| (event.start as i64) < to && (event.end as i64) > from;
+
+    // Moved or cancelled occurrences of recurring events, by uid and original start
+    let mut overrides: HashMap<&str, Vec<i64>> = HashMap::new();
+    for event in events {
+        if let Some(recurrence_id) = event.recurrence_id {
+            overrides.entry(&event.uid).or_default().push(recurrence_id);
+        }
+    }
+
+    let mut occurrences = Vec::new();
+    for event in events.iter().filter(|event| !event.cancelled) {
+        let starts = match (&event.rrule, event.recurrence_id) {
+            (Some(rule), None) => {
+                let Some(rule) = parse_rule(rule, &event.zone) else {
+                    // A rule we cannot expand still has its first occurrence
+                    occurrences.extend(
+                        event
+                            .zone
+                            .timestamp(event.start)
+                            .and_then(|start| occurrence(calendar_id, event, start))
+                            .filter(overlaps),
+                    );
+                    continue;
+                };
+                // Expand a day past the window, since zone offsets shift the wall clock
+                let Some(limit) = event
+                    .zone
+                    .local_time(to)
+                    .map(|limit| limit + Duration::days(1))
+                else {
+                    continue;
+                };
+                let replaced = overrides
+                    .get(event.uid.as_str())
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                rule.occurrences(event.start, limit)
+                    .into_iter()
+                    .filter_map(|start| event.zone.timestamp(start))
+                    .filter(|start| !event.exdates.contains(start) && !replaced.contains(start))
+                    .collect()
+            }
+            _ => event
+                .zone
+                .timestamp(event.start)
+                .into_iter()
+                .collect::<Vec<_>>(),
+        };
+        occurrences.extend(
+            starts
+                .into_iter()
+                .filter_map(|start| occurrence(calendar_id, event, start))
+                .filter(overlaps),
+        );
+    }
+    occurrences.sort_by_key(|event| event.start);
+    occurrences
+}
+
+/// All calendar events overlapping `[from, to)`, in seconds since the epoch.
+pub fn events_between(
+    app: &tauri::AppHandle,
+    from: u32,
+    to: u32,
+) -> CommandResult<Vec<CalendarEvent>> {
+    let mut events = Vec::new();
+    for source in load_sources(app)? {
+        let contents = match fs::read_to_string(cache_path(app, &source.id)?) {
+            Ok(contents) => contents,
+            // A feed that has never been fetched successfully
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        events.extend(expand_events(
+            &source.id,
+            &parse_calendar(&contents),
+            from,
+            to,
+        ));
+    }
+    events.sort_by_key(|event| event.start);
+    Ok(events)
+}
+
+/// The project whose name appears in the event's summary, or else its description.
+/// The longest name wins, so "Project X2" is preferred over "Project X".
+fn project_for_event(event: &CalendarEvent, projects: &[ProjectDescription]) -> Option<String> {
+    let texts = [Some(&event.summary), event.description.as_ref()];
+    texts.into_iter().flatten().find_map(|text| {
+        let text = text.to_lowercase();
+        projects
+            .iter()
+            .filter(|project| !project.name.is_empty())
+            .filter(|project| text.contains(&project.name.to_lowercase()))
+            .max_by_key(|project| project.name.len())
+            .map(|project| project.id.clone())
+    })
+}
+
+/// Align activity blocks with the calendar: for every block, the timed event overlapping
+/// it the most, if any. All-day events are left out since they overlap everything.
+pub fn align_blocks(
+    events: &[CalendarEvent],
+    blocks: &[CalendarBlock],
+    projects: &[ProjectDescription],
+) -> Vec<Option<CalendarMatch>> {
+    blocks
+        .iter()
+        .map(|block| {
+            events
+                .iter()
+                .filter(|event| !event.all_day)
+                .map(|event| {
+                    let overlap = event
+                        .end
+                        .min(block.end)
+                        .saturating_sub(event.start.max(block.start));
+                    (event, overlap)
+                })
+                .filter(|(_, overlap)| *overlap > 0)
+                .max_by_key(|(_, overlap)| *overlap)
+                .map(|(event, overlap)| CalendarMatch {
+                    project_id: project_for_event(event, projects),
+                    event: event.clone(),
+                    overlap_seconds: overlap,
+                })
+        })
+        .collect()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn list_calendars(app: tauri::AppHandle) -> CommandResult<Vec<CalendarSource>> {
+    load_sources(&app)
+}
+
+/// Import an `.ics` file. The name defaults to the file name.
+#[tauri::command]
+#[specta::specta]
+pub fn import_calendar(
+    app: tauri::AppHandle,
+    path: String,
+    name: Option<String>,
+) -> CommandResult<CalendarSource> {
+    let contents = fs::read_to_string(&path)?;
+    let name = name.unwrap_or_else(|| {
+        PathBuf::from(&path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.clone())
+    });
+    add_source(&app, name, CalendarLocation::File(path), &contents)
+}
+
+/// Subscribe to an ICS feed (`http://`, `https://` or `webcal://`) and fetch it once.
+#[tauri::command]
+#[specta::specta]
+pub async fn subscribe_calendar(
+    app: tauri::AppHandle,
+    url: String,
+    name: Option<String>,
+) -> CommandResult<CalendarSource> {
+    let contents = fetch_calendar(&url).await?;
+    let name = name.unwrap_or_else(|| url.clone());
+    add_source(&app, name, CalendarLocation::Url(url), &contents)
+}
+
+/// Fetch every subscribed feed again. Feeds that fail keep their last contents;
+/// returns the ids of those that were updated.
+#[tauri::command]
+#[specta::specta]
+pub async fn refresh_calendars(app: tauri::AppHandle) -> CommandResult<Vec<String>> {
+    let mut refreshed = Vec::new();
+    for source in load_sources(&app)? {
+        let CalendarLocation::Url(url) = &source.location else {
+            continue;
+        };
+        if let Ok(contents) = fetch_calendar(url).await {
+            if store_calendar(&app, &source.id, &contents).is_ok() {
+                refreshed.push(source.id);
+            }
+        }
+    }
+    Ok(refreshed)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn remove_calendar(app: tauri::AppHandle, id: String) -> CommandResult<()> {
+    let mut sources = load_sources(&app)?;
+    sources.retain(|source| source.id != id);
+    save_sources(&app, &sources)?;
+    match fs::remove_file(cache_path(&app, &id)?) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn calendar_events(
+    app: tauri::AppHandle,
+    from: u32,
+    to: u32,
+) -> CommandResult<Vec<CalendarEvent>> {
+    events_between(&app, from, to)
+}
+
+/// For every block, the calendar event overlapping it the most and the project its title
+/// names, e.g. "Sprint planning – Project X" suggests Project X.
+#[tauri::command]
+#[specta::specta]
+pub fn align_calendar_events(
+    app: tauri::AppHandle,
+    blocks: Vec<CalendarBlock>,
+    projects: Vec<ProjectDescription>,
+) -> CommandResult<Vec<Option<CalendarMatch>>> {
+    let (Some(from), Some(to)) = (
+        blocks.iter().map(|block| block.start).min(),
+        blocks.iter().map(|block| block.end).max(),
+    ) else {
+        return Ok(Vec::new());
+    };
+    let events = events_between(&app, from, to)?;
+    Ok(align_blocks(&events, &blocks, &projects))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nEND:VCALENDAR\r\n";
+
+    /// Serve one HTTP response on a local port and return the feed's URL.
+    fn serve_once(status: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/calendar.ics", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let read = stream.read(&mut buffer).unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+            }
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/calendar\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        url
+    }
+
+    fn events(calendar: &str) -> Vec<IcsEvent> {
+        parse_calendar(&calendar.replace('\n', "\r\n"))
+    }
+
+    fn starts(events: &[IcsEvent], from: u32, to: u32) -> Vec<u32> {
+        expand_events("calendar", events, from, to)
+            .iter()
+            .map(|event| event.start)
+            .collect()
+    }
+
+    #[test]
+    fn fetches_a_feed_over_http() {
+        let url = serve_once("200 OK", CALENDAR);
+        let contents = tauri::async_runtime::block_on(fetch_calendar(&url)).unwrap();
+        assert_eq!(contents, CALENDAR);
+
+        let url = serve_once("404 Not Found", "");
+        assert!(matches!(
+            tauri::async_runtime::block_on(fetch_calendar(&url)),
+            Err(CommandError::BackendUnavailable(_))
+        ));
+    }
+
+    #[test]
+    fn accepts_calendars_with_a_byte_order_mark() {
+        assert_eq!(
+            calendar_contents(&format!("\u{feff}{}", CALENDAR)).unwrap(),
+            CALENDAR
+        );
+        assert!(calendar_contents("<html></html>").is_err());
+        assert_eq!(
+            feed_url("webcal://example.com/a.ics"),
+            "https://example.com/a.ics"
+        );
+    }
+
+    #[test]
+    fn keeps_the_wall_clock_time_across_dst() {
+        let events = events(
+            "BEGIN:VEVENT
+UID:standup
+DTSTART;TZID=Europe/Amsterdam:20240325T090000
+DURATION:PT15M
+RRULE:FREQ=WEEKLY;COUNT=2
+END:VEVENT",
+        );
+        // 09:00 is 08:00 UTC before the change on March 31 and 07:00 UTC after it
+        assert_eq!(
+            starts(&events, 1704067200, 1714521600),
+            vec![1711353600, 1711954800]
+        );
+    }
+
+    #[test]
+    fn skips_excluded_and_replaced_occurrences() {
+        let events = events(
+            "BEGIN:VEVENT
+UID:review
+DTSTART:20240115T090000Z
+DURATION:PT1H
+RRULE:FREQ=DAILY;COUNT=4
+EXDATE:20240118T090000Z
+END:VEVENT
+BEGIN:VEVENT
+UID:review
+RECURRENCE-ID:20240116T090000Z
+DTSTART:20240116T150000Z
+DURATION:PT1H
+END:VEVENT
+BEGIN:VEVENT
+UID:review
+RECURRENCE-ID:20240117T090000Z
+DTSTART:20240117T090000Z
+STATUS:CANCELLED
+END:VEVENT",
+        );
+        assert_eq!(
+            starts(&events, 1704067200, 1714521600),
+            vec![1705309200, 1705417200]
+        );
+        // Only occurrences overlapping the window
+        assert_eq!(starts(&events, 1705363200, 1705482000), vec![1705417200]);
+    }
+
+    fn event(summary: &str, start: u32, end: u32, all_day: bool) -> CalendarEvent {
+        CalendarEvent {
+            calendar_id: "calendar".to_string(),
+            uid: summary.to_string(),
+            summary: summary.to_string(),
+            description: None,
+            location: None,
+            start,
+            end,
+            all_day,
+        }
+    }
+
+    fn projects(names: &[(&str, &str)]) -> Vec<ProjectDescription> {
+        names
+            .iter()
+            .map(|(id, name)| {
+                serde_json::from_value(serde_json::json!({ "id": id, "name": name })).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn aligns_blocks_with_the_most_overlapping_event() {
+        let events = [
+            event("Offsite", 0, 86_400, true),
+            event("Standup", 32_400, 33_300, false),
+            event("Planning Project X2", 33_000, 36_000, false),
+        ];
+        let blocks = [
+            // Overlaps the standup by 15 minutes and planning by 20
+            CalendarBlock {
+                start: 32_400,
+                end: 34_200,
+            },
+            CalendarBlock {
+                start: 32_400,
+                end: 32_700,
+            },
+            // Only the all-day event
+            CalendarBlock {
+                start: 40_000,
+                end: 41_000,
+            },
+        ];
+        let projects = projects(&[("x", "Project X"), ("x2", "Project X2")]);
+
+        let matches = align_blocks(&events, &blocks, &projects);
+        let summaries: Vec<_> = matches
+            .iter()
+            .map(|m| {
+                m.as_ref()
+                    .map(|m| (m.event.summary.as_str(), m.overlap_seconds))
+            })
+            .collect();
+        assert_eq!(
+            summaries,
+            [
+                Some(("Planning Project X2", 1_200)),
+                Some(("Standup", 300)),
+                None
+            ]
+        );
+        // The longest project name in the summary wins
+        assert_eq!(
+            matches[0].as_ref().unwrap().project_id.as_deref(),
+            Some("x2")
+        );
+        assert_eq!(matches[1].as_ref().unwrap().project_id, None);
+    }
+
+    #[test]
+    fn finds_the_project_in_the_summary_before_the_description() {
+        let projects = projects(&[("a", "Apollo"), ("h", "Hermes"), ("empty", "")]);
+        let with_description = |summary: &str, description: Option<&str>| CalendarEvent {
+            description: description.map(str::to_string),
+            ..event(summary, 0, 60, false)
+        };
+
+        let cases = [
+            (
+                with_description("apollo sync", Some("Also Hermes")),
+                Some("a"),
+            ),
+            (
+                with_description("Weekly sync", Some("Agenda: Hermes launch")),
+                Some("h"),
+            ),
+            (with_description("Weekly sync", None), None),
+        ];
+        for (event, expected) in cases {
+            assert_eq!(
+                project_for_event(&event, &projects).as_deref(),
+                expected,
+                "{}",
+                event.summary
+            );
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Just enough of RFC 5545 to read events: unfolding, properties with parameters,
+//! dates, date-times and durations.
+//! docs: https://datatracker.ietf.org/doc/html/rfc5545
+
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// The time zone an event's times are written in.
+#[derive(Debug, Clone)]
+pub enum Zone {
+    Utc,
+    /// Floating times and all-day events follow the user's time zone.
+    Local,
+    Named(Tz),
+}
+
+impl Zone {
+    /// Zones whose `TZID` is not an IANA name, e.g. Outlook's "W. Europe Standard Time",
+    /// fall back to the user's time zone.
+    fn from_tzid(tzid: Option<&str>) -> Self {
+        match tzid.and_then(|tzid| tzid.trim_matches('"').parse().ok()) {
+            Some(tz) => Zone::Named(tz),
+            None => Zone::Local,
+        }
+    }
+
+    /// Seconds since the epoch of a wall clock time in this zone. Times skipped by a DST
+    /// change resolve to `None`, repeated ones to the first.
+    pub fn timestamp(&self, time: NaiveDateTime) -> Option<i64> {
+        match self {
+            Zone::Utc => Some(time.and_utc().timestamp()),
+            Zone::Local => Local
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|time| time.timestamp()),
+            Zone::Named(tz) => tz
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|time| time.timestamp()),
+        }
+    }
+
+    /// The wall clock time in this zone at `timestamp`.
+    pub fn local_time(&self, timestamp: i64) -> Option<NaiveDateTime> {
+        let utc = Utc.timestamp_opt(timestamp, 0).single()?;
+        Some(match self {
+            Zone::Utc => utc.naive_utc(),
+            Zone::Local => utc.with_timezone(&Local).naive_local(),
+            Zone::Named(tz) => utc.with_timezone(tz).naive_local(),
+        })
+    }
+}
+
+/// A `VEVENT`, with its times still in its own zone.
+#[derive(Debug, Clone)]
+pub struct IcsEvent {
+    pub uid: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub start: NaiveDateTime,
+    pub zone: Zone,
+    pub duration: Duration,
+    pub all_day: bool,
+    pub rrule: Option<String>,
+    /// Excluded occurrences, in seconds since the epoch.
+    pub exdates: Vec<i64>,
+    /// Set on an event that replaces one occurrence of a recurring event.
+    pub recurrence_id: Option<i64>,
+    pub cancelled: bool,
+}
+
+/// A content line: `NAME;PARAM=value:value`.
+struct Property<'a> {
+    name: String,
+    params: Vec<(String, &'a str)>,
+    value: &'a str,
+}
+
+impl Property<'_> {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| *value)
+    }
+}
+
+fn parse_property(line: &str) -> Option<Property<'_>> {
+    // The value starts at the first colon outside a quoted parameter value
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.to_ascii_uppercase();
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key.to_ascii_uppercase(), value))
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value,
+    })
+}
+
+/// Lines continued on the next line start with a space or tab.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// `20240105`, `20240105T090000` or `20240105T090000Z`. Returns the time, whether it is
+/// a date only and whether it is in UTC.
+pub fn parse_date_time(value: &str) -> Option<(NaiveDateTime, bool, bool)> {
+    let value = value.trim();
+    if value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some((date.and_hms_opt(0, 0, 0)?, true, false));
+    }
+    let (value, is_utc) = match value.strip_suffix('Z') {
+        Some(value) => (value, true),
+        None => (value, false),
+    };
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some((time, false, is_utc))
+}
+
+/// `P1D`, `PT1H30M`, `P2W`. Negative durations are not meaningful for events.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim().trim_start_matches('+').strip_prefix('P')?;
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            unit => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                total += match unit {
+                    'W' => Duration::weeks(n),
+                    'D' => Duration::days(n),
+                    'H' => Duration::hours(n),
+                    'M' => Duration::minutes(n),
+                    'S' => Duration::seconds(n),
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(total)
+}
+
+/// A date or date-time property as a timestamp, in the zone its `TZID` names.
+fn property_timestamp(property: &Property, value: &str) -> Option<i64> {
+    let (time, _, is_utc) = parse_date_time(value)?;
+    let zone = if is_utc {
+        Zone::Utc
+    } else {
+        Zone::from_tzid(property.param("TZID"))
+    };
+    zone.timestamp(time)
+}
+
+#[derive(Default)]
+struct EventBuilder {
+    uid: Option<String>,
+    summary: Option<String>,
+    description: Option<String>,
+    location: Option<String>,
+    start: Option<(NaiveDateTime, Zone, bool)>,
+    end: Option<i64>,
+    duration: Option<Duration>,
+    rrule: Option<String>,
+    exdates: Vec<i64>,
+    recurrence_id: Option<i64>,
+    cancelled: bool,
+}
+
+impl EventBuilder {
+    fn add(&mut self, property: Property) {
+        match property.name.as_str() {
+            "UID" => self.uid = Some(property.value.to_string()),
+            "SUMMARY" => self.summary = Some(unescape(property.value)),
+            "DESCRIPTION" => self.description = Some(unescape(property.value)),
+            "LOCATION" => self.location = Some(unescape(property.value)),
+            "DTSTART" => {
+                self.start = parse_date_time(property.value).map(|(time, all_day, is_utc)| {
+                    let zone = match (is_utc, all_day) {
+                        (true, _) => Zone::Utc,
+                        (false, true) => Zone::Local,
+                        (false, false) => Zone::from_tzid(property.param("TZID")),
+                    };
+                    (time, zone, all_day)
+                })
+            }
+            "DTEND" => self.end = property_timestamp(&property, property.value),
+            "DURATION" => self.duration = parse_duration(property.value),
+            "RRULE" => self.rrule = Some(property.value.to_string()),
+            "EXDATE" => self.exdates.extend(
+                property
+                    .value
+                    .split(',')
+                    .filter_map(|value| property_timestamp(&property, value)),
+            ),
+            "RECURRENCE-ID" => self.recurrence_id = property_timestamp(&property, property.value),
+            "STATUS" => self.cancelled = property.value.eq_ignore_ascii_case("CANCELLED"),
+            _ => {}
+        }
+    }
+
+    fn build(self) -> Option<IcsEvent> {
+        let (start, zone, all_day) = self.start?;
+        let start_timestamp = zone.timestamp(start)?;
+        let duration = match (self.end, self.duration) {
+            (Some(end), _) => Duration::seconds((end - start_timestamp).max(0)),
+            (None, Some(duration)) => duration,
+            (None, None) if all_day => Duration::days(1),
+            (None, None) => Duration::zero(),
+        };
+        Some(IcsEvent {
+            uid: self.uid.unwrap_or_default(),
+            summary: self.summary.unwrap_or_default(),
+            description: self.description,
+            location: self.location,
+            start,
+            zone,
+            duration,
+            all_day,
+            rrule: self.rrule,
+            exdates: self.exdates,
+            recurrence_id: self.recurrence_id,
+            cancelled: self.cancelled,
+        })
+    }
+}
+
+/// The events of a calendar. Events without a start are skipped.
+pub fn parse_calendar(text: &str) -> Vec<IcsEvent> {
+    let mut events = Vec::new();
+    let mut event: Option<EventBuilder> = None;
+    // Alarms nested in an event have properties of their own
+    let mut nested = 0;
+
+    for line in unfold(text) {
+        let Some(property) = parse_property(&line) else {
+            continue;
+        };
+        match (
+            property.name.as_str(),
+            property.value.to_ascii_uppercase().as_str(),
+        ) {
+            ("BEGIN", "VEVENT") => {
+                event = Some(EventBuilder::default());
+                nested = 0;
+            }
+            ("END", "VEVENT") => events.extend(event.take().and_then(EventBuilder::build)),
+            ("BEGIN", _) if event.is_some() => nested += 1,
+            ("END", _) if event.is_some() => nested -= 1,
+            _ => {
+                if let Some(event) = event.as_mut().filter(|_| nested == 0) {
+                    event.add(property);
+                }
+            }
+        }
+    }
+    events
+}
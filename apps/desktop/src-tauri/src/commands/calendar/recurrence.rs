@@ -0,0 +1,380 @@
+//! Expansion of `RRULE`s with the parts calendar apps write: `FREQ`, `INTERVAL`, `COUNT`,
+//! `UNTIL`, `BYDAY` (with ordinals), `BYMONTHDAY` and `BYMONTH`.
+//! docs: https://datatracker.ietf.org/doc/html/rfc5545#section-3.3.10
+
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveDateTime, Weekday};
+
+use super::ics::{parse_date_time, Zone};
+
+/// Periods looked at before giving up, so a rule that never matches cannot loop forever.
+const MAX_PERIODS: u32 = 50_000;
+/// Feeds are untrusted; no calendar app writes intervals anywhere near this.
+const MAX_INTERVAL: u32 = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecurrenceRule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<u32>,
+    /// The last possible occurrence, as wall clock time in the event's zone.
+    until: Option<NaiveDateTime>,
+    /// Weekdays, optionally the nth (or nth from last, when negative) in the month or year.
+    by_day: Vec<(Option<i32>, Weekday)>,
+    by_month_day: Vec<i32>,
+    by_month: Vec<u32>,
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    Some(match day {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+/// `2TU`, `-1FR` or `MO`.
+fn parse_by_day(value: &str) -> Option<(Option<i32>, Weekday)> {
+    let split = value.len().checked_sub(2)?;
+    let (ordinal, day) = value.split_at(split);
+    let ordinal = match ordinal {
+        "" => None,
+        ordinal => Some(ordinal.trim_start_matches('+').parse().ok()?),
+    };
+    Some((ordinal, parse_weekday(day)?))
+}
+
+/// Parse an `RRULE` value. `UNTIL` is converted to wall clock time in the event's `zone`.
+/// Rules with parts we do not understand, like `BYSETPOS` or `FREQ=HOURLY`, are rejected
+/// rather than expanded wrongly.
+pub fn parse_rule(rule: &str, zone: &Zone) -> Option<RecurrenceRule> {
+    let mut frequency = None;
+    let mut parsed = RecurrenceRule {
+        frequency: Frequency::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+        by_day: Vec::new(),
+        by_month_day: Vec::new(),
+        by_month: Vec::new(),
+    };
+
+    for part in rule.split(';').filter(|part| !part.is_empty()) {
+        let (key, value) = part.split_once('=')?;
+        let values = || value.split(',');
+        match key.to_ascii_uppercase().as_str() {
+            "FREQ" => {
+                frequency = Some(match value.to_ascii_uppercase().as_str() {
+                    "DAILY" => Frequency::Daily,
+                    "WEEKLY" => Frequency::Weekly,
+                    "MONTHLY" => Frequency::Monthly,
+                    "YEARLY" => Frequency::Yearly,
+                    _ => return None,
+                })
+            }
+            "INTERVAL" => {
+                parsed.interval = value
+                    .parse()
+                    .ok()
+                    .filter(|n| (1..=MAX_INTERVAL).contains(n))?
+            }
+            "COUNT" => parsed.count = Some(value.parse().ok()?),
+            "UNTIL" => {
+                let (until, all_day, is_utc) = parse_date_time(value)?;
+                parsed.until = Some(match (is_utc, all_day) {
+                    (true, _) => Zone::Utc
+                        .timestamp(until)
+                        .and_then(|timestamp| zone.local_time(timestamp))?,
+                    // A date includes the whole day
+                    (false, true) => until.date().and_hms_opt(23, 59, 59)?,
+                    (false, false) => until,
+                });
+            }
+            "BYDAY" => parsed.by_day = values().map(parse_by_day).collect::<Option<_>>()?,
+            "BYMONTHDAY" => {
+                parsed.by_month_day = values()
+                    .map(|day| day.parse().ok())
+                    .collect::<Option<_>>()?
+            }
+            "BYMONTH" => {
+                parsed.by_month = values()
+                    .map(|month| month.parse().ok())
+                    .collect::<Option<_>>()?
+            }
+            // The week start only matters for `BYWEEKNO` and intervals of weekly rules with
+            // several days; Monday is the default and by far the most common
+            "WKST" => {}
+            _ => return None,
+        }
+    }
+    parsed.frequency = frequency?;
+    Some(parsed)
+}
+
+/// `None` past the range of dates chrono supports.
+fn days_in_month(year: i32, month: u32) -> Option<u32> {
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let next = first.checked_add_months(Months::new(1))?;
+    Some((next - first).num_days() as u32)
+}
+
+/// All days of the month.
+fn month_days(year: i32, month: u32) -> Option<Vec<NaiveDate>> {
+    Some(
+        (1..=days_in_month(year, month)?)
+            .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+            .collect(),
+    )
+}
+
+/// Whether `day` is one of `by_day` within `scope`, the days ordinals count in.
+fn matches_by_day(by_day: &[(Option<i32>, Weekday)], day: NaiveDate, scope: &[NaiveDate]) -> bool {
+    by_day.iter().any(|&(ordinal, weekday)| {
+        if day.weekday() != weekday {
+            return false;
+        }
+        let Some(ordinal) = ordinal else {
+            return true;
+        };
+        let same_weekday: Vec<&NaiveDate> =
+            scope.iter().filter(|d| d.weekday() == weekday).collect();
+        let index = if ordinal > 0 {
+            ordinal as usize - 1
+        } else {
+            match same_weekday
+                .len()
+                .checked_sub(ordinal.unsigned_abs() as usize)
+            {
+                Some(index) => index,
+                None => return false,
+            }
+        };
+        same_weekday.get(index) == Some(&&day)
+    })
+}
+
+fn matches_month_day(by_month_day: &[i32], day: NaiveDate) -> bool {
+    let Some(length) = days_in_month(day.year(), day.month()) else {
+        return false;
+    };
+    let length = length as i32;
+    by_month_day.iter().any(|&n| {
+        let n = if n < 0 { length + n + 1 } else { n };
+        day.day() as i32 == n
+    })
+}
+
+impl RecurrenceRule {
+    /// The days of the `index`th period after the one containing `start`, `None` once that
+    /// is past the range of dates chrono supports.
+    fn period_days(&self, start: NaiveDate, index: u32) -> Option<Vec<Vec<NaiveDate>>> {
+        let step = index.checked_mul(self.interval)?;
+        Some(match self.frequency {
+            Frequency::Daily => vec![vec![start.checked_add_days(Days::new(step as u64))?]],
+            Frequency::Weekly => {
+                let monday = start
+                    .checked_sub_days(Days::new(start.weekday().num_days_from_monday() as u64))?
+                    .checked_add_days(Days::new(step as u64 * 7))?;
+                vec![(0..7)
+                    .map(|day| monday.checked_add_days(Days::new(day)))
+                    .collect::<Option<_>>()?]
+            }
+            Frequency::Monthly => {
+                let month = start.with_day(1)?.checked_add_months(Months::new(step))?;
+                vec![month_days(month.year(), month.month())?]
+            }
+            Frequency::Yearly => {
+                let year = start.year().checked_add(i32::try_from(step).ok()?)?;
+                let months = (1..=12)
+                    .map(|month| month_days(year, month))
+                    .collect::<Option<Vec<_>>>()?;
+                // Ordinals in `BYDAY` count within the month when `BYMONTH` is given,
+                // otherwise within the year
+                if self.by_month.is_empty() && !self.by_day.is_empty() {
+                    vec![months.concat()]
+                } else {
+                    months
+                }
+            }
+        })
+    }
+
+    /// The occurrence days of one period, in order, with the defaults `DTSTART` implies
+    /// when a rule has no `BY*` parts.
+    fn select(&self, start: NaiveDate, scopes: Vec<Vec<NaiveDate>>) -> Vec<NaiveDate> {
+        let by_day = match self.frequency {
+            Frequency::Weekly if self.by_day.is_empty() => vec![(None, start.weekday())],
+            // Ordinals mean nothing within a single day
+            Frequency::Daily => self.by_day.iter().map(|&(_, day)| (None, day)).collect(),
+            _ => self.by_day.clone(),
+        };
+        let by_month_day = match self.frequency {
+            Frequency::Monthly | Frequency::Yearly
+                if self.by_day.is_empty() && self.by_month_day.is_empty() =>
+            {
+                vec![start.day() as i32]
+            }
+            _ => self.by_month_day.clone(),
+        };
+        let by_month = match self.frequency {
+            Frequency::Yearly
+                if self.by_month.is_empty()
+                    && self.by_day.is_empty()
+                    && self.by_month_day.is_empty() =>
+            {
+                vec![start.month()]
+            }
+            _ => self.by_month.clone(),
+        };
+
+        let mut days = Vec::new();
+        for scope in &scopes {
+            days.extend(scope.iter().copied().filter(|day| {
+                (by_month.is_empty() || by_month.contains(&day.month()))
+                    && (by_month_day.is_empty() || matches_month_day(&by_month_day, *day))
+                    && (by_day.is_empty() || matches_by_day(&by_day, *day, scope))
+            }));
+        }
+        days
+    }
+
+    /// Occurrence starts from `start` up to and including `limit`, as wall clock times in
+    /// the event's zone. The first occurrence is `start` itself.
+    pub fn occurrences(&self, start: NaiveDateTime, limit: NaiveDateTime) -> Vec<NaiveDateTime> {
+        let end = self.until.map_or(limit, |until| until.min(limit));
+        if start > end {
+            return Vec::new();
+        }
+        // `DTSTART` is always the first occurrence, even when the rule would not produce it
+        let mut occurrences = vec![start];
+        let mut counted = 1;
+
+        for index in 0..MAX_PERIODS {
+            let Some(scopes) = self.period_days(start.date(), index) else {
+                break;
+            };
+            if scopes
+                .iter()
+                .flatten()
+                .all(|day| day.and_time(start.time()) > end)
+            {
+                break;
+            }
+            for day in self.select(start.date(), scopes) {
+                let occurrence = day.and_time(start.time());
+                if occurrence <= start {
+                    continue;
+                }
+                if occurrence > end || self.count.is_some_and(|count| counted >= count) {
+                    return occurrences;
+                }
+                counted += 1;
+                occurrences.push(occurrence);
+            }
+        }
+        occurrences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(value: &str) -> NaiveDateTime {
+        parse_date_time(value).unwrap().0
+    }
+
+    fn expand(rule: &str, start: &str, limit: &str) -> Vec<NaiveDateTime> {
+        parse_rule(rule, &Zone::Utc)
+            .unwrap()
+            .occurrences(time(start), time(limit))
+    }
+
+    fn times(values: &[&str]) -> Vec<NaiveDateTime> {
+        values.iter().map(|value| time(value)).collect()
+    }
+
+    #[test]
+    fn expands_byday_ordinals() {
+        assert_eq!(
+            expand(
+                "FREQ=MONTHLY;BYDAY=2TU;COUNT=3",
+                "20240109T090000",
+                "20241231T000000"
+            ),
+            times(&["20240109T090000", "20240213T090000", "20240312T090000"])
+        );
+        assert_eq!(
+            expand(
+                "FREQ=MONTHLY;BYDAY=-1FR",
+                "20240126T090000",
+                "20240331T000000"
+            ),
+            times(&["20240126T090000", "20240223T090000", "20240329T090000"])
+        );
+        // Without `BYMONTH` a yearly ordinal counts within the year
+        assert_eq!(
+            expand(
+                "FREQ=YEARLY;BYDAY=1MO;COUNT=2",
+                "20240101T090000",
+                "20301231T000000"
+            ),
+            times(&["20240101T090000", "20250106T090000"])
+        );
+    }
+
+    #[test]
+    fn expands_weekly_days_until() {
+        assert_eq!(
+            expand(
+                "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE;UNTIL=20240118T000000Z",
+                "20240101T090000",
+                "20241231T000000"
+            ),
+            times(&[
+                "20240101T090000",
+                "20240103T090000",
+                "20240115T090000",
+                "20240117T090000"
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_and_oversized_rules() {
+        assert!(parse_rule("FREQ=HOURLY", &Zone::Utc).is_none());
+        assert!(parse_rule("FREQ=MONTHLY;BYSETPOS=-1", &Zone::Utc).is_none());
+        assert!(parse_rule("FREQ=DAILY;INTERVAL=0", &Zone::Utc).is_none());
+        assert!(parse_rule("FREQ=YEARLY;INTERVAL=300000", &Zone::Utc).is_none());
+        assert!(parse_rule("FREQ=WEEKLY;INTERVAL=4000000000", &Zone::Utc).is_none());
+    }
+
+    #[test]
+    fn stops_at_the_end_of_the_calendar() {
+        let start = time("20240101T090000");
+        for rule in [
+            "FREQ=DAILY;INTERVAL=1000",
+            "FREQ=WEEKLY;INTERVAL=1000",
+            "FREQ=MONTHLY;INTERVAL=1000",
+            "FREQ=YEARLY;INTERVAL=1000",
+            "FREQ=YEARLY;INTERVAL=1000;BYDAY=-1SU",
+        ] {
+            let occurrences = parse_rule(rule, &Zone::Utc)
+                .unwrap()
+                .occurrences(start, NaiveDateTime::MAX);
+            assert!(occurrences.len() > 1, "{}", rule);
+        }
+    }
+}
@@ -1,35 +1,328 @@
-use crate::commands::platform::{PlatformImpl, WindowActivityCapture};
+use image::DynamicImage;
+use tauri::Manager;
+
+use crate::{
+    activity_log::{self, ActivityRecord},
+    commands::{
+        browser_activity::active_browser_tab,
+        error::{CommandError, CommandResult},
+        git_repository::{resolve_git_repository, GitRepository},
+        issue_references::{extract_issue_references, IssueReference},
+        meeting_detection::{update_meeting, Meeting},
+        platform::{
+            ApplicationInfo, DisplayCapture, PlatformImpl, ScreenRect, WindowActivityCapture,
+        },
+        redaction::{pixel_bounds, redact_screenshots},
+        screenshot_dedup::save_deduplicated_screenshots,
+        screenshot_vault::DataCipher,
+        settings::{current_settings, CaptureMode, Settings},
+        title_context::{TitleContext, TitleParserRegistry},
+        tracking_pause::current_pause,
+    },
+};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Outcome of capturing a single field of a snapshot.
+#[derive(serde::Serialize, specta::Type)]
+#[serde(tag = "status", content = "error", rename_all = "snake_case")]
+pub enum CaptureStatus {
+    Captured,
+    Failed(CommandError),
+    /// Not attempted, because tracking is paused.
+    Skipped,
+}
+
+impl<T> From<&Result<T, CommandError>> for CaptureStatus {
+    fn from(result: &Result<T, CommandError>) -> Self {
+        match result {
+            Ok(_) => Self::Captured,
+            Err(e) => Self::Failed(e.clone()),
+        }
+    }
+}
+
+#[derive(serde::Serialize, specta::Type)]
+pub struct CaptureStatuses {
+    application: CaptureStatus,
+    idle_time: CaptureStatus,
+    screenshots: CaptureStatus,
+    /// Appending the snapshot to the local activity log.
+    activity_log: CaptureStatus,
+}
+
+/// What the user was doing, which decides the kind of activity block a snapshot ends up in.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, specta::Type,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    #[default]
+    Active,
+    Idle,
+    /// In a call. Takes precedence over idle, since watching a call involves no input.
+    Meeting,
+    /// Tracking was paused; nothing was captured.
+    Paused,
+}
+
 #[derive(serde::Serialize, specta::Type)]
 pub struct WindowActivitySnapshot {
     timestamp: u32,
-    application_name: String,
-    window_title: String,
-    idle_time_seconds: u32,
+    application_name: Option<String>,
+    window_title: Option<String>,
+    /// Stable identifiers of the focused application and window, for matching rules.
+    pid: Option<u32>,
+    executable_path: Option<String>,
+    /// Bundle identifier on macOS, desktop file id on Linux.
+    bundle_id: Option<String>,
+    window_id: Option<u32>,
+    window_bounds: Option<ScreenRect>,
+    display_id: Option<u32>,
+    is_fullscreen: Option<bool>,
+    /// The active tab when a browser is focused, subject to the per-domain privacy rules.
+    url: Option<String>,
+    domain: Option<String>,
+    /// Project, file, branch or ssh host parsed from an editor or terminal window title.
+    title_context: Option<TitleContext>,
+    /// The git repository an editor or terminal is working in.
+    repository: Option<GitRepository>,
+    /// Tickets named in the window title, git branch or browser URL.
+    issues: Vec<IssueReference>,
+    idle_time_seconds: Option<u32>,
+    meeting: Option<Meeting>,
+    kind: ActivityKind,
     screenshot_paths: Vec<String>,
+    /// How much the screen changed since the previous snapshot, from 0 to 1.
+    screen_change: Option<f32>,
+    capture_status: CaptureStatuses,
+}
+
+/// Capture the displays the capture mode asks for, redacted.
+/// Returns the images keyed by display id.
+fn capture_screenshots(
+    settings: &Settings,
+    app_info: Option<&ApplicationInfo>,
+) -> CommandResult<Vec<(u32, DynamicImage)>> {
+    let mode = settings.capture.mode;
+    let display_id = match mode {
+        CaptureMode::AllDisplays => None,
+        CaptureMode::ActiveDisplay | CaptureMode::ActiveWindow => {
+            Some(app_info.and_then(|info| info.display_id).ok_or_else(|| {
+                CommandError::NoActiveWindow(
+                    "The display of the focused window is unknown".to_string(),
+                )
+            })?)
+        }
+    };
+
+    let captures = redact_screenshots(
+        &settings.redaction,
+        PlatformImpl::capture_screenshots(display_id)?,
+    )?;
+    if mode != CaptureMode::ActiveWindow {
+        return Ok(captures
+            .into_iter()
+            .map(|capture| (capture.display_id, capture.image))
+            .collect());
+    }
+
+    // Redaction regions are relative to the whole display, so crop afterwards
+    let window_bounds = app_info
+        .and_then(|info| info.window_bounds)
+        .ok_or_else(|| {
+            CommandError::NoActiveWindow("The focused window has no bounds".to_string())
+        })?;
+    captures
+        .into_iter()
+        .map(|capture: DisplayCapture| {
+            let (x, y, width, height) = pixel_bounds(
+                &capture.image,
+                &capture.to_image_rect(&window_bounds),
+            )
+            .ok_or_else(|| {
+                CommandError::NoActiveWindow("The focused window is not on its display".to_string())
+            })?;
+            Ok((
+                capture.display_id,
+                capture.image.crop_imm(x, y, width, height),
+            ))
+        })
+        .collect()
+}
+
+impl From<&WindowActivitySnapshot> for ActivityRecord {
+    fn from(snapshot: &WindowActivitySnapshot) -> Self {
+        Self {
+            timestamp: snapshot.timestamp,
+            kind: snapshot.kind,
+            application_name: snapshot.application_name.clone(),
+            window_title: snapshot.window_title.clone(),
+            url: snapshot.url.clone(),
+        }
+    }
+}
+
+/// A snapshot taken while tracking is paused, with nothing captured.
+fn paused_snapshot(timestamp: u32) -> WindowActivitySnapshot {
+    WindowActivitySnapshot {
+        timestamp,
+        application_name: None,
+        window_title: None,
+        pid: None,
+        executable_path: None,
+        bundle_id: None,
+        window_id: None,
+        window_bounds: None,
+        display_id: None,
+        is_fullscreen: None,
+        url: None,
+        domain: None,
+        title_context: None,
+        repository: None,
+        issues: Vec::new(),
+        idle_time_seconds: None,
+        meeting: None,
+        kind: ActivityKind::Paused,
+        screenshot_paths: Vec::new(),
+        screen_change: None,
+        capture_status: CaptureStatuses {
+            application: CaptureStatus::Skipped,
+            idle_time: CaptureStatus::Skipped,
+            screenshots: CaptureStatus::Skipped,
+            activity_log: CaptureStatus::Skipped,
+        },
+    }
 }
 
+/// Capture what the user is doing now. Runs on a blocking thread, since screenshots,
+/// OCR and process lookups take a while.
 #[tauri::command]
 #[specta::specta]
-pub fn capture_window_activity(app: tauri::AppHandle) -> WindowActivitySnapshot {
+pub async fn capture_window_activity(
+    app: tauri::AppHandle,
+) -> CommandResult<WindowActivitySnapshot> {
+    Ok(tauri::async_runtime::spawn_blocking(move || capture_snapshot(&app)).await?)
+}
+
+fn capture_snapshot(app: &tauri::AppHandle) -> WindowActivitySnapshot {
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map(|d| d.as_secs() as u32)
         .unwrap_or(0);
+    if current_pause(app).is_some() {
+        return paused_snapshot(timestamp);
+    }
+    // Read once, so every part of the snapshot follows the same settings
+    let settings = current_settings(app);
 
     let app_info = PlatformImpl::get_active_application();
-    let application_name = app_info.app_name;
-    let window_title = app_info.window_title;
+    let idle_time = PlatformImpl::get_idle_time_seconds();
+    let screenshots = settings.clone().and_then(|settings| {
+        let images = capture_screenshots(&settings, app_info.as_ref().ok())?;
+        save_deduplicated_screenshots(app, &settings.capture, timestamp, images)
+    });
 
-    let idle_time_seconds = PlatformImpl::get_idle_time_seconds();
-    let screenshot_paths = PlatformImpl::capture_screenshots(&app, timestamp);
+    let capture_status = CaptureStatuses {
+        application: (&app_info).into(),
+        idle_time: (&idle_time).into(),
+        screenshots: (&screenshots).into(),
+        // Set once the snapshot is logged below
+        activity_log: CaptureStatus::Skipped,
+    };
 
-    WindowActivitySnapshot {
+    let info = app_info.as_ref().ok();
+    let settings = settings.ok();
+    let browser_tab = info
+        .zip(settings.as_ref())
+        .and_then(|(info, settings)| active_browser_tab(&settings.browser, info).ok().flatten());
+    let title_parsers = app.state::<TitleParserRegistry>();
+    let title_context = info.and_then(|info| title_parsers.parse(info));
+    let repository = info
+        .filter(|info| title_parsers.handles(info))
+        .and_then(|info| resolve_git_repository(info, title_context.as_ref()));
+    let branch = repository
+        .as_ref()
+        .and_then(|repository| repository.branch.as_deref())
+        .or_else(|| title_context.as_ref()?.branch.as_deref());
+    let issues = settings
+        .as_ref()
+        .map(|settings| {
+            extract_issue_references(
+                &settings.issues,
+                info.map(|info| info.window_title.as_str()),
+                branch,
+                browser_tab.as_ref().and_then(|tab| tab.url.as_deref()),
+            )
+        })
+        .unwrap_or_default();
+    let meeting = update_meeting(app);
+    let idle_after_seconds = settings
+        .as_ref()
+        .map_or(u32::MAX, |settings| settings.capture.idle_after_seconds);
+    let kind = match (&meeting, &idle_time) {
+        (Some(_), _) => ActivityKind::Meeting,
+        (None, Ok(idle)) if *idle >= idle_after_seconds => ActivityKind::Idle,
+        _ => ActivityKind::Active,
+    };
+    let (screenshot_paths, screen_change) = match screenshots {
+        Ok(screenshots) => (screenshots.paths, screenshots.screen_change),
+        Err(_) => (Vec::new(), None),
+    };
+
+    let mut snapshot = WindowActivitySnapshot {
         timestamp,
-        application_name,
-        window_title,
-        idle_time_seconds,
+        application_name: info.map(|info| info.app_name.clone()),
+        window_title: info.map(|info| info.window_title.clone()),
+        pid: info.and_then(|info| info.pid),
+        executable_path: info.and_then(|info| info.executable_path.clone()),
+        bundle_id: info.and_then(|info| info.bundle_id.clone()),
+        window_id: info.and_then(|info| info.window_id),
+        window_bounds: info.and_then(|info| info.window_bounds),
+        display_id: info.and_then(|info| info.display_id),
+        is_fullscreen: info.map(|info| info.is_fullscreen),
+        url: browser_tab.as_ref().and_then(|tab| tab.url.clone()),
+        domain: browser_tab.and_then(|tab| tab.domain),
+        title_context,
+        repository,
+        issues,
+        idle_time_seconds: idle_time.ok(),
+        meeting,
+        kind,
         screenshot_paths,
+        screen_change,
+        capture_status,
+    };
+    // Without settings it is unknown which apps are excluded, so nothing is logged
+    let logged = match &settings {
+        Some(settings) => log_activity(app, &snapshot, &settings.redaction.excluded_apps),
+        None => Err(CommandError::Internal(
+            "Settings are unavailable".to_string(),
+        )),
+    };
+    snapshot.capture_status.activity_log = (&logged).into();
+    snapshot
+}
+
+/// Append a snapshot to the activity log. Only the application is recorded for apps
+/// excluded from screenshots, not what their window shows.
+fn log_activity(
+    app: &tauri::AppHandle,
+    snapshot: &WindowActivitySnapshot,
+    excluded_apps: &[String],
+) -> CommandResult<()> {
+    let mut record = ActivityRecord::from(snapshot);
+    let excluded = record.application_name.as_ref().is_some_and(|name| {
+        excluded_apps
+            .iter()
+            .any(|excluded| excluded.eq_ignore_ascii_case(name))
+    });
+    if excluded {
+        record.window_title = None;
+        record.url = None;
     }
+    let cipher = DataCipher::load(app)?;
+    activity_log::append(&app.path().app_local_data_dir()?, &record, |json| {
+        cipher.seal(json)
+    })?;
+    Ok(())
 }
@@ -0,0 +1,229 @@
+use crate::commands::{
+    ai::runtime::AiRuntime,
+    error::{CommandError, CommandResult},
+    search_activity::{
+        embedding_index::{cosine_similarity, Modality},
+        with_index,
+    },
+};
+use tauri::Manager;
+
+/// Softmax temperature applied to cosine similarities.
+/// Raw similarities sit in a narrow band, so they are sharpened before being read as shares.
+const SCORE_TEMPERATURE: f32 = 0.05;
+
+#[derive(serde::Deserialize, specta::Type)]
+pub struct ProjectDescription {
+    pub id: String,
+    pub name: String,
+    description: Option<String>,
+}
+
+#[derive(serde::Deserialize, specta::Type)]
+pub struct SnapshotToClassify {
+    application_name: String,
+    window_title: String,
+    screenshot_paths: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize, specta::Type)]
+pub struct ProjectCandidate {
+    project_id: String,
+    similarity: f32,
+    /// Share of this candidate among all candidates, from a softmax over the similarities.
+    /// The shares always sum to 1, so they only rank candidates and are no probability
+    /// that the candidate is right: a lone poor match scores 1.
+    relative_score: f32,
+}
+
+#[derive(Debug, serde::Serialize, specta::Type)]
+pub struct ProjectClassification {
+    /// Candidates ranked by similarity, best first.
+    candidates: Vec<ProjectCandidate>,
+    /// The best candidate, or `None` without a threshold or when its similarity is below it.
+    project_id: Option<String>,
+}
+
+/// Score a snapshot against the workspace's projects by embedding similarity. The window
+/// title and the captions `index_screenshots` stored for the screenshots are compared with
+/// the project descriptions; screenshots that were not indexed yet are left out.
+/// `threshold` is the cosine similarity the best project needs to be picked. Similarities
+/// depend on the model and on how projects are described, so there is no default: the
+/// caller calibrates it on snapshots whose project the user confirmed. Without one, the
+/// candidates are only ranked.
+#[tauri::command]
+#[specta::specta]
+pub async fn classify_project(
+    app: tauri::AppHandle,
+    snapshot: SnapshotToClassify,
+    projects: Vec<ProjectDescription>,
+    threshold: Option<f32>,
+) -> CommandResult<ProjectClassification> {
+    tauri::async_runtime::spawn_blocking(move || -> anyhow::Result<ProjectClassification> {
+        // Text only: screenshot embeddings are not comparable with text embeddings
+        let mut snapshot_embeddings = Vec::new();
+        with_index(&app, |index| {
+            snapshot_embeddings = snapshot
+                .screenshot_paths
+                .iter()
+                .filter_map(|path| index.embedding(path, Modality::Caption))
+                .map(<[f32]>::to_vec)
+                .collect();
+            false
+        })?;
+
+        let project_embeddings = app.state::<AiRuntime>().with_models(|models| {
+            snapshot_embeddings.push(models.embed_text(&format!(
+                "{} - {}",
+                snapshot.application_name, snapshot.window_title
+            ))?);
+            projects
+                .iter()
+                .map(|project| models.embed_text(&project_prompt(project)))
+                .collect::<anyhow::Result<Vec<_>>>()
+        })?;
+
+        let similarities = projects
+            .into_iter()
+            .zip(&project_embeddings)
+            .map(|(project, embedding)| {
+                let similarity = snapshot_embeddings
+                    .iter()
+                    .map(|snapshot| cosine_similarity(snapshot, embedding))
+                    .sum::<f32>()
+                    / snapshot_embeddings.len() as f32;
+                (project.id, similarity)
+            })
+            .collect();
+        Ok(classify(similarities, threshold))
+    })
+    .await?
+    .map_err(CommandError::from)
+}
+
+/// Rank projects by similarity and pick the best one when it reaches `min_similarity`.
+fn classify(
+    similarities: Vec<(String, f32)>,
+    min_similarity: Option<f32>,
+) -> ProjectClassification {
+    let values: Vec<f32> = similarities
+        .iter()
+        .map(|(_, similarity)| *similarity)
+        .collect();
+    let scores = softmax(&values, SCORE_TEMPERATURE);
+
+    let mut candidates: Vec<ProjectCandidate> = similarities
+        .into_iter()
+        .zip(scores)
+        .map(
+            |((project_id, similarity), relative_score)| ProjectCandidate {
+                project_id,
+                similarity,
+                relative_score,
+            },
+        )
+        .collect();
+    candidates.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+
+    let project_id = candidates
+        .first()
+        .filter(|best| min_similarity.is_some_and(|min| best.similarity >= min))
+        .map(|best| best.project_id.clone());
+
+    ProjectClassification {
+        candidates,
+        project_id,
+    }
+}
+
+fn project_prompt(project: &ProjectDescription) -> String {
+    match &project.description {
+        Some(description) if !description.is_empty() => {
+            format!("Working on {}: {}", project.name, description)
+        }
+        _ => format!("Working on {}", project.name),
+    }
+}
+
+fn softmax(values: &[f32], temperature: f32) -> Vec<f32> {
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = values
+        .iter()
+        .map(|v| ((v - max) / temperature).exp())
+        .collect();
+    let sum: f32 = exps.iter().sum();
+    exps.iter().map(|e| e / sum).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn similarities(values: &[(&str, f32)]) -> Vec<(String, f32)> {
+        values
+            .iter()
+            .map(|(id, similarity)| (id.to_string(), *similarity))
+            .collect()
+    }
+
+    fn ranked(classification: &ProjectClassification) -> Vec<&str> {
+        classification
+            .candidates
+            .iter()
+            .map(|candidate| candidate.project_id.as_str())
+            .collect()
+    }
+
+    const MIN_SIMILARITY: Option<f32> = Some(0.6);
+
+    #[test]
+    fn abstains_for_a_single_poor_project() {
+        // Its relative score is all of it, which must not be enough
+        let classification = classify(similarities(&[("a", 0.2)]), MIN_SIMILARITY);
+        assert!((classification.candidates[0].relative_score - 1.0).abs() < 1e-6);
+        assert_eq!(classification.project_id, None);
+
+        let classification = classify(similarities(&[("a", 0.8)]), MIN_SIMILARITY);
+        assert_eq!(classification.project_id.as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn only_ranks_without_a_threshold() {
+        let classification = classify(similarities(&[("a", 0.3), ("b", 0.95)]), None);
+        assert_eq!(ranked(&classification), ["b", "a"]);
+        assert_eq!(classification.project_id, None);
+    }
+
+    #[test]
+    fn abstains_when_every_project_is_poor() {
+        // A clear relative winner among poor matches still is a poor match
+        let classification = classify(
+            similarities(&[("a", 0.1), ("b", 0.45), ("c", 0.2)]),
+            MIN_SIMILARITY,
+        );
+        assert_eq!(ranked(&classification), ["b", "c", "a"]);
+        assert!(classification.candidates[0].relative_score > 0.9);
+        assert_eq!(classification.project_id, None);
+    }
+
+    #[test]
+    fn picks_the_most_similar_project() {
+        let classification = classify(
+            similarities(&[("a", 0.65), ("b", 0.9), ("c", 0.7)]),
+            MIN_SIMILARITY,
+        );
+        assert_eq!(ranked(&classification), ["b", "c", "a"]);
+        assert_eq!(classification.project_id.as_deref(), Some("b"));
+        let total: f32 = classification
+            .candidates
+            .iter()
+            .map(|candidate| candidate.relative_score)
+            .sum();
+        assert!((total - 1.0).abs() < 1e-5);
+
+        // The caller's calibrated threshold decides
+        let classification = classify(similarities(&[("a", 0.65)]), Some(0.7));
+        assert_eq!(classification.project_id, None);
+        assert!(classify(Vec::new(), Some(0.0)).project_id.is_none());
+    }
+}
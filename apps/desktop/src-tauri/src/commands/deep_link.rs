@@ -0,0 +1,283 @@
+//! Routing of `recount://` links, registered in `tauri.conf.json`:
+//!
+//! - `recount://auth/callback?token=…&state=…`
+//! - `recount://timer/start?project=<id>&task=<id>`
+//! - `recount://time-entries/<id>`
+//! - `recount://invitations/<id>/accept`
+
+use std::sync::Mutex;
+use tauri::{Manager, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_specta::Event;
+
+use crate::commands::{
+    error::{CommandError, CommandResult},
+    tray::show_main_window,
+};
+
+const SCHEME: &str = "recount";
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, specta::Type)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeepLinkAction {
+    /// The browser finished signing in and hands over the session token.
+    AuthCallback {
+        token: String,
+        /// Echoed back from the sign-in request, to check it came from this app.
+        state: Option<String>,
+    },
+    /// Start a timer, or switch the running one, to a project. Any web page can open such a
+    /// link, so the frontend asks the user to confirm before calling `switch_timer`.
+    StartTimer {
+        project_id: String,
+        task_id: Option<String>,
+    },
+    OpenTimeEntry {
+        time_entry_id: String,
+    },
+    AcceptInvitation {
+        invitation_id: String,
+    },
+}
+
+/// Emitted after a link was routed and the main window brought to the front.
+#[derive(Debug, Clone, serde::Serialize, specta::Type, tauri_specta::Event)]
+pub struct DeepLinkOpened {
+    action: DeepLinkAction,
+}
+
+/// Emitted for a link that could not be routed.
+#[derive(Debug, Clone, serde::Serialize, specta::Type, tauri_specta::Event)]
+pub struct DeepLinkRejected {
+    url: String,
+    error: CommandError,
+}
+
+/// The link the app was launched with, kept until the frontend is listening.
+#[derive(Default)]
+pub struct PendingDeepLink(Mutex<Option<DeepLinkAction>>);
+
+fn invalid(message: impl Into<String>) -> CommandError {
+    CommandError::InvalidDeepLink(message.into())
+}
+
+/// The ids of the core domain are UUIDs.
+pub fn is_uuid(value: &str) -> bool {
+    value.len() == 36
+        && value.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+fn query_param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+        .filter(|value| !value.is_empty())
+}
+
+fn required_param(url: &Url, name: &str) -> CommandResult<String> {
+    query_param(url, name).ok_or_else(|| invalid(format!("Missing `{}` parameter", name)))
+}
+
+fn uuid(value: String, name: &str) -> CommandResult<String> {
+    if is_uuid(&value) {
+        Ok(value.to_ascii_lowercase())
+    } else {
+        Err(invalid(format!("`{}` is not a valid {}", value, name)))
+    }
+}
+
+/// Parse a `recount://` link into the action it asks for.
+pub fn parse_deep_link(link: &str) -> CommandResult<DeepLinkAction> {
+    let url = Url::parse(link).map_err(|e| invalid(format!("{}: {}", link, e)))?;
+    if url.scheme() != SCHEME {
+        return Err(invalid(format!("Unexpected scheme `{}`", url.scheme())));
+    }
+    // `recount://auth/callback` has `auth` as its host and `/callback` as its path
+    let segments: Vec<&str> = url
+        .host_str()
+        .into_iter()
+        .chain(url.path().split('/'))
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    match segments.as_slice() {
+        ["auth", "callback"] => {
+            let token = required_param(&url, "token")?;
+            if token.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                return Err(invalid("The token contains whitespace"));
+            }
+            Ok(DeepLinkAction::AuthCallback {
+                token,
+                state: query_param(&url, "state"),
+            })
+        }
+        ["timer", "start"] => Ok(DeepLinkAction::StartTimer {
+            project_id: uuid(required_param(&url, "project")?, "project id")?,
+            task_id: query_param(&url, "task")
+                .map(|task| uuid(task, "task id"))
+                .transpose()?,
+        }),
+        ["time-entries", id] => Ok(DeepLinkAction::OpenTimeEntry {
+            time_entry_id: uuid(id.to_string(), "time entry id")?,
+        }),
+        ["invitations", id, "accept"] => Ok(DeepLinkAction::AcceptInvitation {
+            invitation_id: uuid(id.to_string(), "invitation id")?,
+        }),
+        _ => Err(invalid(format!("Unknown link `{}`", link))),
+    }
+}
+
+/// Route a link: focus the main window and tell the frontend. Nothing is carried out
+/// before the frontend has seen the action, since links can come from any web page.
+pub fn open_deep_link(app: &tauri::AppHandle, link: &str) -> CommandResult<DeepLinkAction> {
+    let result = parse_deep_link(link);
+    show_main_window(app);
+    match &result {
+        Ok(action) => {
+            let _ = DeepLinkOpened {
+                action: action.clone(),
+            }
+            .emit(app);
+        }
+        Err(error) => {
+            let _ = DeepLinkRejected {
+                url: link.to_string(),
+                error: error.clone(),
+            }
+            .emit(app);
+        }
+    }
+    result
+}
+
+/// Route links opened while the app runs, and the one it was launched with.
+pub fn route_deep_links(app: &tauri::AppHandle) {
+    let handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            let _ = open_deep_link(&handle, url.as_str());
+        }
+    });
+
+    // The frontend is not listening yet, so keep the action for `take_pending_deep_link`
+    let launched_with = app.deep_link().get_current().ok().flatten();
+    if let Some(url) = launched_with.and_then(|urls| urls.into_iter().next()) {
+        if let Ok(action) = open_deep_link(app, url.as_str()) {
+            if let Ok(mut pending) = app.state::<PendingDeepLink>().0.lock() {
+                *pending = Some(action);
+            }
+        }
+    }
+}
+
+/// The action of the link the app was launched with, once.
+#[tauri::command]
+#[specta::specta]
+pub fn take_pending_deep_link(app: tauri::AppHandle) -> CommandResult<Option<DeepLinkAction>> {
+    let state = app.state::<PendingDeepLink>();
+    let mut pending = state
+        .0
+        .lock()
+        .map_err(|_| CommandError::Internal("Deep link lock poisoned".to_string()))?;
+    Ok(pending.take())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "0b4c7f0e-5d2a-4c4e-9a51-3f1e2d3c4b5a";
+
+    fn rejected(link: &str) -> bool {
+        matches!(parse_deep_link(link), Err(CommandError::InvalidDeepLink(_)))
+    }
+
+    #[test]
+    fn parses_auth_callback() {
+        assert_eq!(
+            parse_deep_link("recount://auth/callback?token=abc.def&state=xyz").unwrap(),
+            DeepLinkAction::AuthCallback {
+                token: "abc.def".to_string(),
+                state: Some("xyz".to_string()),
+            }
+        );
+        assert_eq!(
+            parse_deep_link("recount://auth/callback?token=abc").unwrap(),
+            DeepLinkAction::AuthCallback {
+                token: "abc".to_string(),
+                state: None,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_missing_or_whitespace_token() {
+        assert!(rejected("recount://auth/callback"));
+        assert!(rejected("recount://auth/callback?token="));
+        assert!(rejected("recount://auth/callback?token=abc%20def"));
+        assert!(rejected("recount://auth/callback?token=abc+def"));
+        assert!(rejected("recount://auth/callback?token=abc%0A"));
+    }
+
+    #[test]
+    fn parses_start_timer() {
+        let upper = ID.to_ascii_uppercase();
+        assert_eq!(
+            parse_deep_link(&format!("recount://timer/start?project={}", upper)).unwrap(),
+            DeepLinkAction::StartTimer {
+                project_id: ID.to_string(),
+                task_id: None,
+            }
+        );
+        assert_eq!(
+            parse_deep_link(&format!("recount://timer/start?project={0}&task={0}", ID)).unwrap(),
+            DeepLinkAction::StartTimer {
+                project_id: ID.to_string(),
+                task_id: Some(ID.to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn parses_time_entry_and_invitation() {
+        assert_eq!(
+            parse_deep_link(&format!("recount://time-entries/{}", ID)).unwrap(),
+            DeepLinkAction::OpenTimeEntry {
+                time_entry_id: ID.to_string(),
+            }
+        );
+        assert_eq!(
+            parse_deep_link(&format!("recount://invitations/{}/accept", ID)).unwrap(),
+            DeepLinkAction::AcceptInvitation {
+                invitation_id: ID.to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_bad_uuids() {
+        assert!(rejected("recount://timer/start"));
+        assert!(rejected("recount://timer/start?project=42"));
+        assert!(rejected(&format!(
+            "recount://timer/start?project={}&task=nope",
+            ID
+        )));
+        assert!(rejected(&format!("recount://time-entries/{}0", ID)));
+        assert!(rejected(&format!(
+            "recount://invitations/{}/accept",
+            ID.replace('-', "_")
+        )));
+    }
+
+    #[test]
+    fn rejects_other_schemes_and_routes() {
+        assert!(rejected(&format!("https://timer/start?project={}", ID)));
+        assert!(rejected("not a link"));
+        assert!(rejected("recount://timer/stop"));
+        assert!(rejected(&format!("recount://invitations/{}", ID)));
+        assert!(rejected("recount://"));
+    }
+}
@@ -0,0 +1,114 @@
+/// Error returned by every command, exported to the frontend as a tagged union.
+#[derive(Debug, Clone, thiserror::Error, serde::Serialize, specta::Type)]
+#[serde(tag = "kind", content = "message")]
+pub enum CommandError {
+    /// The OS refused access, e.g. screen recording or accessibility permission is missing.
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+    /// The platform API needed for this operation is not available.
+    #[error("backend unavailable: {0}")]
+    BackendUnavailable(String),
+    /// There is no display to capture.
+    #[error("no displays: {0}")]
+    NoDisplays(String),
+    /// No application is focused.
+    #[error("no active window: {0}")]
+    NoActiveWindow(String),
+    /// A model or tokenizer file is not installed.
+    #[error("model missing: {0}")]
+    ModelMissing(String),
+    /// A tensor did not have the shape a model expects or produces.
+    #[error("shape mismatch: {0}")]
+    ShapeMismatch(String),
+    /// The secret vault has to be unlocked with the passphrase first.
+    #[error("vault locked: {0}")]
+    VaultLocked(String),
+    /// `unlock_vault` was called before a vault was created.
+    #[error("vault not found: {0}")]
+    VaultNotFound(String),
+    /// `create_vault` was called while a vault already exists.
+    #[error("vault exists: {0}")]
+    VaultExists(String),
+    #[error("invalid password: {0}")]
+    InvalidPassword(String),
+    /// Encrypting or decrypting data failed, e.g. a screenshot was tampered with.
+    #[error("crypto error: {0}")]
+    Crypto(String),
+    /// `start_timer` was called while a timer is running, like the core's
+    /// `TimeEntryAlreadyRunningError`.
+    #[error("timer already running: {0}")]
+    TimerAlreadyRunning(String),
+    /// The timer was stopped or discarded while none is running.
+    #[error("no running timer: {0}")]
+    NoRunningTimer(String),
+    /// A time entry would stop before it started.
+    #[error("stopped before started: {0}")]
+    StoppedBeforeStarted(String),
+    /// An accelerator that cannot be parsed, e.g. `Ctrl+Shift`.
+    #[error("invalid shortcut: {0}")]
+    InvalidShortcut(String),
+    /// The shortcut is bound to another action, or taken by another application.
+    #[error("shortcut conflict: {0}")]
+    ShortcutConflict(String),
+    /// A `recount://` link that is malformed or names an unknown action.
+    #[error("invalid deep link: {0}")]
+    InvalidDeepLink(String),
+    /// Command line arguments the app does not understand.
+    #[error("invalid arguments: {0}")]
+    InvalidArguments(String),
+    #[error("inference failed: {0}")]
+    Inference(String),
+    #[error("io error: {0}")]
+    Io(String),
+    #[error("internal error: {0}")]
+    Internal(String),
+}
+
+pub type CommandResult<T> = Result<T, CommandError>;
+
+impl From<std::io::Error> for CommandError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied(error.to_string()),
+            _ => Self::Io(error.to_string()),
+        }
+    }
+}
+
+impl From<tauri::Error> for CommandError {
+    fn from(error: tauri::Error) -> Self {
+        Self::Internal(error.to_string())
+    }
+}
+
+impl From<anyhow::Error> for CommandError {
+    fn from(error: anyhow::Error) -> Self {
+        if let Some(error) = error.downcast_ref::<CommandError>() {
+            return error.clone();
+        }
+        if let Some(error) = error.downcast_ref::<ort::Error>() {
+            return match error.code() {
+                ort::ErrorCode::NoSuchFile | ort::ErrorCode::NoModel => {
+                    Self::ModelMissing(error.to_string())
+                }
+                ort::ErrorCode::InvalidArgument => Self::ShapeMismatch(error.to_string()),
+                _ => Self::Inference(error.to_string()),
+            };
+        }
+        if let Some(error) = error.downcast_ref::<ndarray::ShapeError>() {
+            return Self::ShapeMismatch(error.to_string());
+        }
+        if let Some(image::ImageError::IoError(io_error)) =
+            error.downcast_ref::<image::ImageError>()
+        {
+            return Self::from(std::io::Error::new(io_error.kind(), io_error.to_string()));
+        }
+        if let Some(io_error) = error.downcast_ref::<std::io::Error>() {
+            return match io_error.kind() {
+                std::io::ErrorKind::PermissionDenied => Self::PermissionDenied(error.to_string()),
+                _ => Self::Io(error.to_string()),
+            };
+        }
+        Self::Internal(format!("{:#}", error))
+    }
+}
@@ -0,0 +1,343 @@
+use crate::commands::{browser_activity::url_domain, settings::IssueSettings};
+
+/// Prefixes that look like issue keys but are version or standard names, e.g. `UTF-8`.
+const NOT_ISSUE_PREFIXES: [&str; 14] = [
+    "UTF", "ISO", "SHA", "RFC", "CVE", "COVID", "GPT", "AES", "RSA", "HTTP", "TLS", "MD", "IPV",
+    "WIN",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueTracker {
+    Jira,
+    Linear,
+    #[serde(rename = "github")]
+    GitHub,
+}
+
+/// A ticket the user was working on, for prefilling time entries and linking to it.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, specta::Type)]
+pub struct IssueReference {
+    pub tracker: IssueTracker,
+    /// `ABC-123`, or `owner/repo#123` for GitHub.
+    pub key: String,
+    /// Link to the issue, when the tracker's address is known.
+    pub url: Option<String>,
+}
+
+/// `ABC-123` shaped keys in `text`, uppercased. Branch names are usually lowercase,
+/// so `case_insensitive` also accepts `abc-123`.
+fn find_keys(text: &str, case_insensitive: bool) -> Vec<String> {
+    let mut keys = Vec::new();
+    let words = text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '-'));
+    for word in words {
+        let segments: Vec<&str> = word.split('-').collect();
+        for pair in segments.windows(2) {
+            let (prefix, number) = (pair[0], pair[1]);
+            let is_prefix = (2..=10).contains(&prefix.len())
+                && prefix.starts_with(|c: char| c.is_ascii_alphabetic())
+                && prefix.chars().all(|c| c.is_ascii_alphanumeric())
+                && (case_insensitive || !prefix.chars().any(|c| c.is_ascii_lowercase()));
+            let is_number = (1..=9).contains(&number.len())
+                && number.chars().all(|c| c.is_ascii_digit())
+                && !number.starts_with('0');
+            let prefix = prefix.to_ascii_uppercase();
+            if is_prefix && is_number && !NOT_ISSUE_PREFIXES.contains(&prefix.as_str()) {
+                keys.push(format!("{}-{}", prefix, number));
+            }
+        }
+    }
+    keys
+}
+
+/// Whether `text` is an `owner/repo` GitHub repository name.
+fn is_repository(text: &str) -> bool {
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    matches!(text.split_once('/'), Some((owner, repo)) if valid(owner) && valid(repo))
+}
+
+fn github_reference(repository: &str, number: &str, kind: &str) -> Option<IssueReference> {
+    let number: u32 = number.parse().ok()?;
+    Some(IssueReference {
+        tracker: IssueTracker::GitHub,
+        key: format!("{}#{}", repository, number),
+        url: Some(format!(
+            "https://github.com/{}/{}/{}",
+            repository, kind, number
+        )),
+    })
+}
+
+/// `owner/repo#123`, and GitHub's page titles `… · Pull Request #123 · owner/repo`.
+fn find_github_references(text: &str) -> Vec<IssueReference> {
+    let mut references = Vec::new();
+    for word in text.split_whitespace() {
+        let word = word.trim_matches(|c: char| matches!(c, '(' | ')' | '[' | ']' | ',' | ':'));
+        if let Some((repository, number)) = word.split_once('#') {
+            if is_repository(repository) {
+                references.extend(github_reference(repository, number, "issues"));
+            }
+        }
+    }
+
+    let parts: Vec<&str> = text.split(" · ").map(str::trim).collect();
+    for pair in parts.windows(2) {
+        let kind = if pair[0].starts_with("Pull Request #") {
+            "pull"
+        } else if pair[0].starts_with("Issue #") {
+            "issues"
+        } else {
+            continue;
+        };
+        let number = pair[0].rsplit('#').next().unwrap_or_default();
+        if is_repository(pair[1]) {
+            references.extend(github_reference(pair[1], number, kind));
+        }
+    }
+    references
+}
+
+/// The tracker `key` belongs to. `require_configured` drops keys whose prefix is not a
+/// configured Jira or Linear key, for lowercase text where any `word-123` looks like one.
+fn classify_key(
+    settings: &IssueSettings,
+    key: String,
+    require_configured: bool,
+) -> Option<IssueReference> {
+    let prefix = key.split('-').next().unwrap_or_default();
+    let is_key = |keys: &[String]| keys.iter().any(|k| k.eq_ignore_ascii_case(prefix));
+
+    if is_key(&settings.linear_team_keys) {
+        let url = settings
+            .linear_workspace
+            .as_ref()
+            .map(|workspace| format!("https://linear.app/{}/issue/{}", workspace, key));
+        Some(IssueReference {
+            tracker: IssueTracker::Linear,
+            key,
+            url,
+        })
+    } else if (settings.jira_project_keys.is_empty() && !require_configured)
+        || is_key(&settings.jira_project_keys)
+    {
+        let url = settings
+            .jira_base_url
+            .as_ref()
+            .map(|base| format!("{}/browse/{}", base.trim_end_matches('/'), key));
+        Some(IssueReference {
+            tracker: IssueTracker::Jira,
+            key,
+            url,
+        })
+    } else {
+        None
+    }
+}
+
+/// Issues in a URL: GitHub issue and pull request pages, Linear issues, and Jira's
+/// `/browse/ABC-123` and `selectedIssue=ABC-123`.
+fn find_url_references(settings: &IssueSettings, url: &str) -> Vec<IssueReference> {
+    let Some(domain) = url_domain(url) else {
+        return Vec::new();
+    };
+    let Some((_, rest)) = url.split_once("://") else {
+        return Vec::new();
+    };
+    let (path, query) = match rest.split_once('?') {
+        Some((path, query)) => (path, query.split('#').next().unwrap_or_default()),
+        None => (rest.split('#').next().unwrap_or_default(), ""),
+    };
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+
+    match (domain.as_str(), segments.as_slice()) {
+        ("github.com", [owner, repo, kind @ ("pull" | "issues"), number, ..]) => {
+            github_reference(&format!("{}/{}", owner, repo), number, kind)
+                .into_iter()
+                .collect()
+        }
+        ("linear.app", [workspace, "issue", key, ..]) => find_keys(key, true)
+            .into_iter()
+            .take(1)
+            .map(|key| IssueReference {
+                url: Some(format!("https://linear.app/{}/issue/{}", workspace, key)),
+                tracker: IssueTracker::Linear,
+                key,
+            })
+            .collect(),
+        _ => {
+            let browsed = segments
+                .windows(2)
+                .filter(|pair| pair[0] == "browse")
+                .map(|pair| pair[1]);
+            let selected = query
+                .split('&')
+                .filter_map(|param| param.strip_prefix("selectedIssue="));
+            browsed
+                .chain(selected)
+                .flat_map(|key| find_keys(key, false))
+                .filter_map(|key| classify_key(settings, key, false))
+                .collect()
+        }
+    }
+}
+
+/// Issue references in a window title, git branch and browser URL, first found first and
+/// without duplicates.
+pub fn extract_issue_references(
+    settings: &IssueSettings,
+    window_title: Option<&str>,
+    branch: Option<&str>,
+    url: Option<&str>,
+) -> Vec<IssueReference> {
+    let mut found = Vec::new();
+    if let Some(url) = url {
+        found.extend(find_url_references(settings, url));
+    }
+    if let Some(title) = window_title {
+        found.extend(find_github_references(title));
+        found.extend(
+            find_keys(title, false)
+                .into_iter()
+                .filter_map(|key| classify_key(settings, key, false)),
+        );
+    }
+    if let Some(branch) = branch {
+        // Lowercase words like `release-2024` or `node-18` only count with a configured prefix
+        let uppercase = find_keys(branch, false);
+        found.extend(find_keys(branch, true).into_iter().filter_map(|key| {
+            let require_configured = !uppercase.contains(&key);
+            classify_key(settings, key, require_configured)
+        }));
+    }
+
+    let mut references: Vec<IssueReference> = Vec::new();
+    for reference in found {
+        // A key found in the URL knows its tracker best, so it comes first
+        match references.iter_mut().find(|r| r.key == reference.key) {
+            Some(existing) if existing.url.is_none() => existing.url = reference.url,
+            Some(_) => {}
+            None => references.push(reference),
+        }
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(jira_project_keys: &[&str]) -> IssueSettings {
+        IssueSettings {
+            jira_project_keys: jira_project_keys.iter().map(|k| k.to_string()).collect(),
+            jira_base_url: Some("https://acme.atlassian.net/".to_string()),
+            linear_team_keys: vec!["ENG".to_string()],
+            linear_workspace: Some("acme".to_string()),
+        }
+    }
+
+    fn keys(references: Vec<IssueReference>) -> Vec<String> {
+        references.into_iter().map(|r| r.key).collect()
+    }
+
+    #[test]
+    fn finds_keys_in_titles() {
+        let cases: [(&[&str], &str, &[&str]); 7] = [
+            (&[], "PROJ-12 Fix login - Jira", &["PROJ-12"]),
+            (&[], "ENG-7 and PROJ-12", &["ENG-7", "PROJ-12"]),
+            (&["PROJ"], "ABC-3 and PROJ-12", &["PROJ-12"]),
+            // Lowercase words and version names are not keys in a title
+            (&[], "proj-12 notes.md", &[]),
+            (&[], "UTF-8 ISO-8859 SHA-256", &[]),
+            (&[], "PROJ-012", &[]),
+            (
+                &[],
+                "Fix crash · Pull Request #42 · acme/app",
+                &["acme/app#42"],
+            ),
+        ];
+        for (jira_keys, title, expected) in cases {
+            let found = extract_issue_references(&settings(jira_keys), Some(title), None, None);
+            assert_eq!(keys(found), expected, "{}", title);
+        }
+    }
+
+    #[test]
+    fn finds_keys_in_branches() {
+        let cases: [(&[&str], &str, &[&str]); 7] = [
+            (&["PROJ"], "feature/proj-12-login", &["PROJ-12"]),
+            (&[], "feature/PROJ-12-login", &["PROJ-12"]),
+            (&[], "eng-7-search", &["ENG-7"]),
+            // Without configured keys a lowercase word-number is not a Jira key
+            (&[], "feature/proj-12-login", &[]),
+            (&[], "release-2024", &[]),
+            (&[], "upgrade-node-18", &[]),
+            (&["PROJ"], "release-2024", &[]),
+        ];
+        for (jira_keys, branch, expected) in cases {
+            let found = extract_issue_references(&settings(jira_keys), None, Some(branch), None);
+            assert_eq!(keys(found), expected, "{}", branch);
+        }
+    }
+
+    #[test]
+    fn finds_keys_in_urls() {
+        let cases = [
+            (
+                "https://github.com/acme/app/pull/42/files",
+                "acme/app#42",
+                "https://github.com/acme/app/pull/42",
+            ),
+            (
+                "https://linear.app/acme/issue/eng-7/search",
+                "ENG-7",
+                "https://linear.app/acme/issue/ENG-7",
+            ),
+            (
+                "https://acme.atlassian.net/browse/PROJ-12",
+                "PROJ-12",
+                "https://acme.atlassian.net/browse/PROJ-12",
+            ),
+            (
+                "https://acme.atlassian.net/jira/board?selectedIssue=PROJ-3#top",
+                "PROJ-3",
+                "https://acme.atlassian.net/browse/PROJ-3",
+            ),
+        ];
+        for (url, key, link) in cases {
+            let found = extract_issue_references(&settings(&[]), None, None, Some(url));
+            let found: Vec<_> = found.into_iter().map(|r| (r.key, r.url)).collect();
+            assert_eq!(
+                found,
+                [(key.to_string(), Some(link.to_string()))],
+                "{}",
+                url
+            );
+        }
+
+        let url = "https://example.com/blog/release-2024";
+        assert!(extract_issue_references(&settings(&[]), None, None, Some(url)).is_empty());
+    }
+
+    #[test]
+    fn merges_the_same_key_from_every_source() {
+        let found = extract_issue_references(
+            &settings(&["PROJ"]),
+            Some("PROJ-12 Fix login"),
+            Some("proj-12-login"),
+            Some("https://acme.atlassian.net/browse/PROJ-12"),
+        );
+        assert_eq!(
+            found,
+            [IssueReference {
+                tracker: IssueTracker::Jira,
+                key: "PROJ-12".to_string(),
+                url: Some("https://acme.atlassian.net/browse/PROJ-12".to_string()),
+            }]
+        );
+    }
+}
@@ -0,0 +1,318 @@
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tauri::Manager;
+use tauri_specta::Event;
+
+use crate::commands::{
+    browser_activity::{active_browser_tab, url_domain},
+    platform::{PlatformImpl, WindowActivityCapture},
+    settings::current_settings,
+};
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(10);
+/// A meeting only ends once it has not been seen for this long, so a window briefly
+/// changing its title does not split it.
+const END_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum MeetingApp {
+    Zoom,
+    Teams,
+    GoogleMeet,
+    SlackHuddle,
+}
+
+#[derive(Debug, Clone, serde::Serialize, specta::Type)]
+pub struct Meeting {
+    pub app: MeetingApp,
+    /// Title of the meeting window, or of the browser tab.
+    pub title: String,
+    #[specta(type = f64)]
+    pub started_at: u64,
+}
+
+/// Emitted when a meeting window or tab shows up.
+#[derive(Debug, Clone, serde::Serialize, specta::Type, tauri_specta::Event)]
+pub struct MeetingStarted {
+    meeting: Meeting,
+}
+
+/// Emitted once the meeting has been gone for `END_GRACE_PERIOD`.
+#[derive(Debug, Clone, serde::Serialize, specta::Type, tauri_specta::Event)]
+pub struct MeetingEnded {
+    meeting: Meeting,
+    /// When the meeting was last seen.
+    #[specta(type = f64)]
+    ended_at: u64,
+}
+
+/// The ongoing meeting and when it was last seen.
+#[derive(Default)]
+pub struct MeetingState(Mutex<Option<(Meeting, u64)>>);
+
+/// Google Meet codes look like `abc-defg-hij`.
+fn is_meet_code(code: &str) -> bool {
+    let parts: Vec<&str> = code.split('-').collect();
+    parts.len() == 3
+        && parts
+            .iter()
+            .zip([3, 4, 3])
+            .all(|(part, len)| part.len() == len && part.chars().all(|c| c.is_ascii_lowercase()))
+}
+
+fn meeting_from_url(url: &str) -> Option<MeetingApp> {
+    let domain = url_domain(url)?;
+    let path = url.split_once("://")?.1.split(['?', '#']).next()?;
+    let mut segments = path.split('/').skip(1);
+    match domain.as_str() {
+        "meet.google.com" => segments
+            .next()
+            .is_some_and(is_meet_code)
+            .then_some(MeetingApp::GoogleMeet),
+        "teams.microsoft.com" | "teams.live.com" => segments
+            .any(|segment| segment.starts_with("meetup-join"))
+            .then_some(MeetingApp::Teams),
+        "app.slack.com" => segments
+            .any(|segment| segment == "huddle")
+            .then_some(MeetingApp::SlackHuddle),
+        _ => None,
+    }
+}
+
+/// A meeting recognised from a window's application and title. Browsers are covered by
+/// their title, which is that of the active tab.
+fn meeting_from_window(app_name: &str, title: &str) -> Option<MeetingApp> {
+    let app_name = app_name.to_lowercase();
+    let lower_title = title.to_lowercase();
+
+    let is_zoom = app_name == "zoom.us" || app_name == "zoom";
+    if is_zoom && (title.starts_with("Zoom Meeting") || title.starts_with("Zoom Webinar")) {
+        return Some(MeetingApp::Zoom);
+    }
+
+    let is_teams = app_name.contains("teams") || lower_title.contains("| microsoft teams");
+    let in_call = lower_title.starts_with("meeting")
+        || lower_title.starts_with("call with")
+        || lower_title.contains("| meeting")
+        || lower_title.contains("| call");
+    if is_teams && in_call {
+        return Some(MeetingApp::Teams);
+    }
+
+    // `Meet - abc-defg-hij - Google Chrome`, with a hyphen or an en dash
+    let meet_code = title
+        .strip_prefix("Meet - ")
+        .or_else(|| title.strip_prefix("Meet – "))
+        .and_then(|rest| rest.split_whitespace().next());
+    if meet_code.is_some_and(is_meet_code) {
+        return Some(MeetingApp::GoogleMeet);
+    }
+
+    if app_name == "slack" && lower_title.contains("huddle") {
+        return Some(MeetingApp::SlackHuddle);
+    }
+    None
+}
+
+/// A meeting in the focused browser tab or in any open window, focused or not.
+fn detect_meeting(app: &tauri::AppHandle) -> Option<(MeetingApp, String)> {
+    if let (Ok(info), Ok(settings)) = (
+        PlatformImpl::get_active_application(),
+        current_settings(app),
+    ) {
+        let url = active_browser_tab(&settings.browser, &info)
+            .ok()
+            .flatten()
+            .and_then(|tab| tab.url);
+        if let Some(meeting_app) = url.as_deref().and_then(meeting_from_url) {
+            return Some((meeting_app, info.window_title));
+        }
+    }
+
+    PlatformImpl::open_windows()
+        .ok()?
+        .into_iter()
+        .find_map(|window| {
+            meeting_from_window(&window.app_name, &window.title)
+                .map(|meeting_app| (meeting_app, window.title))
+        })
+}
+
+/// Detect the current meeting, emitting `MeetingStarted` and `MeetingEnded` as it
+/// changes. Returns the ongoing meeting.
+pub fn update_meeting(app: &tauri::AppHandle) -> Option<Meeting> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let detected = detect_meeting(app);
+
+    let state = app.state::<MeetingState>();
+    let mut current = state.0.lock().ok()?;
+    match (current.take(), detected) {
+        // The same meeting, possibly with a new title
+        (Some((meeting, _)), Some((meeting_app, _))) if meeting.app == meeting_app => {
+            *current = Some((meeting, now));
+        }
+        (previous, Some((meeting_app, title))) => {
+            if let Some((meeting, last_seen)) = previous {
+                let _ = MeetingEnded {
+                    meeting,
+                    ended_at: last_seen,
+                }
+                .emit(app);
+            }
+            let meeting = Meeting {
+                app: meeting_app,
+                title,
+                started_at: now,
+            };
+            let _ = MeetingStarted {
+                meeting: meeting.clone(),
+            }
+            .emit(app);
+            *current = Some((meeting, now));
+        }
+        (Some((meeting, last_seen)), None) => {
+            if now.saturating_sub(last_seen) < END_GRACE_PERIOD.as_secs() {
+                *current = Some((meeting, last_seen));
+            } else {
+                let _ = MeetingEnded {
+                    meeting,
+                    ended_at: last_seen,
+                }
+                .emit(app);
+            }
+        }
+        (None, None) => {}
+    }
+    current.as_ref().map(|(meeting, _)| meeting.clone())
+}
+
+/// Poll for meetings in the background, so they are noticed between snapshots too.
+pub fn watch_meetings(app: tauri::AppHandle) {
+    thread::spawn(move || loop {
+        update_meeting(&app);
+        thread::sleep(WATCH_INTERVAL);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_meet_codes() {
+        let cases = [
+            ("abc-defg-hij", true),
+            ("ABC-defg-hij", false),
+            ("ab1-defg-hij", false),
+            ("abcd-efg-hij", false),
+            ("abc-defg", false),
+            ("abc-defg-hij-klm", false),
+            ("", false),
+        ];
+        for (code, expected) in cases {
+            assert_eq!(is_meet_code(code), expected, "{}", code);
+        }
+    }
+
+    #[test]
+    fn recognises_meetings_by_url() {
+        let cases = [
+            (
+                "https://meet.google.com/abc-defg-hij",
+                Some(MeetingApp::GoogleMeet),
+            ),
+            (
+                "https://meet.google.com/abc-defg-hij?authuser=1#chat",
+                Some(MeetingApp::GoogleMeet),
+            ),
+            (
+                "https://teams.microsoft.com/l/meetup-join/19%3ameeting_abc%40thread.v2/0",
+                Some(MeetingApp::Teams),
+            ),
+            (
+                "https://app.slack.com/huddle/T0123/C0456",
+                Some(MeetingApp::SlackHuddle),
+            ),
+            // The Meet landing page and a code that is one letter short
+            ("https://meet.google.com/", None),
+            ("https://meet.google.com/landing", None),
+            ("https://meet.google.com/abc-defg-hi", None),
+            // A Teams chat and a Slack channel
+            (
+                "https://teams.microsoft.com/_#/conversations/19:abc@thread.v2?ctx=chat",
+                None,
+            ),
+            ("https://app.slack.com/client/T0123/C0456", None),
+            ("https://example.com/meet.google.com/abc-defg-hij", None),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(meeting_from_url(url), expected, "{}", url);
+        }
+    }
+
+    #[test]
+    fn recognises_meetings_by_window() {
+        let cases = [
+            ("zoom.us", "Zoom Meeting", Some(MeetingApp::Zoom)),
+            ("Zoom", "Zoom Webinar", Some(MeetingApp::Zoom)),
+            (
+                "Microsoft Teams",
+                "Meeting with Alex | Microsoft Teams",
+                Some(MeetingApp::Teams),
+            ),
+            (
+                "Microsoft Teams",
+                "Call with Alex | Microsoft Teams",
+                Some(MeetingApp::Teams),
+            ),
+            (
+                "Google Chrome",
+                "Weekly sync | Meeting | Microsoft Teams - Google Chrome",
+                Some(MeetingApp::Teams),
+            ),
+            (
+                "Google Chrome",
+                "Meet - abc-defg-hij - Google Chrome",
+                Some(MeetingApp::GoogleMeet),
+            ),
+            (
+                "Firefox",
+                "Meet – abc-defg-hij — Mozilla Firefox",
+                Some(MeetingApp::GoogleMeet),
+            ),
+            (
+                "Slack",
+                "Huddle: #general - Acme - Slack",
+                Some(MeetingApp::SlackHuddle),
+            ),
+            // The Zoom app without a call, and a Zoom meeting title elsewhere
+            ("zoom.us", "Zoom Workplace", None),
+            ("Notes", "Zoom Meeting notes", None),
+            // A Teams chat and the Teams calendar
+            ("Microsoft Teams", "Chat | Alex | Microsoft Teams", None),
+            ("Microsoft Teams", "Calendar | Microsoft Teams", None),
+            // The Meet landing page
+            ("Google Chrome", "Google Meet - Google Chrome", None),
+            ("Google Chrome", "Meet - New meeting - Google Chrome", None),
+            // A Slack channel, and huddles mentioned outside of Slack
+            ("Slack", "general (Channel) - Acme - Slack", None),
+            ("Firefox", "How to start a huddle - Mozilla Firefox", None),
+        ];
+        for (app_name, title, expected) in cases {
+            assert_eq!(
+                meeting_from_window(app_name, title),
+                expected,
+                "{}: {}",
+                app_name,
+                title
+            );
+        }
+    }
+}
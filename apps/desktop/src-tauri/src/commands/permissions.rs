@@ -0,0 +1,97 @@
+use crate::commands::platform::{PlatformImpl, WindowActivityCapture};
+use std::{thread, time::Duration};
+use tauri_specta::Event;
+
+/// How often the background watcher re-checks permissions.
+/// Permissions are usually granted in System Settings while the app keeps running.
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A capability the capture backend depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionCapability {
+    Screenshots,
+    WindowTitles,
+    IdleTime,
+}
+
+impl PermissionCapability {
+    pub const ALL: [PermissionCapability; 3] = [
+        PermissionCapability::Screenshots,
+        PermissionCapability::WindowTitles,
+        PermissionCapability::IdleTime,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionState {
+    Granted,
+    Denied,
+    NotDetermined,
+    /// The platform backend cannot provide this capability at all.
+    Unsupported,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, specta::Type)]
+pub struct PermissionStatus {
+    pub capability: PermissionCapability,
+    pub state: PermissionState,
+    /// Which OS mechanism the status was derived from, e.g. "ScreenCaptureKit" or "X11".
+    pub detail: Option<String>,
+}
+
+/// Emitted whenever the status of any capability changes.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, specta::Type, tauri_specta::Event)]
+pub struct PermissionsChanged {
+    permissions: Vec<PermissionStatus>,
+}
+
+fn current_permissions() -> Vec<PermissionStatus> {
+    PermissionCapability::ALL
+        .into_iter()
+        .map(PlatformImpl::check_permission)
+        .collect()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn check_permissions() -> Vec<PermissionStatus> {
+    current_permissions()
+}
+
+/// Ask the OS for a capability. On macOS this shows the system prompt the first time;
+/// afterwards the user has to change it in System Settings.
+#[tauri::command]
+#[specta::specta]
+pub fn request_permission(
+    app: tauri::AppHandle,
+    capability: PermissionCapability,
+) -> PermissionStatus {
+    let before = current_permissions();
+    let status = PlatformImpl::request_permission(capability);
+
+    let after = current_permissions();
+    if after != before {
+        let _ = PermissionsChanged { permissions: after }.emit(&app);
+    }
+    status
+}
+
+/// Poll permissions in the background and emit `PermissionsChanged` when they change.
+pub fn watch_permissions(app: tauri::AppHandle) {
+    thread::spawn(move || {
+        let mut previous = current_permissions();
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let current = current_permissions();
+            if current != previous {
+                let _ = PermissionsChanged {
+                    permissions: current.clone(),
+                }
+                .emit(&app);
+                previous = current;
+            }
+        }
+    });
+}
@@ -1,13 +1,130 @@
+use image::DynamicImage;
+use std::path::PathBuf;
+
+use crate::commands::{
+    error::CommandResult,
+    permissions::{PermissionCapability, PermissionStatus},
+};
+
 #[derive(Debug, Clone)]
 pub struct ApplicationInfo {
+    /// Localised name, e.g. "Finder" or "Verkenner"; prefer `bundle_id` for matching.
     pub app_name: String,
     pub window_title: String,
+    pub pid: Option<u32>,
+    pub executable_path: Option<String>,
+    /// Bundle identifier on macOS, desktop file id on Linux.
+    pub bundle_id: Option<String>,
+    /// `CGWindowID` on macOS, X11 window id on Linux.
+    pub window_id: Option<u32>,
+    /// Bounds of the focused window, when the backend can tell.
+    pub window_bounds: Option<ScreenRect>,
+    /// The display showing (the centre of) the focused window, see `DisplayCapture::display_id`.
+    pub display_id: Option<u32>,
+    pub is_fullscreen: bool,
+}
+
+/// A rectangle in global screen coordinates (points on macOS, pixels on X11),
+/// or in image pixels where noted.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, specta::Type)]
+pub struct ScreenRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl ScreenRect {
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    pub fn contains(&self, (x, y): (f64, f64)) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// The captured pixels of one display and where that display sits on the desktop.
+pub struct DisplayCapture {
+    /// Platform id of the display: the `CGDirectDisplayID` on macOS,
+    /// the RandR monitor index on X11.
+    pub display_id: u32,
+    pub image: DynamicImage,
+    pub bounds: ScreenRect,
+}
+
+impl DisplayCapture {
+    /// Map a rectangle in desktop coordinates to pixels of the captured image.
+    /// Desktop coordinates are points on HiDPI displays, so this also scales.
+    pub fn to_image_rect(&self, rect: &ScreenRect) -> ScreenRect {
+        let scale_x = self.image.width() as f64 / self.bounds.width;
+        let scale_y = self.image.height() as f64 / self.bounds.height;
+        ScreenRect {
+            x: (rect.x - self.bounds.x) * scale_x,
+            y: (rect.y - self.bounds.y) * scale_y,
+            width: rect.width * scale_x,
+            height: rect.height * scale_y,
+        }
+    }
+}
+
+/// An on-screen window and the application that owns it.
+#[derive(Debug, Clone)]
+pub struct WindowBounds {
+    pub app_name: String,
+    pub bounds: ScreenRect,
+}
+
+/// A top-level window of a running application, on screen or not.
+#[derive(Debug, Clone)]
+pub struct OpenWindow {
+    pub app_name: String,
+    pub title: String,
+}
+
+/// A line of text found by OCR, with its bounds in image pixels.
+#[derive(Debug, Clone)]
+pub struct RecognizedText {
+    pub text: String,
+    pub bounds: ScreenRect,
+}
+
+/// Descendants of `pid` given `(pid, parent pid)` pairs, the most deeply nested first.
+pub fn descendants_deepest_first(pid: u32, processes: &[(u32, u32)]) -> Vec<u32> {
+    let mut found = vec![pid];
+    let mut next = 0;
+    while next < found.len() {
+        let parent = found[next];
+        found.extend(
+            processes
+                .iter()
+                .filter(|(child, ppid)| *ppid == parent && *child != parent)
+                .map(|(child, _)| *child),
+        );
+        next += 1;
+    }
+    found.remove(0);
+    found.reverse();
+    found
 }
 
 pub trait WindowActivityCapture {
-    fn get_idle_time_seconds() -> u32;
-    fn get_active_application() -> ApplicationInfo;
-    fn capture_screenshots(app: &tauri::AppHandle, timestamp: u32) -> Vec<String>;
+    fn get_idle_time_seconds() -> CommandResult<u32>;
+    fn get_active_application() -> CommandResult<ApplicationInfo>;
+    /// One image per display, in display order, or only the display with `display_id`.
+    /// Storing them is up to the caller.
+    fn capture_screenshots(display_id: Option<u32>) -> CommandResult<Vec<DisplayCapture>>;
+    /// Visible windows, front to back.
+    fn visible_windows() -> CommandResult<Vec<WindowBounds>>;
+    /// Windows of all running applications, including minimised and hidden ones.
+    fn open_windows() -> CommandResult<Vec<OpenWindow>>;
+    /// Lines of text in an image. The image is never written to disk.
+    fn recognize_text(image: &DynamicImage) -> CommandResult<Vec<RecognizedText>>;
+    /// Working directories of the descendants of a process, most deeply nested first
+    /// (e.g. the shell inside a terminal), then of the process itself.
+    fn working_directories(pid: u32) -> CommandResult<Vec<PathBuf>>;
+    fn check_permission(capability: PermissionCapability) -> PermissionStatus;
+    fn request_permission(capability: PermissionCapability) -> PermissionStatus;
 }
 
 #[cfg(target_os = "macos")]
@@ -0,0 +1,65 @@
+pub mod permissions;
+pub mod process;
+pub mod recognize_text;
+pub mod x11;
+
+use image::DynamicImage;
+use std::path::PathBuf;
+
+use crate::commands::{
+    error::{CommandError, CommandResult},
+    permissions::{PermissionCapability, PermissionStatus},
+    platform::{
+        ApplicationInfo, DisplayCapture, OpenWindow, RecognizedText, WindowActivityCapture,
+        WindowBounds,
+    },
+};
+
+pub struct LinuxCapture;
+
+/// Capturing windows, screenshots and idle time is not implemented on Linux yet; the
+/// permission checks already report what a session would allow.
+fn not_implemented<T>() -> CommandResult<T> {
+    Err(CommandError::BackendUnavailable(
+        "Capturing is not implemented on Linux yet".to_string(),
+    ))
+}
+
+impl WindowActivityCapture for LinuxCapture {
+    fn get_idle_time_seconds() -> CommandResult<u32> {
+        not_implemented()
+    }
+
+    fn get_active_application() -> CommandResult<ApplicationInfo> {
+        not_implemented()
+    }
+
+    fn capture_screenshots(_display_id: Option<u32>) -> CommandResult<Vec<DisplayCapture>> {
+        not_implemented()
+    }
+
+    fn visible_windows() -> CommandResult<Vec<WindowBounds>> {
+        not_implemented()
+    }
+
+    fn open_windows() -> CommandResult<Vec<OpenWindow>> {
+        not_implemented()
+    }
+
+    fn recognize_text(image: &DynamicImage) -> CommandResult<Vec<RecognizedText>> {
+        recognize_text::recognize_text(image)
+    }
+
+    fn working_directories(pid: u32) -> CommandResult<Vec<PathBuf>> {
+        process::working_directories(pid)
+    }
+
+    fn check_permission(capability: PermissionCapability) -> PermissionStatus {
+        permissions::check_permission(capability)
+    }
+
+    fn request_permission(capability: PermissionCapability) -> PermissionStatus {
+        // Neither X11 nor the portal have a grant step we can trigger up front
+        permissions::check_permission(capability)
+    }
+}
@@ -0,0 +1,59 @@
+use std::{env, path::Path};
+use x11rb::protocol::screensaver;
+
+use crate::commands::permissions::{PermissionCapability, PermissionState, PermissionStatus};
+
+use super::x11::{has_extension, with_connection};
+
+/// Where D-Bus activatable services are installed; the portal is available when its service file is.
+const PORTAL_SERVICE_FILES: [&str; 2] = [
+    "/usr/share/dbus-1/services/org.freedesktop.portal.Desktop.service",
+    "/usr/local/share/dbus-1/services/org.freedesktop.portal.Desktop.service",
+];
+
+/// X11 has no permission model: if we can connect, we can read everything.
+/// On a pure Wayland session only xdg-desktop-portal could provide screenshots,
+/// and only after asking the user, while window titles and idle time are not exposed at all.
+pub fn check_permission(capability: PermissionCapability) -> PermissionStatus {
+    let has_screensaver =
+        with_connection(|conn| has_extension(conn, screensaver::X11_EXTENSION_NAME));
+    let (state, detail) = match has_screensaver {
+        Ok(has_screensaver) => match capability {
+            PermissionCapability::IdleTime if !has_screensaver => (
+                PermissionState::Unsupported,
+                "X11 without MIT-SCREEN-SAVER".to_string(),
+            ),
+            _ => (PermissionState::Granted, "X11".to_string()),
+        },
+        Err(_) if is_wayland_session() => match capability {
+            PermissionCapability::Screenshots if portal_available() => (
+                PermissionState::NotDetermined,
+                "xdg-desktop-portal".to_string(),
+            ),
+            PermissionCapability::Screenshots => (
+                PermissionState::Unsupported,
+                "Wayland without xdg-desktop-portal".to_string(),
+            ),
+            _ => (PermissionState::Unsupported, "Wayland".to_string()),
+        },
+        Err(e) => (PermissionState::Unsupported, e.to_string()),
+    };
+
+    PermissionStatus {
+        capability,
+        state,
+        detail: Some(detail),
+    }
+}
+
+fn is_wayland_session() -> bool {
+    env::var_os("WAYLAND_DISPLAY").is_some()
+        || env::var("XDG_SESSION_TYPE").is_ok_and(|t| t == "wayland")
+}
+
+fn portal_available() -> bool {
+    env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some()
+        && PORTAL_SERVICE_FILES
+            .iter()
+            .any(|path| Path::new(path).exists())
+}
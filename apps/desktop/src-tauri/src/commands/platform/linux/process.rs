@@ -0,0 +1,37 @@
+use std::{fs, path::PathBuf};
+
+use crate::commands::{error::CommandResult, platform::descendants_deepest_first};
+
+/// `(pid, parent pid)` of every process in `/proc`.
+fn processes() -> CommandResult<Vec<(u32, u32)>> {
+    let mut processes = Vec::new();
+    for entry in fs::read_dir("/proc")? {
+        let Some(pid) = entry?.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        // Processes can exit while we list them
+        let Ok(stat) = fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+            continue;
+        };
+        // The command name is in parentheses and may contain spaces; the parent pid
+        // is the second field after it
+        let ppid = stat
+            .rsplit_once(')')
+            .and_then(|(_, rest)| rest.split_whitespace().nth(1))
+            .and_then(|ppid| ppid.parse().ok());
+        if let Some(ppid) = ppid {
+            processes.push((pid, ppid));
+        }
+    }
+    Ok(processes)
+}
+
+/// Read from the `/proc/<pid>/cwd` symlinks. Processes of other users are skipped.
+pub fn working_directories(pid: u32) -> CommandResult<Vec<PathBuf>> {
+    let mut pids = descendants_deepest_first(pid, &processes()?);
+    pids.push(pid);
+    Ok(pids
+        .into_iter()
+        .filter_map(|pid| fs::read_link(format!("/proc/{}/cwd", pid)).ok())
+        .collect())
+}
@@ -0,0 +1,97 @@
+use image::{DynamicImage, ImageFormat};
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Write},
+    process::{Command, Stdio},
+};
+
+use crate::commands::{
+    error::{CommandError, CommandResult},
+    platform::{RecognizedText, ScreenRect},
+};
+
+/// Words tesseract is less than this confident about are ignored.
+const MIN_CONFIDENCE: f64 = 30.0;
+
+/// Recognise text with the `tesseract` CLI. The image is piped through stdin
+/// so it never touches the disk.
+pub fn recognize_text(image: &DynamicImage) -> CommandResult<Vec<RecognizedText>> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| CommandError::Internal(e.to_string()))?;
+
+    let mut child = Command::new("tesseract")
+        .args(["stdin", "stdout", "tsv"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| {
+            CommandError::BackendUnavailable(format!("Could not run tesseract for OCR: {}", e))
+        })?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(&png)?;
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(CommandError::BackendUnavailable(format!(
+            "tesseract exited with {}",
+            output.status
+        )));
+    }
+    Ok(parse_tsv(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Join the word rows of tesseract's TSV output into lines.
+/// Columns: level page block paragraph line word left top width height confidence text
+fn parse_tsv(tsv: &str) -> Vec<RecognizedText> {
+    let mut lines: BTreeMap<(u32, u32, u32, u32), RecognizedText> = BTreeMap::new();
+
+    for row in tsv.lines().skip(1) {
+        let columns: Vec<&str> = row.split('\t').collect();
+        if columns.len() < 12 || columns[0] != "5" {
+            continue;
+        }
+        let text = columns[11].trim();
+        let confidence: f64 = columns[10].parse().unwrap_or(-1.0);
+        if text.is_empty() || confidence < MIN_CONFIDENCE {
+            continue;
+        }
+
+        let number = |i: usize| columns[i].parse::<u32>().unwrap_or(0);
+        let word = ScreenRect {
+            x: number(6) as f64,
+            y: number(7) as f64,
+            width: number(8) as f64,
+            height: number(9) as f64,
+        };
+
+        lines
+            .entry((number(1), number(2), number(3), number(4)))
+            .and_modify(|line| {
+                line.text.push(' ');
+                line.text.push_str(text);
+                line.bounds = union(&line.bounds, &word);
+            })
+            .or_insert(RecognizedText {
+                text: text.to_string(),
+                bounds: word,
+            });
+    }
+    lines.into_values().collect()
+}
+
+fn union(a: &ScreenRect, b: &ScreenRect) -> ScreenRect {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+    ScreenRect {
+        x,
+        y,
+        width: (a.x + a.width).max(b.x + b.width) - x,
+        height: (a.y + a.height).max(b.y + b.height) - y,
+    }
+}
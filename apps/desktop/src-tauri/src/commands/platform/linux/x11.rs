@@ -0,0 +1,37 @@
+use std::sync::Mutex;
+use x11rb::{
+    connection::RequestConnection, protocol::xproto::ConnectionExt as _,
+    rust_connection::RustConnection,
+};
+
+use crate::commands::error::{CommandError, CommandResult};
+
+/// One connection to the X server for every check, opened on first use and again after
+/// the server went away.
+static CONNECTION: Mutex<Option<RustConnection>> = Mutex::new(None);
+
+pub fn x11_error(error: impl std::fmt::Display) -> CommandError {
+    CommandError::BackendUnavailable(format!("X11: {}", error))
+}
+
+/// Run `f` on the shared connection, connecting first when there is none or it broke.
+pub fn with_connection<T>(f: impl FnOnce(&RustConnection) -> T) -> CommandResult<T> {
+    let mut connection = CONNECTION
+        .lock()
+        .map_err(|_| CommandError::Internal("X11 connection lock poisoned".to_string()))?;
+    // A cheap round trip, since a connection to a server that quit only fails on use
+    let alive = connection.as_ref().is_some_and(|conn| {
+        conn.get_input_focus()
+            .is_ok_and(|cookie| cookie.reply().is_ok())
+    });
+    if !alive {
+        *connection = None;
+        let (conn, _) = x11rb::connect(None).map_err(x11_error)?;
+        *connection = Some(conn);
+    }
+    Ok(f(connection.as_ref().expect("connected above")))
+}
+
+pub fn has_extension(conn: &RustConnection, name: &'static str) -> bool {
+    matches!(conn.extension_information(name), Ok(Some(_)))
+}
@@ -1,21 +1,58 @@
 pub mod capture_screenshots;
 pub mod idle_time;
+pub mod permissions;
+pub mod process;
+pub mod recognize_text;
 pub mod window_info;
 
-use crate::commands::platform::{ApplicationInfo, WindowActivityCapture};
+use image::DynamicImage;
+use std::path::PathBuf;
+
+use crate::commands::{
+    error::CommandResult,
+    permissions::{PermissionCapability, PermissionStatus},
+    platform::{
+        ApplicationInfo, DisplayCapture, OpenWindow, RecognizedText, WindowActivityCapture,
+        WindowBounds,
+    },
+};
 
 pub struct MacOSCapture;
 
 impl WindowActivityCapture for MacOSCapture {
-    fn get_idle_time_seconds() -> u32 {
+    fn get_idle_time_seconds() -> CommandResult<u32> {
         idle_time::get_idle_time_seconds()
     }
 
-    fn get_active_application() -> ApplicationInfo {
+    fn get_active_application() -> CommandResult<ApplicationInfo> {
         window_info::get_active_application()
     }
 
-    fn capture_screenshots(app: &tauri::AppHandle, timestamp: u32) -> Vec<String> {
-        capture_screenshots::capture_all_display_screenshots(app, timestamp)
+    fn capture_screenshots(display_id: Option<u32>) -> CommandResult<Vec<DisplayCapture>> {
+        capture_screenshots::capture_all_display_screenshots(display_id)
+    }
+
+    fn visible_windows() -> CommandResult<Vec<WindowBounds>> {
+        window_info::get_visible_windows()
+    }
+
+    fn open_windows() -> CommandResult<Vec<OpenWindow>> {
+        window_info::get_open_windows()
+    }
+
+    fn recognize_text(image: &DynamicImage) -> CommandResult<Vec<RecognizedText>> {
+        recognize_text::recognize_text(image)
+    }
+
+    fn working_directories(pid: u32) -> CommandResult<Vec<PathBuf>> {
+        process::working_directories(pid)
+    }
+
+    fn check_permission(capability: PermissionCapability) -> PermissionStatus {
+        permissions::check_permission(capability)
+    }
+
+    fn request_permission(capability: PermissionCapability) -> PermissionStatus {
+        permissions::request_permission(capability)
     }
 }
@@ -1,121 +1,137 @@
 use block2::StackBlock;
+use image::{DynamicImage, RgbaImage};
 use objc2::AnyThread;
-use objc2_core_graphics::CGImage;
-use objc2_core_image::{CIContext, CIImage};
-use objc2_foundation::{NSArray, NSError, NSString, NSURL};
+use objc2_core_graphics::{CGBitmapInfo, CGDataProvider, CGImage, CGImageAlphaInfo};
+use objc2_foundation::{NSArray, NSError};
 use objc2_screen_capture_kit::{
     SCContentFilter, SCScreenshotManager, SCShareableContent, SCStreamConfiguration,
 };
-use std::{fs, path::PathBuf};
-use std::sync::{Arc, Mutex, mpsc};
-use tauri::Manager;
+use std::sync::{mpsc, Arc, Mutex};
+
+use crate::commands::{
+    error::{CommandError, CommandResult},
+    platform::{DisplayCapture, ScreenRect},
+};
 
 /// Capture screenshots using ScreenCaptureKit API.
 /// This captures actual screen content, not the display buffer (which may show screensaver).
-/// Returns absolute file paths for successfully captured screenshots.
-pub fn capture_all_display_screenshots(app: &tauri::AppHandle, timestamp: u32) -> Vec<String> {
-    // Resolve app-local data screenshots directory in a platform-appropriate place
-    let base_dir: PathBuf = app
-        .path()
-        .resolve("screenshots", tauri::path::BaseDirectory::AppLocalData)
-        .unwrap_or_else(|_| std::env::temp_dir().join("screenshots"));
-
-    // Ensure directory exists
-    let _ = fs::create_dir_all(&base_dir);
+/// Returns one image per display, in display order, or only the display with `display_id`.
+pub fn capture_all_display_screenshots(
+    display_id: Option<u32>,
+) -> CommandResult<Vec<DisplayCapture>> {
+    // Use ScreenCaptureKit to capture the screen
+    capture_screen_with_sck(display_id)
+}
 
-    let mut paths: Vec<String> = Vec::new();
+/// `SCStreamErrorUserDeclined`: the user has not granted screen recording permission.
+const SC_STREAM_ERROR_USER_DECLINED: isize = -3801;
 
-    // Use ScreenCaptureKit to capture the screen
-    match capture_screen_with_sck(&base_dir, timestamp) {
-        Ok(screenshot_paths) => {
-            paths.extend(screenshot_paths);
-        }
-        Err(e) => {
-            eprintln!(
-                "ScreenCaptureKit failed: {}, falling back to screencapture",
-                e
-            );
-        }
+fn sck_error(error: &NSError) -> CommandError {
+    let description = error.localizedDescription().to_string();
+    if error.code() == SC_STREAM_ERROR_USER_DECLINED {
+        CommandError::PermissionDenied(description)
+    } else {
+        CommandError::BackendUnavailable(description)
     }
-
-    paths
 }
 
 /// Capture screen using ScreenCaptureKit API
 /// docs: https://developer.apple.com/documentation/screencapturekit
-fn capture_screen_with_sck(base_dir: &PathBuf, timestamp: u32) -> Result<Vec<String>, String> {
-    let (tx, rx) = mpsc::channel();
-    let successful_paths = Arc::new(Mutex::new(Vec::new()));
+fn capture_screen_with_sck(display_id: Option<u32>) -> CommandResult<Vec<DisplayCapture>> {
+    let (tx, rx) = mpsc::channel::<CommandResult<Vec<DisplayCapture>>>();
+    let captured_images = Arc::new(Mutex::new(Vec::new()));
+    let last_error = Arc::new(Mutex::new(None::<CommandError>));
 
     unsafe {
         SCShareableContent::getShareableContentExcludingDesktopWindows_onScreenWindowsOnly_completionHandler(
-            false, 
-            true,  
+            false,
+            true,
             &StackBlock::new({
-                let base_dir = base_dir.clone();
-                let paths = Arc::clone(&successful_paths);
+                let images = Arc::clone(&captured_images);
+                let last_error = Arc::clone(&last_error);
                 let sender = tx.clone();
-                
+
                 move |shareable_content: *mut SCShareableContent, error: *mut NSError| {
                     if !error.is_null() {
-                        eprintln!("Failed to get shareable content");
-                        let _ = sender.send(Vec::new());
+                        let _ = sender.send(Err(sck_error(&*error)));
                         return;
                     }
-                    
-                    let displays: Vec<_> = (&*shareable_content).displays().iter().collect();
+
+                    let displays: Vec<_> = (&*shareable_content)
+                        .displays()
+                        .iter()
+                        .filter(|display| display_id.is_none_or(|id| id == display.displayID()))
+                        .collect();
                     if displays.is_empty() {
-                        let _ = sender.send(Vec::new());
+                        let _ = sender.send(Err(CommandError::NoDisplays(
+                            "ScreenCaptureKit reported no shareable displays".to_string(),
+                        )));
                         return;
                     }
-                    
+
                     let remaining = Arc::new(Mutex::new(displays.len()));
-                    
+
                     for (idx, display) in displays.iter().enumerate() {
+                        let id = display.displayID();
+                        let frame = display.frame();
+                        let bounds = ScreenRect {
+                            x: frame.origin.x,
+                            y: frame.origin.y,
+                            width: frame.size.width,
+                            height: frame.size.height,
+                        };
                         let filter = SCContentFilter::initWithDisplay_excludingWindows(
-                            SCContentFilter::alloc(), 
-                            display.as_ref(), 
+                            SCContentFilter::alloc(),
+                            display.as_ref(),
                             &NSArray::new()
                         );
-                        
-                        let screenshot_path = base_dir.join(format!("{}_{}_recount.jpg", timestamp, idx));
-                        
+
                         SCScreenshotManager::captureImageWithFilter_configuration_completionHandler(
                             &filter,
                             &SCStreamConfiguration::new(),
                             Some(&StackBlock::new({
-                                let paths = Arc::clone(&paths);
+                                let images = Arc::clone(&images);
+                                let last_error = Arc::clone(&last_error);
                                 let remaining = Arc::clone(&remaining);
                                 let sender = sender.clone();
-                                
+
                                 move |image: *mut CGImage, error: *mut NSError| {
-                                    // Try to save screenshot using Core Image
-                                    if error.is_null() && !image.is_null() {
-                                        let ci_image = CIImage::imageWithCGImage(&*image);
-                                        let ci_context = CIContext::context();
-                                        let file_url = NSURL::fileURLWithPath(&NSString::from_str(&screenshot_path.to_string_lossy()));
-                                        
-                                        // Write JPEG representation using Core Image
-                                        if let Some(color_space) = ci_image.colorSpace() {
-                                            if ci_context.writeJPEGRepresentationOfImage_toURL_colorSpace_options_error(
-                                                &ci_image,
-                                                &file_url,
-                                                &color_space,
-                                                &objc2_foundation::NSDictionary::new()
-                                            ).is_ok() {
-                                                if let Ok(mut p) = paths.lock() {
-                                                    p.push(screenshot_path.to_string_lossy().to_string());
-                                                }
+                                    // Copy the screenshot's pixels
+                                    let result = if !error.is_null() {
+                                        Err(sck_error(&*error))
+                                    } else if image.is_null() {
+                                        Err(CommandError::BackendUnavailable(
+                                            "ScreenCaptureKit returned no image".to_string(),
+                                        ))
+                                    } else {
+                                        to_dynamic_image(&*image)
+                                    };
+
+                                    match result {
+                                        Ok(image) => {
+                                            if let Ok(mut i) = images.lock() {
+                                                i.push((idx, DisplayCapture { display_id: id, image, bounds }));
+                                            }
+                                        }
+                                        Err(e) => {
+                                            if let Ok(mut last) = last_error.lock() {
+                                                *last = Some(e);
                                             }
                                         }
                                     }
-                                    
+
                                     // Check if done
                                     if let Ok(mut count) = remaining.lock() {
                                         *count -= 1;
                                         if *count == 0 {
-                                            let result = paths.lock().map(|p| p.clone()).unwrap_or_default();
-                                            let _ = sender.send(result);
+                                            let mut captured = images.lock().map(|mut i| std::mem::take(&mut *i)).unwrap_or_default();
+                                            captured.sort_by_key(|(idx, _)| *idx);
+                                            let captured: Vec<DisplayCapture> = captured.into_iter().map(|(_, capture)| capture).collect();
+                                            let error = last_error.lock().ok().and_then(|mut e| e.take());
+                                            let _ = sender.send(match (captured.is_empty(), error) {
+                                                (true, Some(error)) => Err(error),
+                                                _ => Ok(captured),
+                                            });
                                         }
                                     }
                                 }
@@ -127,7 +143,67 @@ fn capture_screen_with_sck(base_dir: &PathBuf, timestamp: u32) -> Result<Vec<Str
         );
     }
 
-    // Wait for completion and return paths
-    rx.recv().map_err(|_| "Screenshot capture failed".to_string())
+    // Wait for completion and return images
+    rx.recv().map_err(|_| {
+        CommandError::BackendUnavailable("ScreenCaptureKit never completed the capture".to_string())
+    })?
 }
 
+/// Copy the pixels of a captured image, 8 bits per channel in one of the 32 bit layouts
+/// Core Graphics uses. ScreenCaptureKit delivers BGRA: little endian with alpha first.
+fn to_dynamic_image(cg_image: &CGImage) -> CommandResult<DynamicImage> {
+    let unsupported = |what: String| {
+        CommandError::BackendUnavailable(format!("Unsupported capture pixel format: {}", what))
+    };
+    let image = Some(cg_image);
+    let (width, height) = (CGImage::width(image), CGImage::height(image));
+    let bytes_per_row = CGImage::bytes_per_row(image);
+    let (bits_per_component, bits_per_pixel) = (
+        CGImage::bits_per_component(image),
+        CGImage::bits_per_pixel(image),
+    );
+    if bits_per_component != 8 || bits_per_pixel != 32 {
+        return Err(unsupported(format!(
+            "{} bits per component, {} per pixel",
+            bits_per_component, bits_per_pixel
+        )));
+    }
+
+    let little_endian = CGImage::bitmap_info(image).0 & CGBitmapInfo::ByteOrderMask.0
+        == CGBitmapInfo::ByteOrder32Little.0;
+    let alpha_first = matches!(
+        CGImage::alpha_info(image),
+        CGImageAlphaInfo::PremultipliedFirst
+            | CGImageAlphaInfo::First
+            | CGImageAlphaInfo::NoneSkipFirst
+    );
+    // Offsets of red, green and blue within a pixel
+    let [r, g, b] = match (little_endian, alpha_first) {
+        (true, true) => [2, 1, 0],
+        (true, false) => [3, 2, 1],
+        (false, true) => [1, 2, 3],
+        (false, false) => [0, 1, 2],
+    };
+
+    let data = CGImage::data_provider(image)
+        .and_then(|provider| CGDataProvider::data(Some(&provider)))
+        .ok_or_else(|| {
+            CommandError::BackendUnavailable("The capture has no pixel data".to_string())
+        })?;
+    // SAFETY: `data` is an immutable copy of the pixels and outlives `bytes`
+    let bytes = unsafe { data.as_bytes_unchecked() };
+
+    let width = u32::try_from(width).map_err(|_| unsupported(format!("{} wide", width)))?;
+    let mut rgba = RgbaImage::new(width, height as u32);
+    for (y, row) in rgba.rows_mut().enumerate() {
+        let start = y * bytes_per_row;
+        let pixels = bytes
+            .get(start..start + width as usize * 4)
+            .ok_or_else(|| unsupported("rows shorter than the image".to_string()))?;
+        // Screens are opaque, so premultiplied colours are the colours themselves
+        for (pixel, source) in row.zip(pixels.chunks_exact(4)) {
+            pixel.0 = [source[r], source[g], source[b], u8::MAX];
+        }
+    }
+    Ok(DynamicImage::ImageRgba8(rgba))
+}
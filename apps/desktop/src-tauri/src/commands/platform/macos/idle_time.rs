@@ -1,6 +1,8 @@
 use objc2_core_graphics::{CGEventSource, CGEventSourceStateID, CGEventType};
 
-pub fn get_idle_time_seconds() -> u32 {
+use crate::commands::error::{CommandError, CommandResult};
+
+pub fn get_idle_time_seconds() -> CommandResult<u32> {
     let secs = unsafe {
         CGEventSource::seconds_since_last_event_type(
             CGEventSourceStateID::CombinedSessionState,
@@ -8,8 +10,11 @@ pub fn get_idle_time_seconds() -> u32 {
         )
     };
     if secs.is_finite() && secs >= 0.0 {
-        secs as u32
+        Ok(secs as u32)
     } else {
-        0
+        Err(CommandError::BackendUnavailable(format!(
+            "CGEventSource reported an invalid idle time: {}",
+            secs
+        )))
     }
 }
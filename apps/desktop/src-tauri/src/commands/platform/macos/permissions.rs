@@ -0,0 +1,62 @@
+use objc2_core_graphics::{CGPreflightScreenCaptureAccess, CGRequestScreenCaptureAccess};
+use objc2_foundation::{ns_string, NSString, NSUserDefaults};
+
+use crate::commands::permissions::{PermissionCapability, PermissionState, PermissionStatus};
+
+/// `CGPreflightScreenCaptureAccess` cannot tell "never asked" apart from "denied",
+/// so treat it as not determined until we have shown the prompt once. The flag lives in
+/// the user defaults, since a denial outlasts the process that asked.
+fn screen_capture_requested_key() -> &'static NSString {
+    ns_string!("ScreenCaptureRequested")
+}
+
+fn screen_capture_requested() -> bool {
+    unsafe { NSUserDefaults::standardUserDefaults().boolForKey(screen_capture_requested_key()) }
+}
+
+fn set_screen_capture_requested() {
+    unsafe {
+        NSUserDefaults::standardUserDefaults().setBool_forKey(true, screen_capture_requested_key())
+    };
+}
+
+pub fn check_permission(capability: PermissionCapability) -> PermissionStatus {
+    match capability {
+        // Since macOS 10.15 window titles from CGWindowListCopyWindowInfo
+        // are gated behind the same Screen Recording permission as screenshots.
+        PermissionCapability::Screenshots | PermissionCapability::WindowTitles => {
+            PermissionStatus {
+                capability,
+                state: screen_capture_state(),
+                detail: Some("Screen Recording".to_string()),
+            }
+        }
+        // CGEventSource idle time does not require any permission
+        PermissionCapability::IdleTime => PermissionStatus {
+            capability,
+            state: PermissionState::Granted,
+            detail: Some("CGEventSource".to_string()),
+        },
+    }
+}
+
+pub fn request_permission(capability: PermissionCapability) -> PermissionStatus {
+    match capability {
+        PermissionCapability::Screenshots | PermissionCapability::WindowTitles => {
+            unsafe { CGRequestScreenCaptureAccess() };
+            set_screen_capture_requested();
+        }
+        PermissionCapability::IdleTime => {}
+    }
+    check_permission(capability)
+}
+
+fn screen_capture_state() -> PermissionState {
+    if unsafe { CGPreflightScreenCaptureAccess() } {
+        PermissionState::Granted
+    } else if screen_capture_requested() {
+        PermissionState::Denied
+    } else {
+        PermissionState::NotDetermined
+    }
+}
@@ -0,0 +1,50 @@
+use std::{path::PathBuf, process::Command};
+
+use crate::commands::{
+    error::{CommandError, CommandResult},
+    platform::descendants_deepest_first,
+};
+
+fn run(command: &mut Command) -> CommandResult<String> {
+    let output = command
+        .output()
+        .map_err(|e| CommandError::BackendUnavailable(e.to_string()))?;
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// `(pid, parent pid)` of every process, from `ps`.
+fn processes() -> CommandResult<Vec<(u32, u32)>> {
+    Ok(run(Command::new("ps").args(["-A", "-o", "pid=,ppid="]))?
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().map(|field| field.parse().ok());
+            Some((fields.next()??, fields.next()??))
+        })
+        .collect())
+}
+
+/// macOS has no `/proc`; ask `lsof` for the `cwd` descriptors instead.
+pub fn working_directories(pid: u32) -> CommandResult<Vec<PathBuf>> {
+    let mut pids = descendants_deepest_first(pid, &processes()?);
+    pids.push(pid);
+    let pid_list = pids
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+
+    // `-F pn` prints a `p<pid>` line per process followed by an `n<path>` line
+    let output = run(Command::new("lsof").args(["-a", "-d", "cwd", "-F", "pn", "-p", &pid_list]))?;
+    let mut directories: Vec<(u32, PathBuf)> = Vec::new();
+    let mut current = None;
+    for line in output.lines() {
+        if let Some(pid) = line.strip_prefix('p') {
+            current = pid.parse().ok();
+        } else if let (Some(pid), Some(path)) = (current, line.strip_prefix('n')) {
+            directories.push((pid, PathBuf::from(path)));
+        }
+    }
+    // lsof lists processes by pid, not in the order asked for
+    directories.sort_by_key(|(pid, _)| pids.iter().position(|p| p == pid));
+    Ok(directories.into_iter().map(|(_, path)| path).collect())
+}
@@ -0,0 +1,68 @@
+use image::{DynamicImage, ImageFormat};
+use objc2::{rc::Retained, AnyThread};
+use objc2_foundation::{NSArray, NSData, NSDictionary};
+use objc2_vision::{
+    VNImageRequestHandler, VNRecognizeTextRequest, VNRecognizedTextObservation, VNRequest,
+    VNRequestTextRecognitionLevel,
+};
+use std::io::Cursor;
+
+use crate::commands::{
+    error::{CommandError, CommandResult},
+    platform::{RecognizedText, ScreenRect},
+};
+
+/// Recognise text with the Vision framework, entirely in memory.
+/// docs: https://developer.apple.com/documentation/vision/vnrecognizetextrequest
+pub fn recognize_text(image: &DynamicImage) -> CommandResult<Vec<RecognizedText>> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| CommandError::Internal(e.to_string()))?;
+
+    let (width, height) = (image.width() as f64, image.height() as f64);
+
+    unsafe {
+        let data = NSData::with_bytes(&png);
+        let handler = VNImageRequestHandler::initWithData_options(
+            VNImageRequestHandler::alloc(),
+            &data,
+            &NSDictionary::new(),
+        );
+
+        let request = VNRecognizeTextRequest::new();
+        // The fast path is plenty for matching emails and keys, and runs per capture
+        request.setRecognitionLevel(VNRequestTextRecognitionLevel::Fast);
+        request.setUsesLanguageCorrection(false);
+
+        let requests: Retained<NSArray<VNRequest>> =
+            NSArray::from_retained_slice(&[Retained::into_super(Retained::into_super(
+                request.clone(),
+            ))]);
+        handler
+            .performRequests_error(&requests)
+            .map_err(|e| CommandError::BackendUnavailable(e.localizedDescription().to_string()))?;
+
+        let Some(results) = request.results() else {
+            return Ok(Vec::new());
+        };
+
+        Ok(results
+            .iter()
+            .filter_map(|observation: Retained<VNRecognizedTextObservation>| {
+                let candidate = observation.topCandidates(1).firstObject()?;
+                // Vision boxes are normalised with the origin in the bottom left corner
+                let bbox = observation.boundingBox();
+                Some(RecognizedText {
+                    text: candidate.string().to_string(),
+                    bounds: ScreenRect {
+                        x: bbox.origin.x * width,
+                        y: (1.0 - bbox.origin.y - bbox.size.height) * height,
+                        width: bbox.size.width * width,
+                        height: bbox.size.height * height,
+                    },
+                })
+            })
+            .collect())
+    }
+}
@@ -1,42 +1,43 @@
 use objc2::{msg_send, rc::Retained};
 use objc2_app_kit::{NSRunningApplication, NSWorkspace};
-use objc2_core_foundation::{CFArray, CFDictionary, CFNumber, CFString, CFType};
-use objc2_core_graphics::{kCGNullWindowID, CGWindowListCopyWindowInfo, CGWindowListOption};
-use objc2_foundation::NSString;
+use objc2_core_foundation::{CFArray, CFDictionary, CFNumber, CFString, CFType, CGPoint};
+use objc2_core_graphics::{
+    kCGNullWindowID, CGDirectDisplayID, CGDisplayBounds, CGError, CGGetDisplaysWithPoint,
+    CGWindowListCopyWindowInfo, CGWindowListOption,
+};
+use objc2_foundation::{NSString, NSURL};
 
-use crate::commands::platform::ApplicationInfo;
+use crate::commands::{
+    error::{CommandError, CommandResult},
+    platform::{ApplicationInfo, OpenWindow, ScreenRect, WindowBounds},
+};
 
-pub fn get_active_application() -> ApplicationInfo {
+pub fn get_active_application() -> CommandResult<ApplicationInfo> {
     let workspace = unsafe { NSWorkspace::sharedWorkspace() };
     let active_app: Option<Retained<NSRunningApplication>> =
         unsafe { msg_send![&*workspace, frontmostApplication] };
+    let Some(active_app) = active_app else {
+        return Err(CommandError::NoActiveWindow(
+            "NSWorkspace reported no frontmost application".to_string(),
+        ));
+    };
 
     // Application name
-    let application_name = match active_app.as_ref() {
-        Some(app) => {
-            let name: Option<Retained<NSString>> = unsafe { msg_send![&*app, localizedName] };
-            match name {
-                Some(n) => {
-                    let s = n.to_string();
-                    if s.is_empty() {
-                        "Unknown".to_string()
-                    } else {
-                        s
-                    }
-                }
-                None => "Unknown".to_string(),
-            }
-        }
-        None => "Unknown".to_string(),
-    };
+    let name: Option<Retained<NSString>> = unsafe { msg_send![&*active_app, localizedName] };
+    let application_name = name.map(|n| n.to_string()).unwrap_or_default();
 
     // Application process ID (needed to match correct window)
-    let pid = active_app
-        .as_ref()
-        .map(|app| unsafe { msg_send![&*app, processIdentifier] })
-        .unwrap_or(0);
+    let pid: i32 = unsafe { msg_send![&*active_app, processIdentifier] };
+
+    // Stable identifiers, unlike the localised name
+    let bundle_id: Option<Retained<NSString>> =
+        unsafe { msg_send![&*active_app, bundleIdentifier] };
+    let executable_url: Option<Retained<NSURL>> = unsafe { msg_send![&*active_app, executableURL] };
+    let executable_path = executable_url
+        .and_then(|url| unsafe { url.path() })
+        .map(|path| path.to_string());
 
-    // 3. Get the title of the window belonging to this PID
+    // 3. Get the title and bounds of the window belonging to this PID
     let window_list_info = unsafe {
         CGWindowListCopyWindowInfo(
             CGWindowListOption::OptionOnScreenOnly | CGWindowListOption::ExcludeDesktopElements,
@@ -44,53 +45,161 @@ pub fn get_active_application() -> ApplicationInfo {
         )
     };
 
-    let window_title = {
-        match window_list_info {
-            Some(array) => {
-                // Re-type the array to the actual element type returned by CGWindowListCopyWindowInfo
-                let raw = array.as_ref();
-                let dicts: &CFArray<CFDictionary<CFString, CFType>> = unsafe {
-                    &*(raw as *const CFArray as *const CFArray<CFDictionary<CFString, CFType>>)
-                };
-
-                dicts
-                    .iter()
-                    .filter_map(
-                        |dret: objc2_core_foundation::CFRetained<
-                            CFDictionary<CFString, CFType>,
-                        >| {
-                            let d: &CFDictionary<CFString, CFType> = dret.as_ref();
-
-                            let key_pid = CFString::from_static_str("kCGWindowOwnerPID");
-                            let key_name = CFString::from_static_str("kCGWindowName");
-
-                            let owner_pid = d
-                                .get(&key_pid)
-                                .and_then(|v| v.downcast::<CFNumber>().ok())
-                                .and_then(|n| n.as_i64());
-
-                            let window_name = d
-                                .get(&key_name)
-                                .and_then(|v| v.downcast::<CFString>().ok())
-                                .map(|s| s.to_string());
-
-                            match (owner_pid, window_name) {
-                                (Some(owner), Some(name)) if owner == pid as i64 => {
-                                    Some(name.to_string())
-                                }
-                                _ => None,
-                            }
-                        },
-                    )
-                    .next()
-                    .unwrap_or_default()
-            }
-            None => String::new(),
+    let (window_title, window_id, window_bounds) = match window_list_info {
+        Some(array) => {
+            // Re-type the array to the actual element type returned by CGWindowListCopyWindowInfo
+            let raw = array.as_ref();
+            let dicts: &CFArray<CFDictionary<CFString, CFType>> = unsafe {
+                &*(raw as *const CFArray as *const CFArray<CFDictionary<CFString, CFType>>)
+            };
+
+            dicts
+                .iter()
+                .find(|d| {
+                    number(d, "kCGWindowOwnerPID") == Some(pid as f64)
+                        && number(d, "kCGWindowLayer") == Some(0.0)
+                })
+                .map(|d| {
+                    let name = d
+                        .get(&CFString::from_static_str("kCGWindowName"))
+                        .and_then(|v| v.downcast::<CFString>().ok())
+                        .map(|s| s.to_string())
+                        .unwrap_or_default();
+                    let id = number(&d, "kCGWindowNumber").map(|n| n as u32);
+                    (name, id, window_bounds(&d))
+                })
+                .unwrap_or_default()
+        }
+        None => (String::new(), None, None),
+    };
+
+    // 4. The display under the centre of the window
+    let display_id = window_bounds.and_then(|bounds| {
+        let (x, y) = bounds.center();
+        let mut display: CGDirectDisplayID = 0;
+        let mut count: u32 = 0;
+        let error =
+            unsafe { CGGetDisplaysWithPoint(CGPoint { x, y }, 1, &mut display, &mut count) };
+        (error == CGError::Success && count == 1).then_some(display)
+    });
+
+    // Fullscreen windows cover their whole display, menu bar included
+    let is_fullscreen = match (window_bounds, display_id) {
+        (Some(window), Some(display)) => {
+            let display = unsafe { CGDisplayBounds(display) };
+            window.x == display.origin.x
+                && window.y == display.origin.y
+                && window.width == display.size.width
+                && window.height == display.size.height
         }
+        _ => false,
     };
 
-    ApplicationInfo {
+    Ok(ApplicationInfo {
         app_name: application_name,
         window_title,
+        pid: u32::try_from(pid).ok(),
+        executable_path,
+        bundle_id: bundle_id.map(|id| id.to_string()),
+        window_id,
+        window_bounds,
+        display_id,
+        is_fullscreen,
+    })
+}
+
+fn number(d: &CFDictionary<CFString, CFType>, key: &'static str) -> Option<f64> {
+    d.get(&CFString::from_static_str(key))
+        .and_then(|v| v.downcast::<CFNumber>().ok())
+        .and_then(|n| n.as_f64())
+}
+
+fn string(d: &CFDictionary<CFString, CFType>, key: &'static str) -> Option<String> {
+    d.get(&CFString::from_static_str(key))
+        .and_then(|v| v.downcast::<CFString>().ok())
+        .map(|s| s.to_string())
+}
+
+/// The `kCGWindowBounds` dictionary of a window info entry.
+fn window_bounds(d: &CFDictionary<CFString, CFType>) -> Option<ScreenRect> {
+    let bounds = d
+        .get(&CFString::from_static_str("kCGWindowBounds"))
+        .and_then(|v| v.downcast::<CFDictionary>().ok())?;
+    let bounds: &CFDictionary<CFString, CFType> = unsafe {
+        &*(bounds.as_ref() as *const CFDictionary as *const CFDictionary<CFString, CFType>)
+    };
+
+    Some(ScreenRect {
+        x: number(bounds, "X")?,
+        y: number(bounds, "Y")?,
+        width: number(bounds, "Width")?,
+        height: number(bounds, "Height")?,
+    })
+}
+
+/// On-screen windows of regular applications, front to back.
+pub fn get_visible_windows() -> CommandResult<Vec<WindowBounds>> {
+    let window_list_info = unsafe {
+        CGWindowListCopyWindowInfo(
+            CGWindowListOption::OptionOnScreenOnly | CGWindowListOption::ExcludeDesktopElements,
+            kCGNullWindowID,
+        )
+    }
+    .ok_or_else(|| {
+        CommandError::BackendUnavailable("CGWindowListCopyWindowInfo returned nothing".to_string())
+    })?;
+
+    let raw = window_list_info.as_ref();
+    let dicts: &CFArray<CFDictionary<CFString, CFType>> =
+        unsafe { &*(raw as *const CFArray as *const CFArray<CFDictionary<CFString, CFType>>) };
+
+    Ok(dicts
+        .iter()
+        .filter_map(|d| {
+            // Layer 0 holds normal windows; menu bar, dock and overlays live above it
+            if number(&d, "kCGWindowLayer")? != 0.0 {
+                return None;
+            }
+            let app_name = d
+                .get(&CFString::from_static_str("kCGWindowOwnerName"))
+                .and_then(|v| v.downcast::<CFString>().ok())
+                .map(|s| s.to_string())?;
+
+            Some(WindowBounds {
+                app_name,
+                bounds: window_bounds(&d)?,
+            })
+        })
+        .collect())
+}
+
+/// Windows of regular applications, including minimised ones and those on other spaces.
+/// `kCGWindowName` is only filled in with the screen recording permission.
+pub fn get_open_windows() -> CommandResult<Vec<OpenWindow>> {
+    let window_list_info = unsafe {
+        CGWindowListCopyWindowInfo(
+            CGWindowListOption::OptionAll | CGWindowListOption::ExcludeDesktopElements,
+            kCGNullWindowID,
+        )
     }
+    .ok_or_else(|| {
+        CommandError::BackendUnavailable("CGWindowListCopyWindowInfo returned nothing".to_string())
+    })?;
+
+    let raw = window_list_info.as_ref();
+    let dicts: &CFArray<CFDictionary<CFString, CFType>> =
+        unsafe { &*(raw as *const CFArray as *const CFArray<CFDictionary<CFString, CFType>>) };
+
+    Ok(dicts
+        .iter()
+        .filter_map(|d| {
+            if number(&d, "kCGWindowLayer")? != 0.0 {
+                return None;
+            }
+            Some(OpenWindow {
+                app_name: string(&d, "kCGWindowOwnerName")?,
+                title: string(&d, "kCGWindowName")?,
+            })
+        })
+        .collect())
 }
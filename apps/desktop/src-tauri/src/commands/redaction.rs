@@ -0,0 +1,117 @@
+pub mod sensitive_text;
+
+use image::{imageops, DynamicImage, GenericImageView};
+
+use crate::commands::{
+    error::CommandResult,
+    platform::{DisplayCapture, PlatformImpl, ScreenRect, WindowActivityCapture},
+    settings::RedactionSettings,
+};
+
+/// Size of the blocks a redacted region is pixelated into before blurring.
+/// Large enough that text can't be recovered from what remains.
+const PIXELATE_BLOCK: u32 = 16;
+const BLUR_SIGMA: f32 = 4.0;
+/// Extra pixels around OCR lines, whose boxes are tight around the glyphs.
+const TEXT_PADDING: f64 = 4.0;
+
+/// Blur the configured regions, excluded app windows and sensitive text of each capture.
+/// Runs before anything is encoded, so the original pixels are never written to disk.
+/// Fails rather than returning unredacted images when detection is not possible.
+pub fn redact_screenshots(
+    settings: &RedactionSettings,
+    captures: Vec<DisplayCapture>,
+) -> CommandResult<Vec<DisplayCapture>> {
+    let excluded_windows = if settings.excluded_apps.is_empty() {
+        Vec::new()
+    } else {
+        PlatformImpl::visible_windows()?
+            .into_iter()
+            .filter(|w| {
+                settings
+                    .excluded_apps
+                    .iter()
+                    .any(|app_name| app_name.eq_ignore_ascii_case(&w.app_name))
+            })
+            .map(|w| w.bounds)
+            .collect()
+    };
+
+    captures
+        .into_iter()
+        .map(|capture| redact_capture(settings, capture, &excluded_windows))
+        .collect()
+}
+
+fn redact_capture(
+    settings: &RedactionSettings,
+    mut capture: DisplayCapture,
+    excluded_windows: &[ScreenRect],
+) -> CommandResult<DisplayCapture> {
+    let (width, height) = (capture.image.width() as f64, capture.image.height() as f64);
+    let mut regions = Vec::new();
+
+    for region in settings
+        .regions
+        .iter()
+        .filter(|r| r.display == capture.display_id)
+    {
+        regions.push(ScreenRect {
+            x: region.x * width,
+            y: region.y * height,
+            width: region.width * width,
+            height: region.height * height,
+        });
+    }
+
+    for window in excluded_windows {
+        regions.push(capture.to_image_rect(window));
+    }
+
+    if settings.detect_sensitive_text {
+        for line in PlatformImpl::recognize_text(&capture.image)? {
+            if sensitive_text::contains_sensitive_text(&line.text) {
+                regions.push(ScreenRect {
+                    x: line.bounds.x - TEXT_PADDING,
+                    y: line.bounds.y - TEXT_PADDING,
+                    width: line.bounds.width + 2.0 * TEXT_PADDING,
+                    height: line.bounds.height + 2.0 * TEXT_PADDING,
+                });
+            }
+        }
+    }
+
+    for region in regions {
+        obscure(&mut capture.image, &region);
+    }
+    Ok(capture)
+}
+
+/// Pixelate and blur a region in place. Regions outside the image are clipped.
+fn obscure(image: &mut DynamicImage, region: &ScreenRect) {
+    let Some((x0, y0, w, h)) = pixel_bounds(image, region) else {
+        return;
+    };
+
+    let obscured = image
+        .crop_imm(x0, y0, w, h)
+        .resize_exact(
+            w.div_ceil(PIXELATE_BLOCK),
+            h.div_ceil(PIXELATE_BLOCK),
+            imageops::FilterType::Triangle,
+        )
+        .resize_exact(w, h, imageops::FilterType::Nearest)
+        .blur(BLUR_SIGMA);
+    imageops::replace(image, &obscured, x0 as i64, y0 as i64);
+}
+
+/// A rectangle in image pixels clipped to the image, as `(x, y, width, height)`.
+/// `None` when nothing of it is inside the image.
+pub fn pixel_bounds(image: &DynamicImage, region: &ScreenRect) -> Option<(u32, u32, u32, u32)> {
+    let (width, height) = image.dimensions();
+    let x0 = region.x.max(0.0).floor() as u32;
+    let y0 = region.y.max(0.0).floor() as u32;
+    let x1 = ((region.x + region.width).ceil().max(0.0) as u32).min(width);
+    let y1 = ((region.y + region.height).ceil().max(0.0) as u32).min(height);
+    (x0 < x1 && y0 < y1).then(|| (x0, y0, x1 - x0, y1 - y0))
+}
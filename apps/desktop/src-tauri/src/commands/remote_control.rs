@@ -0,0 +1,118 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    commands::{
+        error::{CommandError, CommandResult},
+        single_instance::{exit_code, resolve_project},
+        timer::{running_timer, stop_timer, switch_timer},
+        tracking_pause::{current_pause, pause_tracking, resume_tracking},
+        tray::{format_elapsed, recent_projects},
+    },
+    instance_ipc::{InstanceRequest, InstanceResponse},
+};
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn to_json(value: impl serde::Serialize) -> CommandResult<serde_json::Value> {
+    serde_json::to_value(value).map_err(|e| CommandError::Internal(e.to_string()))
+}
+
+/// A project's name in the tray, falling back to its id.
+fn project_label(app: &tauri::AppHandle, project_id: &str) -> String {
+    recent_projects(app)
+        .into_iter()
+        .find(|project| project.id == project_id)
+        .map_or_else(|| project_id.to_string(), |project| project.name)
+}
+
+fn status(app: &tauri::AppHandle) -> CommandResult<InstanceResponse> {
+    let timer = running_timer(app);
+    let pause = current_pause(app);
+    let mut lines = vec![match &timer {
+        Some(timer) => format!(
+            "Tracking {} for {}",
+            project_label(app, &timer.project_id),
+            format_elapsed(now().saturating_sub(timer.started_at))
+        ),
+        None => "No timer running".to_string(),
+    }];
+    match pause.as_ref().map(|pause| pause.until) {
+        Some(Some(until)) => lines.push(format!(
+            "Tracking paused for another {} minutes",
+            until.saturating_sub(now()).div_ceil(60)
+        )),
+        Some(None) => lines.push("Tracking paused until resumed".to_string()),
+        None => {}
+    }
+    Ok(InstanceResponse {
+        data: Some(serde_json::json!({ "timer": timer, "pause": pause })),
+        ..InstanceResponse::ok(lines.join("\n"))
+    })
+}
+
+fn run_request(
+    app: &tauri::AppHandle,
+    request: InstanceRequest,
+) -> CommandResult<InstanceResponse> {
+    Ok(match request {
+        InstanceRequest::Status => return status(app),
+        InstanceRequest::Start { project } => {
+            let project_id = resolve_project(app, &project)?;
+            let label = project_label(app, &project_id);
+            let stopped = switch_timer(app.clone(), project_id, None, None)?;
+            InstanceResponse {
+                data: Some(to_json(running_timer(app))?),
+                ..InstanceResponse::ok(match stopped {
+                    Some(entry) => format!(
+                        "Stopped {} after {}, started {}",
+                        project_label(app, &entry.project_id),
+                        format_elapsed(entry.stopped_at - entry.started_at),
+                        label
+                    ),
+                    None => format!("Started {}", label),
+                })
+            }
+        }
+        InstanceRequest::Stop => {
+            let entry = stop_timer(app.clone(), None)?;
+            InstanceResponse {
+                data: Some(to_json(&entry)?),
+                ..InstanceResponse::ok(format!(
+                    "Stopped {} after {}",
+                    project_label(app, &entry.project_id),
+                    format_elapsed(entry.stopped_at - entry.started_at)
+                ))
+            }
+        }
+        InstanceRequest::Pause { minutes } => {
+            let pause = pause_tracking(app.clone(), minutes)?;
+            InstanceResponse {
+                data: Some(to_json(&pause)?),
+                ..InstanceResponse::ok(match minutes {
+                    Some(minutes) => format!("Paused tracking for {} minutes", minutes),
+                    None => "Paused tracking until resumed".to_string(),
+                })
+            }
+        }
+        InstanceRequest::Resume => {
+            resume_tracking(app.clone())?;
+            InstanceResponse::ok("Resumed tracking")
+        }
+        InstanceRequest::Launch { .. } => {
+            return Err(CommandError::Internal(
+                "Launch requests are routed by `single_instance`".to_string(),
+            ))
+        }
+    })
+}
+
+/// Answer a request of the `recount` CLI.
+pub fn handle_cli_request(app: &tauri::AppHandle, request: InstanceRequest) -> InstanceResponse {
+    run_request(app, request)
+        .unwrap_or_else(|error| InstanceResponse::failed(exit_code(&error), error.to_string()))
+}
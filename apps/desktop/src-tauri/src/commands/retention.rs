@@ -0,0 +1,347 @@
+use chrono::{Days, Local};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tauri::Manager;
+use tauri_specta::Event;
+
+use crate::{
+    activity_log,
+    commands::{
+        calendar::write_atomically,
+        error::{CommandError, CommandResult},
+        screenshot_vault::{
+            downsample_screenshot, forget_screenshot_references, record_downsampled_screenshots,
+            stored_screenshots, thumbnail_path, StoredScreenshot,
+        },
+        search_activity::with_index,
+        settings::current_settings,
+    },
+};
+
+/// How often the background job applies the retention settings.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Width old screenshots are downsampled to; enough to recognise what was on screen.
+const DOWNSAMPLE_MAX_WIDTH: u32 = 960;
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+const BILLED_RANGES_FILE: &str = "billed_ranges.json";
+
+/// Serialises the scheduled job and manual runs.
+static RETENTION_LOCK: Mutex<()> = Mutex::new(());
+
+/// A period of time, in seconds since the epoch, `start` inclusive and `end` exclusive.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, specta::Type)]
+pub struct TimeRange {
+    start: u32,
+    end: u32,
+}
+
+impl TimeRange {
+    /// Whether any moment from `start` up to and including `end` falls in the range.
+    fn overlaps(&self, start: u32, end: u32) -> bool {
+        self.start <= end && start < self.end
+    }
+}
+
+/// A screenshot reused by later snapshots is billed when any of those snapshots is.
+fn is_billed(ranges: &[TimeRange], screenshot: &StoredScreenshot) -> bool {
+    ranges
+        .iter()
+        .any(|range| range.overlaps(screenshot.timestamp, screenshot.last_referenced))
+}
+
+/// Whole days since a screenshot was last part of a snapshot, so one that deduplication
+/// keeps reusing does not age.
+fn age_days(screenshot: &StoredScreenshot, now: u32) -> u32 {
+    now.saturating_sub(screenshot.last_referenced) / SECONDS_PER_DAY
+}
+
+/// Which screenshots to delete, in order, to bring their total size down to `max_bytes`:
+/// unbilled ones first, least recently referenced first within each group.
+fn over_size_cap<'a>(
+    screenshots: &'a [StoredScreenshot],
+    ranges: &[TimeRange],
+    max_bytes: u64,
+) -> Vec<&'a StoredScreenshot> {
+    let mut total: u64 = screenshots.iter().map(|s| s.bytes).sum();
+    let mut candidates: Vec<_> = screenshots.iter().collect();
+    candidates.sort_by_key(|s| (is_billed(ranges, s), s.last_referenced));
+    candidates
+        .into_iter()
+        .take_while(|screenshot| {
+            let over = total > max_bytes;
+            total = total.saturating_sub(screenshot.bytes);
+            over
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, specta::Type)]
+pub struct RetentionReport {
+    deleted_files: u32,
+    downsampled_files: u32,
+    #[specta(type = f64)]
+    reclaimed_bytes: u64,
+}
+
+/// Emitted after every scheduled retention run.
+#[derive(Debug, Clone, serde::Serialize, specta::Type, tauri_specta::Event)]
+pub struct RetentionCompleted {
+    report: RetentionReport,
+}
+
+#[derive(Debug, Clone, serde::Serialize, specta::Type)]
+pub struct DailyStorageUsage {
+    /// Start of the UTC day, in seconds since the epoch.
+    day: u32,
+    files: u32,
+    #[specta(type = f64)]
+    bytes: u64,
+}
+
+fn billed_ranges_path(app: &tauri::AppHandle) -> CommandResult<PathBuf> {
+    Ok(app.path().app_local_data_dir()?.join(BILLED_RANGES_FILE))
+}
+
+fn load_billed_ranges(app: &tauri::AppHandle) -> CommandResult<Vec<TimeRange>> {
+    match fs::read(billed_ranges_path(app)?) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map_err(|e| CommandError::Internal(format!("Corrupt billed ranges: {}", e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn delete_screenshot(
+    screenshot: &StoredScreenshot,
+    report: &mut RetentionReport,
+    deleted: &mut HashSet<String>,
+) -> CommandResult<()> {
+    match fs::remove_file(&screenshot.path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+    if let Some(thumbnail) = thumbnail_path(&screenshot.path) {
+        let _ = fs::remove_file(thumbnail);
+    }
+    report.deleted_files += 1;
+    report.reclaimed_bytes += screenshot.bytes;
+    deleted.insert(screenshot.path.to_string_lossy().to_string());
+    Ok(())
+}
+
+/// Apply the retention settings to the stored screenshots.
+pub fn run_retention(app: &tauri::AppHandle) -> CommandResult<RetentionReport> {
+    let _guard = RETENTION_LOCK
+        .lock()
+        .map_err(|_| CommandError::Internal("Retention lock poisoned".to_string()))?;
+
+    let settings = current_settings(app)?.retention;
+    let billed_ranges = load_billed_ranges(app)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or(0);
+
+    let mut report = RetentionReport::default();
+    let mut deleted = HashSet::new();
+    let mut downsampled = Vec::new();
+    let mut kept = Vec::new();
+
+    for mut screenshot in stored_screenshots(app)? {
+        let age_days = age_days(&screenshot, now);
+        let max_age_days = if is_billed(&billed_ranges, &screenshot) {
+            settings.billed_max_age_days
+        } else {
+            settings.max_age_days
+        };
+
+        if max_age_days.is_some_and(|max| age_days >= max) {
+            delete_screenshot(&screenshot, &mut report, &mut deleted)?;
+            continue;
+        }
+
+        if !screenshot.downsampled
+            && settings
+                .downsample_after_days
+                .is_some_and(|days| age_days >= days)
+        {
+            // Downsampling needs the unlocked vault; a screenshot that can't be
+            // downsampled now is picked up again by the next run
+            match downsample_screenshot(app, &screenshot.path, DOWNSAMPLE_MAX_WIDTH) {
+                Ok(Some(bytes)) => {
+                    // The thumbnail stays as it is
+                    let bytes = bytes + screenshot.thumbnail_bytes;
+                    report.downsampled_files += 1;
+                    report.reclaimed_bytes += screenshot.bytes.saturating_sub(bytes);
+                    screenshot.bytes = bytes;
+                    downsampled.push(screenshot.path.clone());
+                }
+                Ok(None) => downsampled.push(screenshot.path.clone()),
+                Err(_) => {}
+            }
+        }
+        kept.push(screenshot);
+    }
+    record_downsampled_screenshots(app, &downsampled)?;
+
+    if let Some(max_megabytes) = settings.max_total_megabytes {
+        let max_bytes = max_megabytes as u64 * 1024 * 1024;
+        for screenshot in over_size_cap(&kept, &billed_ranges, max_bytes) {
+            delete_screenshot(screenshot, &mut report, &mut deleted)?;
+        }
+    }
+
+    if !deleted.is_empty() {
+        with_index(app, |index| index.remove(&deleted))?;
+        forget_screenshot_references(app, &deleted)?;
+    }
+
+    // The activity log does not know what was billed, so it is kept as long as the
+    // longest lived screenshots
+    let log_max_age_days = settings
+        .max_age_days
+        .zip(settings.billed_max_age_days)
+        .map(|(unbilled, billed)| unbilled.max(billed));
+    if let Some(cutoff) = log_max_age_days.and_then(|days| {
+        Local::now()
+            .date_naive()
+            .checked_sub_days(Days::new(days.into()))
+    }) {
+        activity_log::remove_before(&app.path().app_local_data_dir()?, cutoff)?;
+    }
+    Ok(report)
+}
+
+/// Run retention now and then every `RETENTION_INTERVAL` on a background thread.
+pub fn schedule_retention(app: tauri::AppHandle) {
+    thread::spawn(move || loop {
+        if let Ok(report) = run_retention(&app) {
+            let _ = RetentionCompleted { report }.emit(&app);
+        }
+        thread::sleep(RETENTION_INTERVAL);
+    });
+}
+
+/// Replace the periods covered by billed time entries.
+/// Screenshots taken in these periods are kept for `billed_max_age_days`.
+#[tauri::command]
+#[specta::specta]
+pub fn set_billed_ranges(app: tauri::AppHandle, ranges: Vec<TimeRange>) -> CommandResult<()> {
+    let bytes = serde_json::to_vec(&ranges).map_err(|e| CommandError::Internal(e.to_string()))?;
+    write_atomically(billed_ranges_path(&app)?, &bytes)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn apply_retention(app: tauri::AppHandle) -> CommandResult<RetentionReport> {
+    tauri::async_runtime::spawn_blocking(move || run_retention(&app)).await?
+}
+
+/// Disk used by screenshots, per UTC day, oldest first.
+#[tauri::command]
+#[specta::specta]
+pub fn storage_usage(app: tauri::AppHandle) -> CommandResult<Vec<DailyStorageUsage>> {
+    let mut days: BTreeMap<u32, DailyStorageUsage> = BTreeMap::new();
+    for screenshot in stored_screenshots(&app)? {
+        let day = screenshot.timestamp - screenshot.timestamp % SECONDS_PER_DAY;
+        let usage = days.entry(day).or_insert(DailyStorageUsage {
+            day,
+            files: 0,
+            bytes: 0,
+        });
+        usage.files += 1;
+        usage.bytes += screenshot.bytes;
+    }
+    Ok(days.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screenshot(timestamp: u32, last_referenced: u32) -> StoredScreenshot {
+        StoredScreenshot {
+            path: PathBuf::from(format!("{}_1_recount.jpg.enc", timestamp)),
+            timestamp,
+            last_referenced,
+            bytes: 0,
+            thumbnail_bytes: 0,
+            downsampled: false,
+        }
+    }
+
+    fn sized(timestamp: u32, bytes: u64) -> StoredScreenshot {
+        StoredScreenshot {
+            bytes,
+            ..screenshot(timestamp, timestamp)
+        }
+    }
+
+    #[test]
+    fn bills_screenshots_reused_in_a_billed_range() {
+        let ranges = [TimeRange {
+            start: 1_000,
+            end: 2_000,
+        }];
+        // Captured before the range, reused by snapshots inside it
+        assert!(is_billed(&ranges, &screenshot(500, 1_200)));
+        assert!(is_billed(&ranges, &screenshot(500, 1_000)));
+        assert!(is_billed(&ranges, &screenshot(1_999, 1_999)));
+        assert!(!is_billed(&ranges, &screenshot(500, 999)));
+        // The end is exclusive
+        assert!(!is_billed(&ranges, &screenshot(2_000, 2_500)));
+        assert!(!is_billed(&[], &screenshot(1_500, 1_500)));
+    }
+
+    #[test]
+    fn ages_screenshots_from_their_last_reference() {
+        let now = 100 * SECONDS_PER_DAY;
+        // Captured 90 days ago, but still on screen yesterday
+        assert_eq!(
+            age_days(
+                &screenshot(10 * SECONDS_PER_DAY, now - SECONDS_PER_DAY),
+                now
+            ),
+            1
+        );
+        assert_eq!(
+            age_days(&screenshot(10 * SECONDS_PER_DAY, 10 * SECONDS_PER_DAY), now),
+            90
+        );
+        assert_eq!(age_days(&screenshot(now, now + 60), now), 0);
+    }
+
+    #[test]
+    fn deletes_unbilled_then_oldest_screenshots_over_the_size_cap() {
+        let ranges = [TimeRange {
+            start: 1_000,
+            end: 2_000,
+        }];
+        let screenshots = [
+            sized(1_100, 30),
+            sized(500, 30),
+            sized(3_000, 30),
+            sized(1_500, 30),
+            sized(2_500, 30),
+        ];
+        let deleted = |max_bytes| -> Vec<u32> {
+            over_size_cap(&screenshots, &ranges, max_bytes)
+                .iter()
+                .map(|s| s.timestamp)
+                .collect()
+        };
+
+        assert_eq!(deleted(150), Vec::<u32>::new());
+        assert_eq!(deleted(149), [500]);
+        assert_eq!(deleted(90), [500, 2_500]);
+        assert_eq!(deleted(30), [500, 2_500, 3_000, 1_100]);
+        assert_eq!(deleted(0), [500, 2_500, 3_000, 1_100, 1_500]);
+    }
+}
@@ -0,0 +1,331 @@
+use image::{imageops::FilterType, DynamicImage};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tauri::Manager;
+use zeroize::Zeroizing;
+
+use crate::commands::{
+    calendar::write_atomically,
+    error::{CommandError, CommandResult},
+    screenshot_vault::{
+        record_screenshot_references, save_screenshots, screenshots_dir, DataCipher,
+    },
+    settings::CaptureSettings,
+};
+
+/// Size of the difference hash of a capture: every pixel of the capture scaled down to
+/// this size is compared with its right neighbour. Fine enough that a pixel of a full HD
+/// screen covers about a word, so editing text flips several bits.
+const HASH_WIDTH: u32 = 128;
+const HASH_HEIGHT: u32 = 72;
+/// Two bits per comparison, for brighter and for darker.
+const HASH_BITS: u32 = 2 * HASH_WIDTH * HASH_HEIGHT;
+/// How much two neighbouring pixels must differ to set a bit. Without it, scaling and
+/// color noise flip the bits of flat areas.
+const GRADIENT_TOLERANCE: u8 = 8;
+/// The last capture of every display, sealed like the screenshots, so a restart does not
+/// store an unchanged screen again. Key rotation re-encrypts it with the screenshots.
+const HISTORY_FILE: &str = "dedup.json.enc";
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PreviousCapture {
+    hash: Vec<u64>,
+    path: String,
+}
+
+/// The last stored capture of every display, used to skip storing unchanged screens.
+/// Loaded from disk on first use.
+#[derive(Default)]
+pub struct ScreenshotHistory(Mutex<Option<HashMap<u32, PreviousCapture>>>);
+
+pub struct DeduplicatedScreenshots {
+    pub paths: Vec<String>,
+    /// How much the screen changed since the previous capture, 0 (identical) to 1.
+    /// The largest change over all displays; `None` without a previous capture.
+    pub screen_change: Option<f32>,
+}
+
+/// Difference hash of the capture scaled down to `HASH_WIDTH`x`HASH_HEIGHT`, row by row:
+/// for every pixel, one bit is set when it is brighter than its right neighbour and the
+/// next when it is darker.
+pub fn difference_hash(image: &DynamicImage) -> Vec<u64> {
+    let pixels = image
+        .resize_exact(HASH_WIDTH + 1, HASH_HEIGHT, FilterType::Triangle)
+        .to_luma8();
+    let mut hash = vec![0u64; HASH_BITS.div_ceil(64) as usize];
+    for y in 0..HASH_HEIGHT {
+        for x in 0..HASH_WIDTH {
+            let [left] = pixels.get_pixel(x, y).0;
+            let [right] = pixels.get_pixel(x + 1, y).0;
+            let bit = 2 * (y * HASH_WIDTH + x) as usize;
+            if left > right.saturating_add(GRADIENT_TOLERANCE) {
+                hash[bit / 64] |= 1 << (bit % 64);
+            }
+            if right > left.saturating_add(GRADIENT_TOLERANCE) {
+                hash[bit / 64] |= 1 << (bit % 64 + 1);
+            }
+        }
+    }
+    hash
+}
+
+/// Number of bits that differ between two hashes.
+fn hamming_distance(previous: &[u64], current: &[u64]) -> u32 {
+    previous
+        .iter()
+        .zip(current)
+        .map(|(a, b)| (a ^ b).count_ones())
+        .sum()
+}
+
+fn history_path(app: &tauri::AppHandle) -> PathBuf {
+    screenshots_dir(app).join(HISTORY_FILE)
+}
+
+/// The history saved by a previous run. Empty when there is none or it cannot be opened,
+/// which only costs storing the current screens once more.
+fn read_history(
+    path: &Path,
+    open: impl Fn(&[u8]) -> io::Result<Zeroizing<Vec<u8>>>,
+) -> HashMap<u32, PreviousCapture> {
+    std::fs::read(path)
+        .ok()
+        .and_then(|data| open(&data).ok())
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .unwrap_or_default()
+}
+
+fn write_history(
+    path: PathBuf,
+    history: &HashMap<u32, PreviousCapture>,
+    seal: impl Fn(&[u8]) -> io::Result<Vec<u8>>,
+) -> CommandResult<()> {
+    let json = Zeroizing::new(
+        serde_json::to_vec(history).map_err(|e| CommandError::Internal(e.to_string()))?,
+    );
+    write_atomically(path, &seal(&json)?)
+}
+
+/// Store captured display images, keyed by display id, reusing the previous file of a
+/// display when its hash is at most `dedup_max_distance` bits away from the previous
+/// capture's.
+pub fn save_deduplicated_screenshots(
+    app: &tauri::AppHandle,
+    settings: &CaptureSettings,
+    timestamp: u32,
+    images: Vec<(u32, DynamicImage)>,
+) -> CommandResult<DeduplicatedScreenshots> {
+    let max_distance = settings.dedup_max_distance;
+    let history = app.state::<ScreenshotHistory>();
+    let mut history = history
+        .0
+        .lock()
+        .map_err(|_| CommandError::Internal("Screenshot history lock poisoned".to_string()))?;
+    let previous = match &mut *history {
+        Some(previous) => previous,
+        None => {
+            // Dropped right away, since saving takes the keyring again
+            let cipher = DataCipher::load(app)?;
+            history.insert(read_history(&history_path(app), |data| cipher.open(data)))
+        }
+    };
+
+    let mut hashes: Vec<Vec<u64>> = images
+        .iter()
+        .map(|(_, image)| difference_hash(image))
+        .collect();
+    let mut paths: Vec<Option<String>> = vec![None; images.len()];
+    let mut screen_change: Option<f32> = None;
+    let mut changed = Vec::new();
+    let mut changed_idx = Vec::new();
+    let mut reused = Vec::new();
+
+    for (idx, (display_id, image)) in images.into_iter().enumerate() {
+        if let Some(prev) = previous.get(&display_id) {
+            let distance = hamming_distance(&prev.hash, &hashes[idx]);
+            let change = distance as f32 / HASH_BITS as f32;
+            screen_change = Some(screen_change.map_or(change, |c| c.max(change)));
+
+            // Retention may have deleted the previous file in the meantime
+            if distance <= max_distance && Path::new(&prev.path).exists() {
+                paths[idx] = Some(prev.path.clone());
+                reused.push(prev.path.clone());
+                continue;
+            }
+        }
+        changed.push((display_id, image));
+        changed_idx.push(idx);
+    }
+
+    // Retention keeps a reused file as long as any snapshot referencing it is billed
+    record_screenshot_references(app, &reused, timestamp)?;
+    if changed.is_empty() {
+        return Ok(DeduplicatedScreenshots {
+            paths: paths.into_iter().flatten().collect(),
+            screen_change,
+        });
+    }
+    let saved = save_screenshots(app, settings, timestamp, &changed)?;
+    for ((idx, (display_id, _)), path) in changed_idx.into_iter().zip(&changed).zip(saved) {
+        previous.insert(
+            *display_id,
+            PreviousCapture {
+                hash: std::mem::take(&mut hashes[idx]),
+                path: path.clone(),
+            },
+        );
+        paths[idx] = Some(path);
+    }
+    let cipher = DataCipher::load(app)?;
+    write_history(history_path(app), previous, |json| cipher.seal(json))?;
+
+    Ok(DeduplicatedScreenshots {
+        paths: paths.into_iter().flatten().collect(),
+        screen_change,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// As in `CaptureSettings::default`.
+    const DEFAULT_MAX_DISTANCE: u32 = 4;
+
+    /// Dark glyph-like strokes: 2 pixel bars every 4 pixels.
+    fn draw_word(image: &mut RgbImage, x: u32, y: u32, width: u32) {
+        for dx in (0..width).filter(|dx| dx % 4 < 2) {
+            for dy in 0..14 {
+                image.put_pixel(x + dx, y + dy, Rgb([30, 30, 30]));
+            }
+        }
+    }
+
+    /// A full HD window with a title bar and a few lines of text.
+    fn screen() -> RgbImage {
+        let mut image = RgbImage::from_pixel(1920, 1080, Rgb([250, 250, 250]));
+        for x in 0..1920 {
+            for y in 0..40 {
+                image.put_pixel(x, y, Rgb([60, 60, 70]));
+            }
+        }
+        for line in 0..10 {
+            let y = 100 + line * 24;
+            draw_word(&mut image, 80, y, 120);
+            draw_word(&mut image, 216, y, 64 + line * 20);
+        }
+        image
+    }
+
+    fn distance(previous: RgbImage, current: RgbImage) -> u32 {
+        hamming_distance(
+            &difference_hash(&DynamicImage::ImageRgb8(previous)),
+            &difference_hash(&DynamicImage::ImageRgb8(current)),
+        )
+    }
+
+    #[test]
+    fn reuses_near_duplicates() {
+        assert_eq!(distance(screen(), screen()), 0);
+
+        // Color noise over the whole screen and a blinking text cursor
+        let mut noisy = screen();
+        for (x, y, pixel) in noisy.enumerate_pixels_mut() {
+            let noise = ((x * 7 + y * 13) % 5) as u8;
+            pixel.0 = pixel.0.map(|c| c.saturating_add(noise).saturating_sub(2));
+        }
+        for y in 316..334 {
+            for x in 500..502 {
+                noisy.put_pixel(x, y, Rgb([0, 0, 0]));
+            }
+        }
+        let distance = distance(screen(), noisy);
+        assert!(
+            distance <= DEFAULT_MAX_DISTANCE,
+            "near duplicate changed {} bits",
+            distance
+        );
+    }
+
+    #[test]
+    fn reuses_a_jpeg_round_trip() {
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageRgb8(screen())
+            .write_to(
+                &mut std::io::Cursor::new(&mut jpeg),
+                image::ImageFormat::Jpeg,
+            )
+            .unwrap();
+        let decoded = image::load_from_memory(&jpeg).unwrap().to_rgb8();
+        let distance = distance(screen(), decoded);
+        assert!(
+            distance <= DEFAULT_MAX_DISTANCE,
+            "JPEG round trip changed {} bits",
+            distance
+        );
+    }
+
+    #[test]
+    fn keeps_the_history_across_restarts() {
+        let path = std::env::temp_dir().join(format!(
+            "recount-dedup-history-{}.json.enc",
+            std::process::id()
+        ));
+        let hash = difference_hash(&DynamicImage::ImageRgb8(screen()));
+        let history = HashMap::from([(
+            7,
+            PreviousCapture {
+                hash: hash.clone(),
+                path: "/screenshots/1_7_recount.jpg.enc".to_string(),
+            },
+        )]);
+        // Reversing the bytes stands in for encryption
+        write_history(path.clone(), &history, |json| {
+            Ok(json.iter().rev().copied().collect())
+        })
+        .unwrap();
+
+        let restored = read_history(&path, |data| {
+            Ok(Zeroizing::new(data.iter().rev().copied().collect()))
+        });
+        assert_eq!(restored[&7].hash, hash);
+        assert_eq!(restored[&7].path, "/screenshots/1_7_recount.jpg.enc");
+        // A history sealed with another key is dropped, not an error
+        assert!(read_history(&path, |_| Err(io::Error::other("wrong key"))).is_empty());
+        assert!(read_history(&path.with_extension("missing"), |_| unreachable!()).is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn detects_text_edits() {
+        // A word typed on an empty line
+        let mut typed = screen();
+        draw_word(&mut typed, 80, 340, 48);
+        let typed = distance(screen(), typed);
+        assert!(
+            typed > DEFAULT_MAX_DISTANCE,
+            "typed word changed {} bits",
+            typed
+        );
+
+        // A word replaced by a shorter one
+        let mut edited = screen();
+        for x in 216..400 {
+            for y in 148..162 {
+                edited.put_pixel(x, y, Rgb([250, 250, 250]));
+            }
+        }
+        draw_word(&mut edited, 216, 148, 64);
+        let edited = distance(screen(), edited);
+        assert!(
+            edited > DEFAULT_MAX_DISTANCE,
+            "edited word changed {} bits",
+            edited
+        );
+    }
+}
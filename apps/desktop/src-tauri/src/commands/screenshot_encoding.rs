@@ -0,0 +1,107 @@
+use image::{
+    codecs::{avif::AvifEncoder, jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage,
+};
+use zeroize::Zeroizing;
+
+use crate::commands::{
+    error::{CommandError, CommandResult},
+    settings::CaptureSettings,
+};
+
+/// Longest side of the timeline thumbnails.
+const THUMBNAIL_MAX_DIMENSION: u32 = 320;
+const THUMBNAIL_QUALITY: u8 = 70;
+/// ravif speed from 1 (slowest) to 10; captures happen every few seconds.
+const AVIF_SPEED: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenshotFormat {
+    Jpeg,
+    /// Lossless, the encoder has no lossy mode so quality is ignored.
+    Webp,
+    Avif,
+    /// Lossless, quality is ignored.
+    Png,
+}
+
+impl ScreenshotFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+            Self::Avif => "avif",
+            Self::Png => "png",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "webp" => Some(Self::Webp),
+            "avif" => Some(Self::Avif),
+            "png" => Some(Self::Png),
+            _ => None,
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+            Self::Avif => "image/avif",
+            Self::Png => "image/png",
+        }
+    }
+}
+
+/// Apply the resolution and colour settings to a captured image.
+pub fn prepare(image: &DynamicImage, settings: &CaptureSettings) -> DynamicImage {
+    let image = match settings.max_dimension {
+        Some(max) if image.width().max(image.height()) > max => {
+            image.resize(max, max, FilterType::Triangle)
+        }
+        _ => image.clone(),
+    };
+    if settings.grayscale {
+        DynamicImage::ImageLuma8(image.to_luma8())
+    } else {
+        DynamicImage::ImageRgb8(image.to_rgb8())
+    }
+}
+
+/// Encode an image in memory. `quality` (1-100) only applies to lossy formats.
+pub fn encode(
+    image: &DynamicImage,
+    format: ScreenshotFormat,
+    quality: u8,
+) -> CommandResult<Zeroizing<Vec<u8>>> {
+    let quality = quality.clamp(1, 100);
+    let mut buffer = Zeroizing::new(Vec::new());
+    let result = match format {
+        ScreenshotFormat::Jpeg => {
+            image.write_with_encoder(JpegEncoder::new_with_quality(&mut *buffer, quality))
+        }
+        ScreenshotFormat::Webp => image.write_with_encoder(WebPEncoder::new_lossless(&mut *buffer)),
+        ScreenshotFormat::Avif => DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(
+            AvifEncoder::new_with_speed_quality(&mut *buffer, AVIF_SPEED, quality),
+        ),
+        ScreenshotFormat::Png => image.write_with_encoder(PngEncoder::new(&mut *buffer)),
+    };
+    result.map_err(|e| {
+        CommandError::Internal(format!("Failed to encode {}: {}", format.extension(), e))
+    })?;
+    Ok(buffer)
+}
+
+/// A small JPEG for the timeline, whatever format the screenshot itself is stored in.
+pub fn thumbnail(image: &DynamicImage) -> CommandResult<Zeroizing<Vec<u8>>> {
+    let thumbnail = image.resize(
+        THUMBNAIL_MAX_DIMENSION,
+        THUMBNAIL_MAX_DIMENSION,
+        FilterType::Triangle,
+    );
+    encode(&thumbnail, ScreenshotFormat::Jpeg, THUMBNAIL_QUALITY)
+}
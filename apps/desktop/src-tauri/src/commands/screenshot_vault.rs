@@ -0,0 +1,732 @@
+use base64::Engine;
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use image::DynamicImage;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock, RwLockReadGuard},
+};
+use tauri::Manager;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+    activity_log,
+    commands::{
+        error::{CommandError, CommandResult},
+        screenshot_encoding::{encode, prepare, thumbnail, ScreenshotFormat},
+        settings::{current_settings, CaptureSettings},
+        vault::VaultState,
+    },
+};
+
+/// Serialises updates of the references and downsampled files.
+static REFERENCES_LOCK: Mutex<()> = Mutex::new(());
+/// Shared while a loaded keyring encrypts or decrypts files, exclusive while the key is
+/// rotated, so nothing is written under a key the rotation is about to drop.
+static KEYRING_LOCK: RwLock<()> = RwLock::new(());
+
+/// Header of every encrypted screenshot: magic, key id (u32 LE), then the 24 byte nonce.
+/// The magic and key id are authenticated as associated data.
+const MAGIC: &[u8; 4] = b"RCV1";
+const HEADER_LENGTH: usize = 4 + 4;
+const NONCE_LENGTH: usize = 24;
+const KEYRING_SECRET: &str = "screenshot_keyring";
+const THUMBNAILS_DIR: &str = "thumbnails";
+const REFERENCES_FILE: &str = "references.json";
+const DOWNSAMPLED_FILE: &str = "downsampled.json";
+pub const ENCRYPTED_EXTENSION: &str = "enc";
+
+/// Screenshot encryption keys, stored as a single Stronghold secret.
+/// Old keys are kept until every file has been re-encrypted with the current one.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Keyring {
+    current: u32,
+    keys: BTreeMap<u32, Vec<u8>>,
+}
+
+impl Drop for Keyring {
+    fn drop(&mut self) {
+        for key in self.keys.values_mut() {
+            key.zeroize();
+        }
+    }
+}
+
+fn lock_keyring_shared() -> CommandResult<RwLockReadGuard<'static, ()>> {
+    KEYRING_LOCK
+        .read()
+        .map_err(|_| CommandError::Internal("Keyring lock poisoned".to_string()))
+}
+
+/// A keyring loaded under a shared `KEYRING_LOCK`. Key rotation waits until it is dropped.
+struct SharedKeyring {
+    keyring: Keyring,
+    _lock: RwLockReadGuard<'static, ()>,
+}
+
+impl Deref for SharedKeyring {
+    type Target = Keyring;
+
+    fn deref(&self) -> &Keyring {
+        &self.keyring
+    }
+}
+
+impl Keyring {
+    fn generate() -> Self {
+        Keyring {
+            current: 1,
+            keys: BTreeMap::from([(1, XChaCha20Poly1305::generate_key(&mut OsRng).to_vec())]),
+        }
+    }
+
+    fn load(vault: &VaultState) -> CommandResult<Option<Self>> {
+        vault
+            .get_secret(KEYRING_SECRET)?
+            .map(|bytes| {
+                serde_json::from_slice(&bytes)
+                    .map_err(|e| CommandError::Crypto(format!("Corrupt screenshot keyring: {}", e)))
+            })
+            .transpose()
+    }
+
+    fn load_or_create(vault: &VaultState) -> CommandResult<Self> {
+        if let Some(keyring) = Self::load(vault)? {
+            return Ok(keyring);
+        }
+
+        let keyring = Self::generate();
+        keyring.save(vault)?;
+        Ok(keyring)
+    }
+
+    /// Load the keyring to encrypt or decrypt files with.
+    fn load_shared(app: &tauri::AppHandle) -> CommandResult<SharedKeyring> {
+        let lock = lock_keyring_shared()?;
+        Ok(SharedKeyring {
+            keyring: Self::load_or_create(&app.state::<VaultState>())?,
+            _lock: lock,
+        })
+    }
+
+    fn save(&self, vault: &VaultState) -> CommandResult<()> {
+        let bytes = serde_json::to_vec(self).map_err(|e| CommandError::Internal(e.to_string()))?;
+        vault.insert_secret(KEYRING_SECRET, bytes)
+    }
+
+    fn cipher(&self, key_id: u32) -> CommandResult<XChaCha20Poly1305> {
+        let key = self.keys.get(&key_id).ok_or_else(|| {
+            CommandError::Crypto(format!("Screenshot key {} is not in the keyring", key_id))
+        })?;
+        XChaCha20Poly1305::new_from_slice(key).map_err(|e| CommandError::Crypto(e.to_string()))
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> CommandResult<Vec<u8>> {
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&self.current.to_le_bytes());
+
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher(self.current)?
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: &header,
+                },
+            )
+            .map_err(|e| CommandError::Crypto(e.to_string()))?;
+
+        let mut data = header;
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    }
+
+    fn decrypt(&self, data: &[u8]) -> CommandResult<Zeroizing<Vec<u8>>> {
+        if data.len() < HEADER_LENGTH + NONCE_LENGTH || &data[..4] != MAGIC {
+            return Err(CommandError::Crypto(
+                "Not an encrypted screenshot".to_string(),
+            ));
+        }
+        let (header, rest) = data.split_at(HEADER_LENGTH);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LENGTH);
+        let key_id = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+        self.cipher(key_id)?
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| CommandError::Crypto("Screenshot failed authentication".to_string()))
+    }
+}
+
+fn io_error(error: CommandError) -> std::io::Error {
+    std::io::Error::other(error.to_string())
+}
+
+/// Encrypts other files of the app, like the activity log, with the screenshot keys, so
+/// `rotate_screenshot_key` rotates them too. Key rotation waits while one is alive, so
+/// drop it once the data is written.
+pub struct DataCipher(SharedKeyring);
+
+impl DataCipher {
+    /// Fails while the vault is locked.
+    pub fn load(app: &tauri::AppHandle) -> CommandResult<Self> {
+        Keyring::load_shared(app).map(Self)
+    }
+
+    /// From a vault unlocked outside of the app, like the CLI does. Never creates a key, so
+    /// it fails when nothing was ever encrypted.
+    pub fn from_unlocked_vault(vault: &VaultState) -> CommandResult<Self> {
+        let lock = lock_keyring_shared()?;
+        let keyring = Keyring::load(vault)?
+            .ok_or_else(|| CommandError::Crypto("The vault holds no data key yet".to_string()))?;
+        Ok(Self(SharedKeyring {
+            keyring,
+            _lock: lock,
+        }))
+    }
+
+    pub fn seal(&self, plaintext: &[u8]) -> std::io::Result<Vec<u8>> {
+        self.0.encrypt(plaintext).map_err(io_error)
+    }
+
+    pub fn open(&self, data: &[u8]) -> std::io::Result<Zeroizing<Vec<u8>>> {
+        self.0.decrypt(data).map_err(io_error)
+    }
+}
+
+pub fn screenshots_dir(app: &tauri::AppHandle) -> PathBuf {
+    // Resolve app-local data screenshots directory in a platform-appropriate place
+    app.path()
+        .resolve("screenshots", tauri::path::BaseDirectory::AppLocalData)
+        .unwrap_or_else(|_| std::env::temp_dir().join("screenshots"))
+}
+
+/// Thumbnails share the file name of their screenshot, in a subdirectory.
+pub fn thumbnail_path(screenshot_path: &Path) -> Option<PathBuf> {
+    Some(
+        screenshot_path
+            .parent()?
+            .join(THUMBNAILS_DIR)
+            .join(screenshot_path.file_name()?),
+    )
+}
+
+/// Format of an encrypted screenshot, from the extension before `.enc`.
+fn screenshot_format(path: &Path) -> ScreenshotFormat {
+    path.file_stem()
+        .and_then(|stem| Path::new(stem).extension())
+        .and_then(|ext| ScreenshotFormat::from_extension(&ext.to_string_lossy()))
+        .unwrap_or(ScreenshotFormat::Jpeg)
+}
+
+fn encrypted_files(dir: &Path) -> CommandResult<Vec<PathBuf>> {
+    files_with_extension(dir, ENCRYPTED_EXTENSION)
+}
+
+fn files_with_extension(dir: &Path, extension: &str) -> CommandResult<Vec<PathBuf>> {
+    match fs::read_dir(dir) {
+        Ok(entries) => Ok(entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == extension))
+            .collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Write through a temporary file so a file is either fully written or not there.
+fn write_atomic(path: &Path, data: &[u8]) -> CommandResult<()> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, data)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Encode and encrypt captured display images, keyed by display id, with a thumbnail
+/// each. Format, resolution and colour follow the capture settings, for every backend.
+/// The plaintext never touches the disk.
+/// Returns absolute file paths of the encrypted screenshots.
+pub fn save_screenshots(
+    app: &tauri::AppHandle,
+    settings: &CaptureSettings,
+    timestamp: u32,
+    images: &[(u32, DynamicImage)],
+) -> CommandResult<Vec<String>> {
+    if images.is_empty() {
+        return Ok(Vec::new());
+    }
+    let keyring = Keyring::load_shared(app)?;
+
+    let base_dir = screenshots_dir(app);
+    fs::create_dir_all(base_dir.join(THUMBNAILS_DIR))?;
+
+    images
+        .iter()
+        .map(|(display_id, image)| {
+            let image = prepare(image, settings);
+            let screenshot_path = base_dir.join(format!(
+                "{}_{}_recount.{}.{}",
+                timestamp,
+                display_id,
+                settings.format.extension(),
+                ENCRYPTED_EXTENSION
+            ));
+            let encoded = encode(&image, settings.format, settings.quality)?;
+            write_atomic(&screenshot_path, &keyring.encrypt(&encoded)?)?;
+            if let Some(thumbnail_path) = thumbnail_path(&screenshot_path) {
+                write_atomic(&thumbnail_path, &keyring.encrypt(&thumbnail(&image)?)?)?;
+            }
+            Ok(screenshot_path.to_string_lossy().to_string())
+        })
+        .collect()
+}
+
+/// An encrypted screenshot on disk.
+pub struct StoredScreenshot {
+    pub path: PathBuf,
+    /// Capture time, taken from the `{timestamp}_{display}_recount` file name.
+    pub timestamp: u32,
+    /// The last snapshot that references the file. Later than `timestamp` when
+    /// deduplication reused the file for later snapshots.
+    pub last_referenced: u32,
+    /// Size of the screenshot and its thumbnail.
+    pub bytes: u64,
+    pub thumbnail_bytes: u64,
+    /// Already downsampled by retention, or found small enough.
+    pub downsampled: bool,
+}
+
+fn references_path(app: &tauri::AppHandle) -> PathBuf {
+    screenshots_dir(app).join(REFERENCES_FILE)
+}
+
+fn downsampled_path(app: &tauri::AppHandle) -> PathBuf {
+    screenshots_dir(app).join(DOWNSAMPLED_FILE)
+}
+
+/// A JSON file about the screenshots, like when each reused screenshot was last
+/// referenced, keyed by file name.
+fn load_index<T: serde::de::DeserializeOwned + Default>(path: &Path) -> CommandResult<T> {
+    match fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+            CommandError::Internal(format!("Corrupt {}: {}", path.to_string_lossy(), e))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn update_index<T: serde::de::DeserializeOwned + serde::Serialize + Default>(
+    path: &Path,
+    update: impl FnOnce(&mut T),
+) -> CommandResult<()> {
+    let _guard = REFERENCES_LOCK
+        .lock()
+        .map_err(|_| CommandError::Internal("References lock poisoned".to_string()))?;
+    let mut index = load_index(path)?;
+    update(&mut index);
+    let bytes = serde_json::to_vec(&index).map_err(|e| CommandError::Internal(e.to_string()))?;
+    write_atomic(path, &bytes)
+}
+
+fn update_references(
+    app: &tauri::AppHandle,
+    update: impl FnOnce(&mut HashMap<String, u32>),
+) -> CommandResult<()> {
+    update_index(&references_path(app), update)
+}
+
+fn file_name(path: &str) -> Option<String> {
+    Some(Path::new(path).file_name()?.to_string_lossy().to_string())
+}
+
+/// Record that the snapshot taken at `timestamp` reuses the screenshots at `paths`.
+pub fn record_screenshot_references(
+    app: &tauri::AppHandle,
+    paths: &[String],
+    timestamp: u32,
+) -> CommandResult<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    update_references(app, |references| {
+        for name in paths.iter().filter_map(|path| file_name(path)) {
+            let last = references.entry(name).or_insert(timestamp);
+            *last = (*last).max(timestamp);
+        }
+    })
+}
+
+/// Record that the screenshots at `paths` are downsampled, so retention skips them.
+pub fn record_downsampled_screenshots(
+    app: &tauri::AppHandle,
+    paths: &[PathBuf],
+) -> CommandResult<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    update_index(
+        &downsampled_path(app),
+        |downsampled: &mut HashSet<String>| {
+            downsampled.extend(
+                paths
+                    .iter()
+                    .filter_map(|path| file_name(&path.to_string_lossy())),
+            );
+        },
+    )
+}
+
+/// Forget the references and downsampling of deleted screenshots.
+pub fn forget_screenshot_references(
+    app: &tauri::AppHandle,
+    paths: &HashSet<String>,
+) -> CommandResult<()> {
+    let names: Vec<String> = paths.iter().filter_map(|path| file_name(path)).collect();
+    update_references(app, |references| {
+        for name in &names {
+            references.remove(name);
+        }
+    })?;
+    update_index(
+        &downsampled_path(app),
+        |downsampled: &mut HashSet<String>| {
+            for name in &names {
+                downsampled.remove(name);
+            }
+        },
+    )
+}
+
+/// Every encrypted screenshot, oldest first. Files with an unexpected name are skipped.
+pub fn stored_screenshots(app: &tauri::AppHandle) -> CommandResult<Vec<StoredScreenshot>> {
+    let references: HashMap<String, u32> = load_index(&references_path(app))?;
+    let downsampled: HashSet<String> = load_index(&downsampled_path(app))?;
+    let mut screenshots: Vec<_> = encrypted_files(&screenshots_dir(app))?
+        .into_iter()
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?;
+            let timestamp = name.split('_').next()?.parse().ok()?;
+            let last_referenced = references
+                .get(name)
+                .map_or(timestamp, |&last| last.max(timestamp));
+            let thumbnail_bytes = thumbnail_path(&path)
+                .and_then(|thumbnail| fs::metadata(thumbnail).ok())
+                .map_or(0, |metadata| metadata.len());
+            let bytes = fs::metadata(&path).ok()?.len() + thumbnail_bytes;
+            Some(StoredScreenshot {
+                downsampled: downsampled.contains(name),
+                path,
+                timestamp,
+                last_referenced,
+                bytes,
+                thumbnail_bytes,
+            })
+        })
+        .collect();
+    screenshots.sort_by_key(|s| s.timestamp);
+    Ok(screenshots)
+}
+
+/// Re-encode a stored screenshot at most `max_width` pixels wide. Returns the new file
+/// size, or `None` when it was already small enough or its format cannot be decoded.
+pub fn downsample_screenshot(
+    app: &tauri::AppHandle,
+    path: &Path,
+    max_width: u32,
+) -> CommandResult<Option<u64>> {
+    let quality = current_settings(app)?.capture.quality;
+    let keyring = Keyring::load_shared(app)?;
+    let image = match image::load_from_memory(&keyring.decrypt(&fs::read(path)?)?) {
+        Ok(image) => image,
+        // e.g. AVIF without the `avif-decoding` feature
+        Err(image::ImageError::Unsupported(_)) => return Ok(None),
+        Err(e) => return Err(CommandError::Io(e.to_string())),
+    };
+    if image.width() <= max_width {
+        return Ok(None);
+    }
+
+    let image = image.resize(max_width, u32::MAX, image::imageops::FilterType::Triangle);
+    let data = keyring.encrypt(&encode(&image, screenshot_format(path), quality)?)?;
+    write_atomic(path, &data)?;
+    Ok(Some(data.len() as u64))
+}
+
+/// Decrypted encoded bytes of a stored screenshot, kept in memory only.
+pub fn read_screenshot_bytes(
+    app: &tauri::AppHandle,
+    path: &Path,
+) -> CommandResult<Zeroizing<Vec<u8>>> {
+    let keyring = Keyring::load_shared(app)?;
+    keyring.decrypt(&fs::read(path)?)
+}
+
+/// Load an image for analysis, decrypting it when it is a vault screenshot.
+pub fn load_image(app: &tauri::AppHandle, path: &str) -> CommandResult<DynamicImage> {
+    let path = Path::new(path);
+    let image = if path
+        .extension()
+        .is_some_and(|ext| ext == ENCRYPTED_EXTENSION)
+    {
+        image::load_from_memory(&read_screenshot_bytes(app, path)?)
+    } else {
+        image::open(path)
+    };
+    image.map_err(|e| CommandError::Io(e.to_string()))
+}
+
+fn ensure_in_screenshots_dir(app: &tauri::AppHandle, path: &Path) -> CommandResult<()> {
+    let dir = screenshots_dir(app).canonicalize()?;
+    if path.canonicalize()?.starts_with(dir) {
+        Ok(())
+    } else {
+        Err(CommandError::PermissionDenied(format!(
+            "{} is not a screenshot",
+            path.to_string_lossy()
+        )))
+    }
+}
+
+fn data_url(mime_type: &str, bytes: &[u8]) -> String {
+    format!(
+        "data:{};base64,{}",
+        mime_type,
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    )
+}
+
+/// Decrypt a screenshot into a `data:` URL for display.
+#[tauri::command]
+#[specta::specta]
+pub async fn read_screenshot(app: tauri::AppHandle, path: String) -> CommandResult<String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = PathBuf::from(path);
+        ensure_in_screenshots_dir(&app, &path)?;
+        let bytes = read_screenshot_bytes(&app, &path)?;
+        Ok(data_url(screenshot_format(&path).mime_type(), &bytes))
+    })
+    .await?
+}
+
+/// Decrypt the thumbnail of a screenshot into a JPEG `data:` URL for the timeline.
+#[tauri::command]
+#[specta::specta]
+pub async fn read_screenshot_thumbnail(
+    app: tauri::AppHandle,
+    path: String,
+) -> CommandResult<String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = PathBuf::from(path);
+        ensure_in_screenshots_dir(&app, &path)?;
+        let thumbnail_path = thumbnail_path(&path)
+            .ok_or_else(|| CommandError::Io(format!("{} has no thumbnail", path.display())))?;
+        let bytes = read_screenshot_bytes(&app, &thumbnail_path)?;
+        Ok(data_url(ScreenshotFormat::Jpeg.mime_type(), &bytes))
+    })
+    .await?
+}
+
+/// Add a new current key to `keyring`, re-encrypt the screenshots and thumbnails in
+/// `screenshots_dir` and the activity log in `data_dir` with it, then drop the old keys.
+/// `save` persists the keyring before and after, so a crash halfway leaves every file
+/// decryptable with one of the stored keys. Returns the number of re-encrypted files.
+fn rotate_keyring(
+    keyring: &mut Keyring,
+    screenshots_dir: &Path,
+    data_dir: &Path,
+    save: impl Fn(&Keyring) -> CommandResult<()>,
+) -> CommandResult<u32> {
+    let new_id = keyring.keys.keys().max().copied().unwrap_or(0) + 1;
+    keyring
+        .keys
+        .insert(new_id, XChaCha20Poly1305::generate_key(&mut OsRng).to_vec());
+    keyring.current = new_id;
+    save(keyring)?;
+
+    let mut rotated = 0;
+    for path in encrypted_files(screenshots_dir)?
+        .into_iter()
+        .chain(encrypted_files(&screenshots_dir.join(THUMBNAILS_DIR))?)
+    {
+        let plaintext = keyring.decrypt(&fs::read(&path)?)?;
+        write_atomic(&path, &keyring.encrypt(&plaintext)?)?;
+        rotated += 1;
+    }
+    rotated += activity_log::reseal(
+        data_dir,
+        |data| keyring.decrypt(data).map_err(io_error),
+        |plaintext| keyring.encrypt(plaintext).map_err(io_error),
+    )?;
+
+    keyring.keys.retain(|&id, _| id == new_id);
+    save(keyring)?;
+    Ok(rotated)
+}
+
+/// Generate a new screenshot key and re-encrypt every stored screenshot and the activity
+/// log with it. Captures and log appends wait until it is done. Returns the number of
+/// re-encrypted files.
+#[tauri::command]
+#[specta::specta]
+pub async fn rotate_screenshot_key(app: tauri::AppHandle) -> CommandResult<u32> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let _lock = KEYRING_LOCK
+            .write()
+            .map_err(|_| CommandError::Internal("Keyring lock poisoned".to_string()))?;
+        let vault = app.state::<VaultState>();
+        let mut keyring = Keyring::load_or_create(&vault)?;
+        rotate_keyring(
+            &mut keyring,
+            &screenshots_dir(&app),
+            &app.path().app_local_data_dir()?,
+            |keyring| keyring.save(&vault),
+        )
+    })
+    .await?
+}
+
+/// Encrypt screenshots written before the vault existed, with a thumbnail each, and
+/// delete the plaintext files. Returns the number of migrated files.
+#[tauri::command]
+#[specta::specta]
+pub async fn migrate_plaintext_screenshots(app: tauri::AppHandle) -> CommandResult<u32> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let keyring = Keyring::load_shared(&app)?;
+        let dir = screenshots_dir(&app);
+        fs::create_dir_all(dir.join(THUMBNAILS_DIR))?;
+
+        let mut migrated = 0;
+        for path in files_with_extension(&dir, "jpg")? {
+            let plaintext = Zeroizing::new(fs::read(&path)?);
+            let mut encrypted_path = path.clone().into_os_string();
+            encrypted_path.push(format!(".{}", ENCRYPTED_EXTENSION));
+            let encrypted_path = PathBuf::from(encrypted_path);
+
+            write_atomic(&encrypted_path, &keyring.encrypt(&plaintext)?)?;
+            // A file that does not decode is still migrated, just without a thumbnail
+            let image = image::load_from_memory(&plaintext);
+            if let (Ok(image), Some(thumbnail_path)) = (image, thumbnail_path(&encrypted_path)) {
+                write_atomic(&thumbnail_path, &keyring.encrypt(&thumbnail(&image)?)?)?;
+            }
+            fs::remove_file(&path)?;
+            migrated += 1;
+        }
+        Ok(migrated)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("recount-vault-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn round_trips_encrypted_data() {
+        let keyring = Keyring::generate();
+        let data = keyring.encrypt(b"screenshot").unwrap();
+        assert!(!data.windows(10).any(|window| window == b"screenshot"));
+        assert_eq!(keyring.decrypt(&data).unwrap().as_slice(), b"screenshot");
+        // A fresh nonce every time
+        assert_ne!(keyring.encrypt(b"screenshot").unwrap(), data);
+    }
+
+    #[test]
+    fn rejects_tampered_data() {
+        let keyring = Keyring::generate();
+        let data = keyring.encrypt(b"screenshot").unwrap();
+
+        let tampered_at = |index: usize| {
+            let mut tampered = data.clone();
+            tampered[index] ^= 1;
+            tampered
+        };
+        // Magic, key id, nonce and ciphertext
+        for index in [0, 4, HEADER_LENGTH, data.len() - 1] {
+            assert!(
+                keyring.decrypt(&tampered_at(index)).is_err(),
+                "accepted a change at byte {}",
+                index
+            );
+        }
+        assert!(keyring.decrypt(&data[..HEADER_LENGTH]).is_err());
+        assert!(Keyring::generate().decrypt(&data).is_err());
+    }
+
+    #[test]
+    fn rotates_every_file_to_a_new_key() {
+        let dir = temp_dir("rotation");
+        let screenshots = dir.join("screenshots");
+        fs::create_dir_all(screenshots.join(THUMBNAILS_DIR)).unwrap();
+        let mut keyring = Keyring::generate();
+        let old = Keyring {
+            current: keyring.current,
+            keys: keyring.keys.clone(),
+        };
+
+        let files = [
+            screenshots.join("1_1_recount.jpg.enc"),
+            screenshots.join(THUMBNAILS_DIR).join("1_1_recount.jpg.enc"),
+        ];
+        for file in &files {
+            fs::write(file, keyring.encrypt(b"pixels").unwrap()).unwrap();
+        }
+        let record = activity_log::ActivityRecord {
+            timestamp: chrono::Local::now().timestamp() as u32,
+            kind: crate::commands::ActivityKind::Active,
+            application_name: Some("Editor".to_string()),
+            window_title: None,
+            url: None,
+        };
+        activity_log::append(&dir, &record, |json| {
+            keyring.encrypt(json).map_err(io_error)
+        })
+        .unwrap();
+
+        let saves = Cell::new(0);
+        let rotated = rotate_keyring(&mut keyring, &screenshots, &dir, |_| {
+            saves.set(saves.get() + 1);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(rotated, 3);
+        assert_eq!(saves.get(), 2);
+        assert_eq!(keyring.current, 2);
+        assert_eq!(keyring.keys.keys().collect::<Vec<_>>(), [&2]);
+
+        for file in &files {
+            let data = fs::read(file).unwrap();
+            assert_eq!(keyring.decrypt(&data).unwrap().as_slice(), b"pixels");
+            assert!(old.decrypt(&data).is_err());
+        }
+        let read = |keyring: &Keyring| {
+            activity_log::read(&dir, record.timestamp, record.timestamp + 1, |data| {
+                keyring.decrypt(data).map_err(io_error)
+            })
+            .unwrap()
+        };
+        assert_eq!(read(&keyring), vec![record.clone()]);
+        assert!(read(&old).is_empty());
+    }
+}
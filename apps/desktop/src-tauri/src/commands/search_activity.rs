@@ -0,0 +1,171 @@
+pub mod embedding_index;
+
+use crate::commands::{
+    ai::runtime::AiRuntime,
+    error::{CommandError, CommandResult},
+    screenshot_vault::load_image,
+};
+use embedding_index::{EmbeddingEntry, EmbeddingIndex, Modality};
+use std::{path::PathBuf, sync::Mutex};
+use tauri::Manager;
+
+const DEFAULT_SEARCH_LIMIT: u32 = 20;
+/// Text queries are matched against a caption of each screenshot, since the screenshot's
+/// own embedding is not comparable with a text embedding.
+const CAPTION_PROMPT: &str = "Describe what the user is working on in this screenshot.";
+
+/// In-memory copy of the on-disk embedding index, loaded on first use.
+#[derive(Default)]
+pub struct EmbeddingIndexState(Mutex<Option<EmbeddingIndex>>);
+
+#[derive(serde::Deserialize, specta::Type)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ActivitySearchQuery {
+    /// Matched against screenshot captions.
+    Text { text: String },
+    /// Matched against the screenshots themselves.
+    Image { image_path: String },
+}
+
+#[derive(serde::Serialize, specta::Type)]
+pub struct ActivitySearchMatch {
+    timestamp: u32,
+    screenshot_path: String,
+    score: f32,
+}
+
+/// Embed the screenshots of a captured snapshot and their captions, and add them to the
+/// search index.
+#[tauri::command]
+#[specta::specta]
+pub async fn index_screenshots(
+    app: tauri::AppHandle,
+    timestamp: u32,
+    screenshot_paths: Vec<String>,
+) -> CommandResult<u32> {
+    tauri::async_runtime::spawn_blocking(move || -> anyhow::Result<u32> {
+        let images = screenshot_paths
+            .iter()
+            .map(|path| load_image(&app, path))
+            .collect::<CommandResult<Vec<_>>>()?;
+        let embeddings = app.state::<AiRuntime>().with_models(|models| {
+            screenshot_paths
+                .into_iter()
+                .zip(&images)
+                .map(|(path, image)| {
+                    let caption = models.generate_text(Some(image), Some(CAPTION_PROMPT))?;
+                    Ok((
+                        path,
+                        models.embed_image(image)?,
+                        models.embed_text(&caption)?,
+                    ))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        })?;
+
+        let indexed = embeddings.len() as u32;
+        with_index(&app, |index| {
+            for (screenshot_path, image, caption) in embeddings {
+                index.insert(EmbeddingEntry {
+                    timestamp,
+                    screenshot_path: screenshot_path.clone(),
+                    modality: Modality::Image,
+                    embedding: image,
+                });
+                index.insert(EmbeddingEntry {
+                    timestamp,
+                    screenshot_path,
+                    modality: Modality::Caption,
+                    embedding: caption,
+                });
+            }
+            true
+        })?;
+
+        Ok(indexed)
+    })
+    .await?
+    .map_err(CommandError::from)
+}
+
+/// Rank indexed screenshots by similarity to a text query or a reference image.
+#[tauri::command]
+#[specta::specta]
+pub async fn search_activity(
+    app: tauri::AppHandle,
+    query: ActivitySearchQuery,
+    since: Option<u32>,
+    until: Option<u32>,
+    limit: Option<u32>,
+) -> CommandResult<Vec<ActivitySearchMatch>> {
+    tauri::async_runtime::spawn_blocking(move || -> anyhow::Result<Vec<ActivitySearchMatch>> {
+        let (query_embedding, modality) = match &query {
+            ActivitySearchQuery::Text { text } => (
+                app.state::<AiRuntime>()
+                    .with_models(|models| models.embed_text(text))?,
+                Modality::Caption,
+            ),
+            ActivitySearchQuery::Image { image_path } => {
+                let image = load_image(&app, image_path)?;
+                (
+                    app.state::<AiRuntime>()
+                        .with_models(|models| models.embed_image(&image))?,
+                    Modality::Image,
+                )
+            }
+        };
+
+        let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT) as usize;
+        let mut matches = Vec::new();
+        with_index(&app, |index| {
+            matches = index
+                .search(&query_embedding, modality, limit, |entry| {
+                    since.is_none_or(|s| entry.timestamp >= s)
+                        && until.is_none_or(|u| entry.timestamp <= u)
+                })
+                .into_iter()
+                .map(|(entry, score)| ActivitySearchMatch {
+                    timestamp: entry.timestamp,
+                    screenshot_path: entry.screenshot_path.clone(),
+                    score,
+                })
+                .collect();
+            false
+        })?;
+
+        Ok(matches)
+    })
+    .await?
+    .map_err(CommandError::from)
+}
+
+fn index_path(app: &tauri::AppHandle) -> anyhow::Result<PathBuf> {
+    Ok(app.path().resolve(
+        "embeddings/index.json",
+        tauri::path::BaseDirectory::AppLocalData,
+    )?)
+}
+
+/// Run `f` against the loaded index. `f` returns whether it modified the index,
+/// in which case the index is written back to disk.
+pub fn with_index(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&mut EmbeddingIndex) -> bool,
+) -> anyhow::Result<()> {
+    let path = index_path(app)?;
+    let state = app.state::<EmbeddingIndexState>();
+    let mut index = state
+        .0
+        .lock()
+        .map_err(|_| anyhow::anyhow!("Embedding index lock poisoned"))?;
+
+    if index.is_none() {
+        *index = Some(EmbeddingIndex::load(&path)?);
+    }
+    let index = index.as_mut().expect("index is loaded above");
+
+    if f(index) {
+        index.save(&path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use anyhow::Result;
+use std::{collections::HashSet, fs, path::Path};
+
+/// What an embedding was computed from. Image and text embeddings come from different
+/// encoders, so they are only ever compared with embeddings of the same modality.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Modality {
+    /// The screenshot itself. Indexes written before captions were added only have these.
+    #[default]
+    Image,
+    /// The screenshot's generated caption.
+    Caption,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EmbeddingEntry {
+    pub timestamp: u32,
+    pub screenshot_path: String,
+    #[serde(default)]
+    pub modality: Modality,
+    pub embedding: Vec<f32>,
+}
+
+/// Flat nearest-neighbour index over screenshot embeddings.
+/// A few thousand entries a week is small enough for an exhaustive scan.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct EmbeddingIndex {
+    entries: Vec<EmbeddingEntry>,
+}
+
+impl EmbeddingIndex {
+    /// Load the index from disk; a missing file is an empty index.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read(path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write to a temporary file first so a crash never leaves a truncated index behind.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec(self)?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Insert an entry, replacing any previous embedding of the same modality for the same
+    /// screenshot.
+    pub fn insert(&mut self, entry: EmbeddingEntry) {
+        self.entries
+            .retain(|e| e.screenshot_path != entry.screenshot_path || e.modality != entry.modality);
+        self.entries.push(entry);
+    }
+
+    /// The embedding of one modality of a screenshot, when it was indexed.
+    pub fn embedding(&self, screenshot_path: &str, modality: Modality) -> Option<&[f32]> {
+        self.entries
+            .iter()
+            .find(|e| e.screenshot_path == screenshot_path && e.modality == modality)
+            .map(|e| e.embedding.as_slice())
+    }
+
+    /// Remove the embeddings of deleted screenshots. Returns whether anything was removed.
+    pub fn remove(&mut self, screenshot_paths: &HashSet<String>) -> bool {
+        let before = self.entries.len();
+        self.entries
+            .retain(|e| !screenshot_paths.contains(&e.screenshot_path));
+        self.entries.len() != before
+    }
+
+    /// Entries of `modality` ranked by cosine similarity to `query`, best first.
+    pub fn search(
+        &self,
+        query: &[f32],
+        modality: Modality,
+        limit: usize,
+        filter: impl Fn(&EmbeddingEntry) -> bool,
+    ) -> Vec<(&EmbeddingEntry, f32)> {
+        let mut scored: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|e| e.modality == modality && filter(e))
+            .filter(|e| e.embedding.len() == query.len())
+            .map(|e| (e, cosine_similarity(&e.embedding, query)))
+            .collect();
+        scored.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        scored.truncate(limit);
+        scored
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: u32, path: &str, modality: Modality, embedding: &[f32]) -> EmbeddingEntry {
+        EmbeddingEntry {
+            timestamp,
+            screenshot_path: path.to_string(),
+            modality,
+            embedding: embedding.to_vec(),
+        }
+    }
+
+    fn paths(matches: &[(&EmbeddingEntry, f32)]) -> Vec<String> {
+        matches
+            .iter()
+            .map(|(entry, _)| entry.screenshot_path.clone())
+            .collect()
+    }
+
+    fn index() -> EmbeddingIndex {
+        let mut index = EmbeddingIndex::default();
+        index.insert(entry(1, "a.jpg", Modality::Image, &[1.0, 0.0]));
+        index.insert(entry(2, "b.jpg", Modality::Image, &[0.6, 0.8]));
+        index.insert(entry(3, "c.jpg", Modality::Image, &[0.0, 1.0]));
+        index.insert(entry(1, "a.jpg", Modality::Caption, &[0.0, 1.0]));
+        index
+    }
+
+    #[test]
+    fn ranks_the_top_k_of_one_modality() {
+        let index = index();
+        let matches = index.search(&[1.0, 0.0], Modality::Image, 2, |_| true);
+        assert_eq!(paths(&matches), ["a.jpg", "b.jpg"]);
+        assert!((matches[0].1 - 1.0).abs() < 1e-6);
+        assert!((matches[1].1 - 0.6).abs() < 1e-6);
+
+        let matches = index.search(&[1.0, 0.0], Modality::Caption, 10, |_| true);
+        assert_eq!(paths(&matches), ["a.jpg"]);
+
+        let matches = index.search(&[1.0, 0.0], Modality::Image, 10, |e| e.timestamp >= 2);
+        assert_eq!(paths(&matches), ["b.jpg", "c.jpg"]);
+        // Embeddings of another model are skipped
+        assert!(index
+            .search(&[1.0, 0.0, 0.0], Modality::Image, 10, |_| true)
+            .is_empty());
+    }
+
+    #[test]
+    fn replaces_an_embedding_per_screenshot_and_modality() {
+        let mut index = index();
+        index.insert(entry(1, "a.jpg", Modality::Image, &[0.0, 1.0]));
+        assert_eq!(index.entries.len(), 4);
+        assert_eq!(
+            index.embedding("a.jpg", Modality::Image),
+            Some([0.0, 1.0].as_slice())
+        );
+        assert_eq!(
+            index.embedding("a.jpg", Modality::Caption),
+            Some([0.0, 1.0].as_slice())
+        );
+        assert_eq!(index.embedding("d.jpg", Modality::Image), None);
+
+        assert!(index.remove(&HashSet::from(["a.jpg".to_string()])));
+        assert!(!index.remove(&HashSet::from(["a.jpg".to_string()])));
+        assert_eq!(index.entries.len(), 2);
+    }
+
+    #[test]
+    fn persists_and_reloads() {
+        let dir = std::env::temp_dir().join(format!("recount-index-{}", std::process::id()));
+        let path = dir.join("embeddings").join("index.json");
+        assert!(EmbeddingIndex::load(&path).unwrap().entries.is_empty());
+
+        index().save(&path).unwrap();
+        let reloaded = EmbeddingIndex::load(&path).unwrap();
+        assert_eq!(reloaded.entries.len(), 4);
+        let matches = reloaded.search(&[0.0, 1.0], Modality::Image, 3, |_| true);
+        assert_eq!(paths(&matches), ["c.jpg", "b.jpg", "a.jpg"]);
+        assert_eq!(
+            reloaded.embedding("a.jpg", Modality::Caption),
+            Some([0.0, 1.0].as_slice())
+        );
+
+        // Entries from before modalities were recorded are image embeddings
+        fs::write(
+            &path,
+            r#"{"entries":[{"timestamp":1,"screenshot_path":"a.jpg","embedding":[1.0]}]}"#,
+        )
+        .unwrap();
+        let old = EmbeddingIndex::load(&path).unwrap();
+        assert_eq!(old.entries[0].modality, Modality::Image);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
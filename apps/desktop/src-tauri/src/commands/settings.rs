@@ -0,0 +1,271 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tauri::Manager;
+
+use crate::commands::{
+    error::{CommandError, CommandResult},
+    screenshot_encoding::ScreenshotFormat,
+};
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// User preferences, persisted as JSON in app local data.
+/// Every field has a default so older settings files keep loading.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(default)]
+pub struct Settings {
+    pub capture: CaptureSettings,
+    pub retention: RetentionSettings,
+    pub redaction: RedactionSettings,
+    pub browser: BrowserSettings,
+    pub issues: IssueSettings,
+    pub shortcuts: ShortcutSettings,
+}
+
+/// What a snapshot captures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMode {
+    AllDisplays,
+    /// Only the display showing the focused window.
+    ActiveDisplay,
+    /// Only the focused window, cropped from its display.
+    ActiveWindow,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(default)]
+pub struct CaptureSettings {
+    pub mode: CaptureMode,
+    pub format: ScreenshotFormat,
+    /// Quality of lossy formats, from 1 to 100.
+    pub quality: u8,
+    /// Screenshots are scaled down so their longest side is at most this many pixels.
+    pub max_dimension: Option<u32>,
+    pub grayscale: bool,
+    /// A display whose difference hash is at most this many bits away from the previous
+    /// capture's is considered unchanged, and the previous screenshot is reused.
+    pub dedup_max_distance: u32,
+    /// Snapshots taken after this long without input count as idle, unless in a meeting.
+    pub idle_after_seconds: u32,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        Self {
+            mode: CaptureMode::AllDisplays,
+            format: ScreenshotFormat::Jpeg,
+            quality: 80,
+            max_dimension: None,
+            grayscale: false,
+            dedup_max_distance: 4,
+            idle_after_seconds: 300,
+        }
+    }
+}
+
+/// How long screenshots are kept. `None` disables a limit.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(default)]
+pub struct RetentionSettings {
+    /// Screenshots not linked to billed time are deleted after this many days.
+    pub max_age_days: Option<u32>,
+    /// Screenshots linked to billed time are deleted after this many days.
+    pub billed_max_age_days: Option<u32>,
+    /// Screenshots are downsampled after this many days.
+    pub downsample_after_days: Option<u32>,
+    /// Oldest screenshots are deleted once the total exceeds this size.
+    pub max_total_megabytes: Option<u32>,
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            max_age_days: Some(30),
+            billed_max_age_days: Some(365),
+            downsample_after_days: Some(7),
+            max_total_megabytes: Some(4096),
+        }
+    }
+}
+
+/// What is blurred in screenshots before they are saved.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(default)]
+pub struct RedactionSettings {
+    /// Fixed regions blurred on every capture.
+    pub regions: Vec<RedactionRegion>,
+    /// Blur lines of text that OCR recognises as emails, IBANs or API keys.
+    pub detect_sensitive_text: bool,
+    /// Windows of these applications are blurred, matched on application name.
+    pub excluded_apps: Vec<String>,
+}
+
+/// A region of one display, as fractions of the display size so it survives resolution changes.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, specta::Type)]
+pub struct RedactionRegion {
+    /// Platform id of the display, as in `DisplayCapture::display_id`.
+    pub display: u32,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// How much of the active browser tab is recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlPrivacy {
+    FullUrl,
+    DomainOnly,
+    /// Nothing about the tab is recorded.
+    Hidden,
+}
+
+/// Per-domain privacy of browser tabs. Incognito tabs are never recorded.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(default)]
+pub struct BrowserSettings {
+    pub default_privacy: UrlPrivacy,
+    /// The most specific matching rule wins; a rule also matches subdomains.
+    pub domain_rules: Vec<DomainRule>,
+}
+
+impl Default for BrowserSettings {
+    fn default() -> Self {
+        Self {
+            default_privacy: UrlPrivacy::FullUrl,
+            domain_rules: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, specta::Type)]
+pub struct DomainRule {
+    pub domain: String,
+    pub privacy: UrlPrivacy,
+}
+
+/// Which issue keys are recognised in titles, branches and URLs, and where they link to.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(default)]
+pub struct IssueSettings {
+    /// Jira project keys, e.g. `PROJ`. When empty, any uppercase `ABC-123` key not
+    /// claimed by Linear is taken to be a Jira issue; lowercase keys in branch names
+    /// always need a configured key.
+    pub jira_project_keys: Vec<String>,
+    /// e.g. `https://acme.atlassian.net`.
+    pub jira_base_url: Option<String>,
+    /// Linear team keys, e.g. `ENG`.
+    pub linear_team_keys: Vec<String>,
+    /// The workspace in Linear URLs, `linear.app/<workspace>/issue/ENG-123`.
+    pub linear_workspace: Option<String>,
+}
+
+/// Global keyboard shortcuts, as accelerators like `CommandOrControl+Shift+T`.
+/// `None` leaves an action unbound. Changed through `rebind_shortcut`, which checks for
+/// conflicts.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(default)]
+pub struct ShortcutSettings {
+    pub toggle_timer: Option<String>,
+    pub pause_tracking: Option<String>,
+    pub quick_entry: Option<String>,
+}
+
+impl Default for ShortcutSettings {
+    fn default() -> Self {
+        Self {
+            toggle_timer: Some("CommandOrControl+Alt+Shift+T".to_string()),
+            pause_tracking: Some("CommandOrControl+Alt+Shift+P".to_string()),
+            quick_entry: Some("CommandOrControl+Alt+Shift+Space".to_string()),
+        }
+    }
+}
+
+/// The settings, loaded from disk on first use.
+#[derive(Default)]
+pub struct SettingsState(Mutex<Option<Settings>>);
+
+fn settings_path(app: &tauri::AppHandle) -> CommandResult<PathBuf> {
+    Ok(app.path().app_local_data_dir()?.join(SETTINGS_FILE))
+}
+
+fn load(app: &tauri::AppHandle) -> CommandResult<Settings> {
+    read_settings(&app.path().app_local_data_dir()?)
+}
+
+/// The settings saved in `data_dir`, for processes without an app handle.
+pub fn read_settings(data_dir: &Path) -> CommandResult<Settings> {
+    match fs::read(data_dir.join(SETTINGS_FILE)) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map_err(|e| CommandError::Internal(format!("Corrupt settings file: {}", e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Settings::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save(app: &tauri::AppHandle, settings: &Settings) -> CommandResult<()> {
+    let path = settings_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let bytes =
+        serde_json::to_vec_pretty(settings).map_err(|e| CommandError::Internal(e.to_string()))?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// The current settings, for use by other commands.
+pub fn current_settings(app: &tauri::AppHandle) -> CommandResult<Settings> {
+    let state = app.state::<SettingsState>();
+    let mut settings = state
+        .0
+        .lock()
+        .map_err(|_| CommandError::Internal("Settings lock poisoned".to_string()))?;
+    if settings.is_none() {
+        *settings = Some(load(app)?);
+    }
+    Ok(settings.clone().expect("settings are loaded above"))
+}
+
+/// Persist new shortcuts, once `rebind_shortcut` registered them.
+pub fn save_shortcuts(app: &tauri::AppHandle, shortcuts: ShortcutSettings) -> CommandResult<()> {
+    let mut settings = current_settings(app)?;
+    settings.shortcuts = shortcuts;
+    let state = app.state::<SettingsState>();
+    let mut current = state
+        .0
+        .lock()
+        .map_err(|_| CommandError::Internal("Settings lock poisoned".to_string()))?;
+    save(app, &settings)?;
+    *current = Some(settings);
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_settings(app: tauri::AppHandle) -> CommandResult<Settings> {
+    current_settings(&app)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn update_settings(app: tauri::AppHandle, mut settings: Settings) -> CommandResult<Settings> {
+    let shortcuts = current_settings(&app)?.shortcuts;
+    let state = app.state::<SettingsState>();
+    let mut current = state
+        .0
+        .lock()
+        .map_err(|_| CommandError::Internal("Settings lock poisoned".to_string()))?;
+    // Shortcuts stay as registered, `rebind_shortcut` changes them
+    settings.shortcuts = shortcuts;
+    save(&app, &settings)?;
+    *current = Some(settings.clone());
+    Ok(settings)
+}
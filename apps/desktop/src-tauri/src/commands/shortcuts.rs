@@ -0,0 +1,281 @@
+use tauri::{plugin::TauriPlugin, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+
+use crate::commands::{
+    error::{CommandError, CommandResult},
+    settings::{current_settings, save_shortcuts, ShortcutSettings},
+    timer::{running_timer, start_timer, stop_timer},
+    tracking_pause::{current_pause, pause_tracking, resume_tracking},
+    tray::recent_projects,
+};
+
+/// Label of the always-on-top quick-entry window in `tauri.conf.json`.
+const QUICK_ENTRY_WINDOW: &str = "quick-entry";
+
+/// What a global shortcut does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortcutAction {
+    /// Stop the running timer, or start one for the most recent project.
+    ToggleTimer,
+    /// Pause tracking until resumed, or resume it.
+    PauseTracking,
+    /// Show or hide the quick-entry window.
+    QuickEntry,
+}
+
+const ACTIONS: [ShortcutAction; 3] = [
+    ShortcutAction::ToggleTimer,
+    ShortcutAction::PauseTracking,
+    ShortcutAction::QuickEntry,
+];
+
+impl ShortcutAction {
+    fn label(self) -> &'static str {
+        match self {
+            ShortcutAction::ToggleTimer => "start/stop timer",
+            ShortcutAction::PauseTracking => "pause tracking",
+            ShortcutAction::QuickEntry => "quick entry",
+        }
+    }
+
+    fn accelerator(self, shortcuts: &ShortcutSettings) -> Option<&str> {
+        match self {
+            ShortcutAction::ToggleTimer => shortcuts.toggle_timer.as_deref(),
+            ShortcutAction::PauseTracking => shortcuts.pause_tracking.as_deref(),
+            ShortcutAction::QuickEntry => shortcuts.quick_entry.as_deref(),
+        }
+    }
+
+    fn set_accelerator(self, shortcuts: &mut ShortcutSettings, accelerator: Option<String>) {
+        match self {
+            ShortcutAction::ToggleTimer => shortcuts.toggle_timer = accelerator,
+            ShortcutAction::PauseTracking => shortcuts.pause_tracking = accelerator,
+            ShortcutAction::QuickEntry => shortcuts.quick_entry = accelerator,
+        }
+    }
+
+    fn shortcut(self, shortcuts: &ShortcutSettings) -> Option<Shortcut> {
+        self.accelerator(shortcuts)
+            .and_then(|accelerator| parse(accelerator).ok())
+    }
+}
+
+#[derive(serde::Serialize, specta::Type)]
+pub struct ShortcutBinding {
+    action: ShortcutAction,
+    accelerator: Option<String>,
+    /// False when the accelerator is taken by another application or does not parse.
+    registered: bool,
+}
+
+fn parse(accelerator: &str) -> CommandResult<Shortcut> {
+    accelerator
+        .parse()
+        .map_err(|e| CommandError::InvalidShortcut(format!("{}: {}", accelerator, e)))
+}
+
+/// Show the quick-entry window, or hide it when it is already showing.
+pub fn toggle_quick_entry(app: &tauri::AppHandle) {
+    let Some(window) = app.get_webview_window(QUICK_ENTRY_WINDOW) else {
+        return;
+    };
+    if window.is_visible().unwrap_or(false) {
+        let _ = window.hide();
+    } else {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+fn toggle_timer(app: &tauri::AppHandle) -> CommandResult<()> {
+    if running_timer(app).is_some() {
+        // The stopped entry waits in the timer's outbox until the frontend creates it
+        return stop_timer(app.clone(), None).map(|_| ());
+    }
+    // Without a project to start, ask for one
+    match recent_projects(app).into_iter().next() {
+        Some(project) => start_timer(app.clone(), project.id, None, None, None).map(|_| ()),
+        None => {
+            toggle_quick_entry(app);
+            Ok(())
+        }
+    }
+}
+
+fn handle_shortcut(app: &tauri::AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state() != ShortcutState::Pressed {
+        return;
+    }
+    let Ok(settings) = current_settings(app) else {
+        return;
+    };
+    let Some(action) = ACTIONS
+        .into_iter()
+        .find(|action| action.shortcut(&settings.shortcuts) == Some(*shortcut))
+    else {
+        return;
+    };
+    let _ = match action {
+        ShortcutAction::ToggleTimer => toggle_timer(app),
+        ShortcutAction::PauseTracking if current_pause(app).is_some() => {
+            resume_tracking(app.clone())
+        }
+        ShortcutAction::PauseTracking => pause_tracking(app.clone(), None).map(|_| ()),
+        ShortcutAction::QuickEntry => {
+            toggle_quick_entry(app);
+            Ok(())
+        }
+    };
+}
+
+/// The global shortcut plugin, dispatching presses to their action.
+pub fn shortcut_plugin() -> TauriPlugin<tauri::Wry> {
+    tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(handle_shortcut)
+        .build()
+}
+
+/// Register the shortcuts in the settings. Shortcuts another application holds are left
+/// unregistered; `get_shortcuts` reports them.
+pub fn register_shortcuts(app: &tauri::AppHandle) {
+    let Ok(settings) = current_settings(app) else {
+        return;
+    };
+    for action in ACTIONS {
+        if let Some(shortcut) = action.shortcut(&settings.shortcuts) {
+            let _ = app.global_shortcut().register(shortcut);
+        }
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_shortcuts(app: tauri::AppHandle) -> CommandResult<Vec<ShortcutBinding>> {
+    let settings = current_settings(&app)?;
+    Ok(ACTIONS
+        .into_iter()
+        .map(|action| ShortcutBinding {
+            action,
+            accelerator: action.accelerator(&settings.shortcuts).map(str::to_string),
+            registered: action
+                .shortcut(&settings.shortcuts)
+                .is_some_and(|shortcut| app.global_shortcut().is_registered(shortcut)),
+        })
+        .collect())
+}
+
+/// The shortcut `accelerator` stands for, unless it does not parse or another action
+/// is bound to it.
+fn checked_binding(
+    shortcuts: &ShortcutSettings,
+    action: ShortcutAction,
+    accelerator: Option<&str>,
+) -> CommandResult<Option<Shortcut>> {
+    let Some(accelerator) = accelerator else {
+        return Ok(None);
+    };
+    let shortcut = parse(accelerator)?;
+    match ACTIONS
+        .into_iter()
+        .find(|other| *other != action && other.shortcut(shortcuts) == Some(shortcut))
+    {
+        Some(other) => Err(CommandError::ShortcutConflict(format!(
+            "{} is already bound to {}",
+            accelerator,
+            other.label()
+        ))),
+        None => Ok(Some(shortcut)),
+    }
+}
+
+/// Bind `action` to `accelerator`, or unbind it when `None`. Fails without changing the
+/// binding when the accelerator is bound to another action or taken by another application.
+#[tauri::command]
+#[specta::specta]
+pub fn rebind_shortcut(
+    app: tauri::AppHandle,
+    action: ShortcutAction,
+    accelerator: Option<String>,
+) -> CommandResult<Vec<ShortcutBinding>> {
+    let mut shortcuts = current_settings(&app)?.shortcuts;
+    let previous = action.shortcut(&shortcuts);
+    let shortcut = checked_binding(&shortcuts, action, accelerator.as_deref())?;
+    // Registering first tells whether another application holds the accelerator
+    let newly_registered = match shortcut {
+        Some(shortcut) if !app.global_shortcut().is_registered(shortcut) => {
+            app.global_shortcut().register(shortcut).map_err(|e| {
+                CommandError::ShortcutConflict(format!(
+                    "{} is taken by another application: {}",
+                    accelerator.as_deref().unwrap_or_default(),
+                    e
+                ))
+            })?;
+            Some(shortcut)
+        }
+        _ => None,
+    };
+
+    action.set_accelerator(&mut shortcuts, accelerator);
+    if let Err(error) = save_shortcuts(&app, shortcuts) {
+        // Keep the old binding, which is still registered
+        if let Some(shortcut) = newly_registered {
+            let _ = app.global_shortcut().unregister(shortcut);
+        }
+        return Err(error);
+    }
+    if let Some(previous) = previous.filter(|previous| Some(*previous) != shortcut) {
+        let _ = app.global_shortcut().unregister(previous);
+    }
+    get_shortcuts(app)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_accelerators_bound_to_another_action() {
+        let shortcuts = ShortcutSettings {
+            toggle_timer: Some("Control+Shift+T".to_string()),
+            pause_tracking: None,
+            // Never registered, so it conflicts with nothing
+            quick_entry: Some("Control+".to_string()),
+        };
+        let conflict =
+            |action, accelerator| match checked_binding(&shortcuts, action, Some(accelerator)) {
+                Err(CommandError::ShortcutConflict(message)) => Some(message),
+                Err(error) => panic!("{}: {}", accelerator, error),
+                Ok(_) => None,
+            };
+
+        assert_eq!(
+            conflict(ShortcutAction::PauseTracking, "Control+Shift+T").as_deref(),
+            Some("Control+Shift+T is already bound to start/stop timer")
+        );
+        // The same shortcut, spelled differently
+        assert!(conflict(ShortcutAction::QuickEntry, "Shift+Ctrl+T").is_some());
+        // Rebinding an action to its own shortcut is no conflict
+        assert_eq!(
+            conflict(ShortcutAction::ToggleTimer, "Control+Shift+T"),
+            None
+        );
+        assert_eq!(
+            conflict(ShortcutAction::PauseTracking, "Control+Shift+P"),
+            None
+        );
+    }
+
+    #[test]
+    fn unbinds_without_an_accelerator_and_rejects_invalid_ones() {
+        let shortcuts = ShortcutSettings::default();
+        assert!(matches!(
+            checked_binding(&shortcuts, ShortcutAction::ToggleTimer, None),
+            Ok(None)
+        ));
+        assert!(matches!(
+            checked_binding(&shortcuts, ShortcutAction::ToggleTimer, Some("Control+")),
+            Err(CommandError::InvalidShortcut(_))
+        ));
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    commands::{
+        calendar::import_calendar,
+        deep_link::{is_uuid, open_deep_link},
+        error::{CommandError, CommandResult},
+        remote_control::handle_cli_request,
+        timer::{stop_timer, switch_timer},
+        tray::{format_elapsed, recent_projects, show_main_window},
+    },
+    instance_ipc::{self, InstanceRequest, InstanceResponse, EXIT_FAILED, EXIT_OK, EXIT_USAGE},
+};
+
+/// Something asked for on the command line:
+/// `recount [recount://…] [--start <project>] [--stop] [<calendar.ics>…]`.
+#[derive(Debug, Clone, PartialEq)]
+enum LaunchArgument {
+    Link(String),
+    /// A project name, as in the tray, or id.
+    Start(String),
+    Stop,
+    ImportCalendar(PathBuf),
+}
+
+fn invalid(message: impl Into<String>) -> CommandError {
+    CommandError::InvalidArguments(message.into())
+}
+
+fn parse_arguments(args: &[String], cwd: &Path) -> CommandResult<Vec<LaunchArgument>> {
+    let mut parsed = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        parsed.push(match arg.as_str() {
+            "--start" => LaunchArgument::Start(
+                args.next()
+                    .ok_or_else(|| invalid("--start needs a project"))?
+                    .clone(),
+            ),
+            "--stop" => LaunchArgument::Stop,
+            // Added by macOS to apps started from Finder
+            arg if arg.starts_with("-psn_") => continue,
+            arg if arg.starts_with("--start=") => {
+                LaunchArgument::Start(arg["--start=".len()..].to_string())
+            }
+            arg if arg.starts_with("recount:") => LaunchArgument::Link(arg.to_string()),
+            arg if arg.starts_with('-') => return Err(invalid(format!("Unknown option {}", arg))),
+            path => {
+                let path = cwd.join(path);
+                let is_calendar = path
+                    .extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("ics"));
+                if !is_calendar {
+                    return Err(invalid(format!(
+                        "Cannot import {}, only .ics calendars can be imported",
+                        path.display()
+                    )));
+                }
+                LaunchArgument::ImportCalendar(path)
+            }
+        });
+    }
+    Ok(parsed)
+}
+
+/// The id of a project given by id or by its name in the tray.
+pub fn resolve_project(app: &tauri::AppHandle, project: &str) -> CommandResult<String> {
+    if is_uuid(project) {
+        return Ok(project.to_ascii_lowercase());
+    }
+    recent_projects(app)
+        .into_iter()
+        .find(|recent| recent.name.eq_ignore_ascii_case(project.trim()))
+        .map(|recent| recent.id)
+        .ok_or_else(|| invalid(format!("No recent project is called {}", project)))
+}
+
+fn run_argument(app: &tauri::AppHandle, argument: LaunchArgument) -> CommandResult<String> {
+    match argument {
+        LaunchArgument::Link(link) => {
+            open_deep_link(app, &link).map(|_| format!("Opened {}", link))
+        }
+        LaunchArgument::Start(project) => {
+            let project_id = resolve_project(app, &project)?;
+            switch_timer(app.clone(), project_id, None, None)
+                .map(|_| format!("Started a timer for {}", project))
+        }
+        LaunchArgument::Stop => stop_timer(app.clone(), None).map(|entry| {
+            format!(
+                "Stopped the timer after {}",
+                format_elapsed(entry.stopped_at - entry.started_at)
+            )
+        }),
+        LaunchArgument::ImportCalendar(path) => {
+            import_calendar(app.clone(), path.to_string_lossy().to_string(), None)
+                .map(|source| format!("Imported the calendar {}", source.name))
+        }
+    }
+}
+
+/// The exit code a command line or CLI request that failed with `error` ends with.
+pub fn exit_code(error: &CommandError) -> i32 {
+    match error {
+        CommandError::InvalidArguments(_) | CommandError::InvalidDeepLink(_) => EXIT_USAGE,
+        _ => EXIT_FAILED,
+    }
+}
+
+/// Carry out a command line in order, stopping at the first failure.
+fn route_arguments(app: &tauri::AppHandle, args: &[String], cwd: &Path) -> InstanceResponse {
+    let arguments = match parse_arguments(args, cwd) {
+        Ok(arguments) => arguments,
+        Err(error) => return InstanceResponse::failed(EXIT_USAGE, error.to_string()),
+    };
+    let mut output = Vec::new();
+    for argument in arguments {
+        match run_argument(app, argument) {
+            Ok(line) => output.push(line),
+            Err(error) => {
+                output.push(error.to_string());
+                return InstanceResponse::failed(exit_code(&error), output.join("\n"));
+            }
+        }
+    }
+    InstanceResponse::ok(output.join("\n"))
+}
+
+/// Hand the command line to an already running instance, which comes to the front.
+/// Returns the exit code for this process, or `None` when no instance is running.
+pub fn hand_off_to_running_instance() -> Option<i32> {
+    let request = InstanceRequest::Launch {
+        args: env::args().skip(1).collect(),
+        cwd: env::current_dir().unwrap_or_default(),
+    };
+    let response = instance_ipc::send(&request).ok()?;
+    if !response.output.is_empty() {
+        if response.exit_code == EXIT_OK {
+            println!("{}", response.output);
+        } else {
+            eprintln!("{}", response.output);
+        }
+    }
+    Some(response.exit_code)
+}
+
+fn handle_request(app: &tauri::AppHandle, request: InstanceRequest) -> InstanceResponse {
+    match request {
+        InstanceRequest::Launch { args, cwd } => {
+            show_main_window(app);
+            route_arguments(app, &args, &cwd)
+        }
+        request => handle_cli_request(app, request),
+    }
+}
+
+/// Answer requests of other processes on the instance socket.
+#[cfg(unix)]
+pub fn listen_for_instances(app: tauri::AppHandle) {
+    use std::{
+        fs, os::unix::fs::PermissionsExt, os::unix::net::UnixListener, thread, time::Duration,
+    };
+
+    // How long a client may take to send its request, so one that never does cannot hold
+    // a thread forever
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+    let Ok(path) = instance_ipc::socket_path() else {
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    // The single instance plugin makes this the only instance, so an existing socket was
+    // left behind by one that crashed
+    let _ = fs::remove_file(&path);
+    let Ok(listener) = UnixListener::bind(&path) else {
+        return;
+    };
+    let _ = fs::set_permissions(&path, fs::Permissions::from_mode(0o600));
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let app = app.clone();
+            thread::spawn(move || {
+                if stream.set_read_timeout(Some(REQUEST_TIMEOUT)).is_err() {
+                    return;
+                }
+                let response = match instance_ipc::read_message(&stream) {
+                    Ok(request) => handle_request(&app, request),
+                    Err(error) => {
+                        InstanceResponse::failed(EXIT_USAGE, format!("Invalid request: {}", error))
+                    }
+                };
+                let _ = instance_ipc::write_message(&stream, &response);
+            });
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn listen_for_instances(_app: tauri::AppHandle) {}
+
+/// The arguments without `recount:` links, which the deep link plugin delivers itself.
+fn without_links(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    args.into_iter()
+        .filter(|arg| !arg.starts_with("recount:"))
+        .collect()
+}
+
+/// Called by the single instance plugin when a second launch could not hand off its
+/// command line over the socket. Links are left to the deep link plugin, which passes
+/// them to `on_open_url`.
+pub fn handle_second_instance(app: &tauri::AppHandle, argv: Vec<String>, cwd: String) {
+    handle_request(
+        app,
+        InstanceRequest::Launch {
+            args: without_links(argv.into_iter().skip(1)),
+            cwd: PathBuf::from(cwd),
+        },
+    );
+}
+
+/// Carry out the command line this instance was started with. Links are left to
+/// `route_deep_links`, which reads them from the launch arguments as well.
+pub fn route_launch_arguments(app: &tauri::AppHandle) {
+    let args = without_links(env::args().skip(1));
+    if args.is_empty() {
+        return;
+    }
+    let response = route_arguments(app, &args, &env::current_dir().unwrap_or_default());
+    if response.exit_code != EXIT_OK {
+        eprintln!("{}", response.output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaves_links_to_the_deep_link_plugin() {
+        let argv = ["recount://timer/start", "--stop", "recount:open"]
+            .map(str::to_string)
+            .to_vec();
+        let args = without_links(argv);
+        assert_eq!(args, ["--stop"]);
+        assert_eq!(
+            parse_arguments(&args, Path::new("/")).unwrap(),
+            [LaunchArgument::Stop]
+        );
+    }
+}
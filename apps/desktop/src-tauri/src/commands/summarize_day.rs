@@ -0,0 +1,303 @@
+use crate::commands::ai::{
+    inference::{InferenceModels, MAX_INPUT_TOKENS},
+    runtime::AiRuntime,
+};
+use crate::commands::{
+    capture_window_activity::ActivityKind,
+    error::{CommandError, CommandResult},
+    issue_references::IssueReference,
+};
+use std::collections::BTreeMap;
+use tauri::Manager;
+
+const UNASSIGNED_PROJECT: &str = "Unassigned";
+const SUMMARY_MAX_LENGTH: usize = 120;
+const MAX_BULLETS_PER_PROJECT: usize = 8;
+/// Room left in the context window for the instruction wrapped around each chunk.
+const PROMPT_OVERHEAD_TOKENS: usize = 64;
+
+#[derive(serde::Deserialize, specta::Type)]
+pub struct ActivityBlockInput {
+    project: Option<String>,
+    application_name: String,
+    window_title: String,
+    duration_seconds: u32,
+    caption: Option<String>,
+    #[serde(default)]
+    kind: ActivityKind,
+    /// Tickets detected in the block's snapshots.
+    #[serde(default)]
+    issues: Vec<IssueReference>,
+}
+
+#[derive(serde::Serialize, specta::Type)]
+pub struct ProjectSummary {
+    project: String,
+    total_seconds: u32,
+    narrative: String,
+    bullets: Vec<String>,
+    /// Tickets worked on, to prefill time entry descriptions and link them.
+    issues: Vec<IssueReference>,
+}
+
+#[derive(serde::Serialize, specta::Type)]
+pub struct DaySummary {
+    total_seconds: u32,
+    projects: Vec<ProjectSummary>,
+}
+
+/// Summarise a day's activity blocks into a timesheet-style narrative per project.
+/// Runs entirely on the local model runtime.
+#[tauri::command]
+#[specta::specta]
+pub async fn summarize_day(
+    app: tauri::AppHandle,
+    blocks: Vec<ActivityBlockInput>,
+) -> CommandResult<DaySummary> {
+    tauri::async_runtime::spawn_blocking(move || -> anyhow::Result<DaySummary> {
+        let mut by_project: BTreeMap<String, Vec<ActivityBlockInput>> = BTreeMap::new();
+        for block in blocks {
+            let project = block
+                .project
+                .clone()
+                .unwrap_or_else(|| UNASSIGNED_PROJECT.to_string());
+            by_project.entry(project).or_default().push(block);
+        }
+
+        let mut projects = app.state::<AiRuntime>().with_models(|models| {
+            by_project
+                .into_iter()
+                .map(|(project, blocks)| summarize_project(models, project, &blocks))
+                .collect::<anyhow::Result<Vec<_>>>()
+        })?;
+        projects.sort_by_key(|p| std::cmp::Reverse(p.total_seconds));
+
+        Ok(DaySummary {
+            total_seconds: projects.iter().map(|p| p.total_seconds).sum(),
+            projects,
+        })
+    })
+    .await?
+    .map_err(CommandError::from)
+}
+
+fn summarize_project(
+    models: &mut InferenceModels,
+    project: String,
+    blocks: &[ActivityBlockInput],
+) -> anyhow::Result<ProjectSummary> {
+    // Merge repeated (app, title) pairs so the bullets and prompt list each activity once
+    let mut activities: BTreeMap<(&str, &str), (u32, Option<&str>, bool)> = BTreeMap::new();
+    for block in blocks {
+        let entry = activities
+            .entry((block.application_name.as_str(), block.window_title.as_str()))
+            .or_insert((0, None, false));
+        entry.0 += block.duration_seconds;
+        if entry.1.is_none() {
+            entry.1 = block.caption.as_deref();
+        }
+        entry.2 |= block.kind == ActivityKind::Meeting;
+    }
+    let mut activities: Vec<_> = activities.into_iter().collect();
+    activities.sort_by(|(_, (a, ..)), (_, (b, ..))| b.cmp(a));
+
+    let lines: Vec<String> = activities
+        .iter()
+        .map(|((app_name, title), (seconds, caption, is_meeting))| {
+            let duration = format_duration(*seconds);
+            let mut line = if *is_meeting {
+                format!("- Meeting in {} ({}): {}", app_name, duration, title)
+            } else {
+                format!("- {} ({}): {}", app_name, duration, title)
+            };
+            if let Some(caption) = caption {
+                line.push_str(&format!(" [{}]", caption));
+            }
+            line
+        })
+        .collect();
+
+    let bullets = activities
+        .iter()
+        .take(MAX_BULLETS_PER_PROJECT)
+        .map(|((app_name, title), (seconds, ..))| {
+            let label = if title.is_empty() { app_name } else { title };
+            format!("{} ({})", label, format_duration(*seconds))
+        })
+        .collect();
+
+    let mut issues: Vec<IssueReference> = Vec::new();
+    for issue in blocks.iter().flat_map(|block| &block.issues) {
+        if !issues.iter().any(|known| known.key == issue.key) {
+            issues.push(issue.clone());
+        }
+    }
+
+    Ok(ProjectSummary {
+        narrative: summarize_lines(models, &project, lines)?,
+        total_seconds: blocks.iter().map(|b| b.duration_seconds).sum(),
+        project,
+        bullets,
+        issues,
+    })
+}
+
+/// What summarising needs from the model, so the chunking can be tested without one.
+trait ChunkSummarizer {
+    fn count_tokens(&self, text: &str) -> anyhow::Result<usize>;
+    fn summarize(&mut self, prompt: &str) -> anyhow::Result<String>;
+}
+
+impl ChunkSummarizer for InferenceModels {
+    fn count_tokens(&self, text: &str) -> anyhow::Result<usize> {
+        InferenceModels::count_tokens(self, text)
+    }
+
+    fn summarize(&mut self, prompt: &str) -> anyhow::Result<String> {
+        self.generate_text_with_limit(None, Some(prompt), SUMMARY_MAX_LENGTH)
+    }
+}
+
+/// Summarise `lines` chunk by chunk, then merge the partial summaries the same way
+/// until everything fits into a single prompt.
+fn summarize_lines(
+    models: &mut impl ChunkSummarizer,
+    project: &str,
+    mut lines: Vec<String>,
+) -> anyhow::Result<String> {
+    let budget = MAX_INPUT_TOKENS - PROMPT_OVERHEAD_TOKENS;
+
+    loop {
+        let chunks = chunk_lines(models, &lines, budget)?;
+        let partials = chunks
+            .iter()
+            .map(|chunk| {
+                let prompt = format!(
+                    "Summarize this work on {} as a timesheet entry:\n{}",
+                    project, chunk
+                );
+                models.summarize(&prompt)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // A single chunk, or no progress, means we are done merging
+        if partials.len() <= 1 || partials.len() >= lines.len() {
+            return Ok(partials.join(" ").trim().to_string());
+        }
+        lines = partials;
+    }
+}
+
+/// Greedily pack lines into chunks of at most `budget` tokens.
+/// A single line over budget becomes its own chunk and is truncated by the model.
+fn chunk_lines(
+    models: &impl ChunkSummarizer,
+    lines: &[String],
+    budget: usize,
+) -> anyhow::Result<Vec<String>> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_tokens = 0;
+
+    for line in lines {
+        let tokens = models.count_tokens(line)?;
+        if !current.is_empty() && current_tokens + tokens > budget {
+            chunks.push(std::mem::take(&mut current));
+            current_tokens = 0;
+        }
+        current.push_str(line);
+        current.push('\n');
+        current_tokens += tokens;
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    Ok(chunks)
+}
+
+fn format_duration(seconds: u32) -> String {
+    let hours = seconds / 3600;
+    let minutes = (seconds % 3600) / 60;
+    if hours > 0 {
+        format!("{}h {:02}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts a token per word and summarises a prompt as `summary_words` words.
+    struct WordModel {
+        summary_words: usize,
+        prompts: Vec<String>,
+    }
+
+    impl ChunkSummarizer for WordModel {
+        fn count_tokens(&self, text: &str) -> anyhow::Result<usize> {
+            Ok(text.split_whitespace().count())
+        }
+
+        fn summarize(&mut self, prompt: &str) -> anyhow::Result<String> {
+            self.prompts.push(prompt.to_string());
+            Ok(vec!["done"; self.summary_words].join(" "))
+        }
+    }
+
+    fn model(summary_words: usize) -> WordModel {
+        WordModel {
+            summary_words,
+            prompts: Vec::new(),
+        }
+    }
+
+    fn lines(count: usize, words: usize) -> Vec<String> {
+        vec![vec!["word"; words].join(" "); count]
+    }
+
+    #[test]
+    fn packs_lines_into_chunks_within_the_budget() {
+        let chunks = chunk_lines(&model(1), &lines(5, 4), 10).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].lines().count(), 2);
+        assert_eq!(chunks[2].lines().count(), 1);
+
+        // A line over budget is kept whole in a chunk of its own
+        let long = lines(1, 30).remove(0);
+        let chunks = chunk_lines(&model(1), &[long, "short".to_string()], 10).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert!(chunk_lines(&model(1), &[], 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn summarizes_a_small_day_in_one_prompt() {
+        let mut model = model(3);
+        let summary = summarize_lines(&mut model, "Recount", lines(3, 5)).unwrap();
+        assert_eq!(summary, "done done done");
+        assert_eq!(model.prompts.len(), 1);
+        assert!(model.prompts[0].contains("work on Recount"));
+    }
+
+    #[test]
+    fn merges_partial_summaries_until_they_fit() {
+        // 4 chunks of lines, whose 4 summaries fit in one merge prompt
+        let budget = MAX_INPUT_TOKENS - PROMPT_OVERHEAD_TOKENS;
+        let mut model = model(10);
+        let summary = summarize_lines(&mut model, "Recount", lines(8, budget / 2)).unwrap();
+        assert_eq!(model.prompts.len(), 5);
+        assert_eq!(summary.split_whitespace().count(), 10);
+    }
+
+    #[test]
+    fn stops_merging_when_summaries_do_not_shrink() {
+        // Every summary fills a whole chunk, so merging makes no progress
+        let budget = MAX_INPUT_TOKENS - PROMPT_OVERHEAD_TOKENS;
+        let mut model = model(budget);
+        let summary = summarize_lines(&mut model, "Recount", lines(3, budget)).unwrap();
+        assert_eq!(model.prompts.len(), 3);
+        assert_eq!(summary.split_whitespace().count(), 3 * budget);
+    }
+}
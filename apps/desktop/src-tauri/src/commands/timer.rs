@@ -0,0 +1,576 @@
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tauri::Manager;
+use tauri_specta::Event;
+
+use crate::commands::error::{CommandError, CommandResult};
+
+const TIMER_FILE: &str = "timer.json";
+const PENDING_ENTRIES_FILE: &str = "pending_time_entries.json";
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The running time entry: a core `TimeEntry` whose `stoppedAt` is not set yet.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, specta::Type)]
+pub struct RunningTimer {
+    /// Id the time entry is created with once stopped. The entry stays pending under this
+    /// id until the frontend acknowledges it, so creating it twice is never needed.
+    pub id: String,
+    pub project_id: String,
+    pub task_id: Option<String>,
+    /// Seconds since the epoch.
+    #[specta(type = f64)]
+    pub started_at: u64,
+    /// The entry's notes as serialised JSON, passed through untouched.
+    pub notes: Option<String>,
+}
+
+/// A time entry the timer produced, ready to be created through the core API.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, specta::Type)]
+pub struct StoppedTimeEntry {
+    pub id: String,
+    pub project_id: String,
+    pub task_id: Option<String>,
+    #[specta(type = f64)]
+    pub started_at: u64,
+    #[specta(type = f64)]
+    pub stopped_at: u64,
+    pub notes: Option<String>,
+}
+
+/// Emitted to every window after each transition.
+#[derive(Debug, Clone, serde::Serialize, specta::Type, tauri_specta::Event)]
+pub struct TimerStateChanged {
+    running: Option<RunningTimer>,
+}
+
+/// Emitted to every window when a stopped time entry is added to or removed from the
+/// outbox. Carries every entry the frontend still has to create.
+#[derive(Debug, Clone, serde::Serialize, specta::Type, tauri_specta::Event)]
+pub struct PendingTimeEntriesChanged {
+    entries: Vec<StoppedTimeEntry>,
+}
+
+/// Emitted to every window every second while a timer runs.
+#[derive(Debug, Clone, serde::Serialize, specta::Type, tauri_specta::Event)]
+pub struct TimerTick {
+    id: String,
+    #[specta(type = f64)]
+    elapsed_seconds: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Timer {
+    running: Option<RunningTimer>,
+    /// Outbox of stopped time entries the frontend has not created through the core API.
+    pending: Vec<StoppedTimeEntry>,
+}
+
+/// The running timer and the outbox of stopped entries, mirrored to `timer.json` and
+/// `pending_time_entries.json` in app local data before every change is applied, so
+/// neither is lost when the window closes or the app crashes.
+#[derive(Default)]
+pub struct TimerState(Mutex<Timer>);
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// A random (version 4) UUID, the format of the core's `TimeEntryId`.
+fn generate_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn read_json<T: serde::de::DeserializeOwned + Default>(path: &Path) -> CommandResult<T> {
+    match fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+            CommandError::Internal(format!("Corrupt {}: {}", path.to_string_lossy(), e))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The timer persisted in `data_dir`, for the CLI when the app is not running.
+pub fn read_timer_file(data_dir: &Path) -> CommandResult<Option<RunningTimer>> {
+    read_json(&data_dir.join(TIMER_FILE))
+}
+
+/// Write to a temporary file, flush it to disk and rename it over the old state, so a
+/// crash leaves either the old or the new state behind.
+fn save(path: &Path, value: &impl serde::Serialize) -> CommandResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let bytes =
+        serde_json::to_vec_pretty(value).map_err(|e| CommandError::Internal(e.to_string()))?;
+    let tmp_path = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(&bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn lock_timer(state: &TimerState) -> CommandResult<std::sync::MutexGuard<'_, Timer>> {
+    state
+        .0
+        .lock()
+        .map_err(|_| CommandError::Internal("Timer lock poisoned".to_string()))
+}
+
+/// Stop `running` at `stopped_at`, like the core's `updateTimeEntry` setting `stoppedAt`.
+fn stop(running: &RunningTimer, stopped_at: u64) -> CommandResult<StoppedTimeEntry> {
+    if stopped_at < running.started_at {
+        return Err(CommandError::StoppedBeforeStarted(format!(
+            "The timer started at {} and cannot stop at {}",
+            running.started_at, stopped_at
+        )));
+    }
+    Ok(StoppedTimeEntry {
+        id: running.id.clone(),
+        project_id: running.project_id.clone(),
+        task_id: running.task_id.clone(),
+        started_at: running.started_at,
+        stopped_at,
+        notes: running.notes.clone(),
+    })
+}
+
+/// The result of a transition: the next running timer and the entry it stopped, if any.
+struct Transition<T> {
+    running: Option<RunningTimer>,
+    stopped: Option<StoppedTimeEntry>,
+    result: T,
+}
+
+/// The transitions of the core `time-entry` domain. They only compute the next state;
+/// `transition` persists and applies it.
+impl Timer {
+    fn running(&self) -> CommandResult<&RunningTimer> {
+        self.running
+            .as_ref()
+            .ok_or_else(|| CommandError::NoRunningTimer("No timer is running".to_string()))
+    }
+
+    /// Start `timer`. Fails while another timer runs.
+    fn start(&self, timer: RunningTimer) -> CommandResult<Transition<RunningTimer>> {
+        if self.running.is_some() {
+            return Err(CommandError::TimerAlreadyRunning(
+                "Stop or switch the running timer first".to_string(),
+            ));
+        }
+        Ok(Transition {
+            running: Some(timer.clone()),
+            stopped: None,
+            result: timer,
+        })
+    }
+
+    /// Stop the running timer at `stopped_at`.
+    fn stop(&self, stopped_at: u64) -> CommandResult<Transition<StoppedTimeEntry>> {
+        let entry = stop(self.running()?, stopped_at)?;
+        Ok(Transition {
+            running: None,
+            stopped: Some(entry.clone()),
+            result: entry,
+        })
+    }
+
+    /// Stop the running timer, if any, when `next` starts.
+    fn switch(&self, next: RunningTimer) -> CommandResult<Transition<Option<StoppedTimeEntry>>> {
+        let stopped = self
+            .running
+            .as_ref()
+            .map(|running| stop(running, next.started_at))
+            .transpose()?;
+        Ok(Transition {
+            running: Some(next),
+            stopped: stopped.clone(),
+            result: stopped,
+        })
+    }
+
+    /// Drop the running timer without producing a time entry.
+    fn discard(&self) -> CommandResult<Transition<RunningTimer>> {
+        Ok(Transition {
+            running: None,
+            stopped: None,
+            result: self.running()?.clone(),
+        })
+    }
+
+    /// The timer once `transition` is applied, with a stopped entry added to the outbox.
+    fn after<T>(&self, transition: &Transition<T>) -> Timer {
+        let mut pending = self.pending.clone();
+        pending.extend(transition.stopped.clone());
+        Timer {
+            running: transition.running.clone(),
+            pending,
+        }
+    }
+
+    /// The timer as read back from disk. A running timer whose entry is already in the
+    /// outbox was stopped, but the app quit before the running timer was cleared.
+    fn restore(running: Option<RunningTimer>, pending: Vec<StoppedTimeEntry>) -> Timer {
+        let running = running.filter(|running| !pending.iter().any(|entry| entry.id == running.id));
+        Timer { running, pending }
+    }
+}
+
+/// Persist the timer, with its outbox when `pending_changed`. The outbox goes first, see
+/// `transition`.
+fn save_timer(data_dir: &Path, timer: &Timer, pending_changed: bool) -> CommandResult<()> {
+    if pending_changed {
+        save(&data_dir.join(PENDING_ENTRIES_FILE), &timer.pending)?;
+    }
+    save(&data_dir.join(TIMER_FILE), &timer.running)
+}
+
+/// Apply a transition to the timer files in `data_dir`, for the CLI while the app is not
+/// running. The app restores the result, and the frontend creates the stopped entries,
+/// when it next starts.
+fn transition_offline<T>(
+    data_dir: &Path,
+    apply: impl FnOnce(&Timer) -> CommandResult<Transition<T>>,
+) -> CommandResult<T> {
+    let timer = Timer::restore(
+        read_timer_file(data_dir)?,
+        read_json(&data_dir.join(PENDING_ENTRIES_FILE))?,
+    );
+    let transition = apply(&timer)?;
+    save_timer(
+        data_dir,
+        &timer.after(&transition),
+        transition.stopped.is_some(),
+    )?;
+    Ok(transition.result)
+}
+
+/// Start a timer for `project_id` now, like `switch_timer`, while the app is not running.
+pub fn switch_timer_offline(
+    data_dir: &Path,
+    project_id: String,
+) -> CommandResult<Option<StoppedTimeEntry>> {
+    transition_offline(data_dir, |timer| {
+        timer.switch(RunningTimer {
+            id: generate_id(),
+            project_id,
+            task_id: None,
+            started_at: now(),
+            notes: None,
+        })
+    })
+}
+
+/// Stop the running timer now, like `stop_timer`, while the app is not running.
+pub fn stop_timer_offline(data_dir: &Path) -> CommandResult<StoppedTimeEntry> {
+    transition_offline(data_dir, |timer| timer.stop(now()))
+}
+
+/// Apply a transition to the timer: persist the new state, then emit it.
+///
+/// A stopped entry is added to the outbox before the running timer is cleared, so a crash
+/// in between leaves the timer running with its entry already pending. `restore_timer`
+/// recognises that by the shared id.
+fn transition<T>(
+    app: &tauri::AppHandle,
+    apply: impl FnOnce(&Timer) -> CommandResult<Transition<T>>,
+) -> CommandResult<T> {
+    let data_dir = app.path().app_local_data_dir()?;
+    let state = app.state::<TimerState>();
+    let mut timer = lock_timer(&state)?;
+    let transition = apply(&timer)?;
+    let next = timer.after(&transition);
+
+    let stopped = transition.stopped.is_some();
+    save_timer(&data_dir, &next, stopped)?;
+    *timer = next;
+    let running = timer.running.clone();
+    let pending = stopped.then(|| timer.pending.clone());
+    drop(timer);
+
+    let _ = TimerStateChanged { running }.emit(app);
+    if let Some(entries) = pending {
+        let _ = PendingTimeEntriesChanged { entries }.emit(app);
+    }
+    Ok(transition.result)
+}
+
+/// Restore a timer that was running when the app last quit or crashed, and the outbox of
+/// stopped entries, and emit ticks while a timer runs. An unreadable timer file starts the
+/// app without a running timer; an unreadable outbox is moved aside rather than replaced.
+pub fn restore_timer(app: tauri::AppHandle) {
+    if let Ok(data_dir) = app.path().app_local_data_dir() {
+        let running = read_timer_file(&data_dir).unwrap_or(None);
+        let pending_path = data_dir.join(PENDING_ENTRIES_FILE);
+        let pending: Vec<StoppedTimeEntry> = read_json(&pending_path).unwrap_or_else(|_| {
+            let _ = fs::rename(&pending_path, pending_path.with_extension("json.corrupt"));
+            Vec::new()
+        });
+        let restored = Timer::restore(running.clone(), pending);
+        if restored.running != running {
+            let _ = save(&data_dir.join(TIMER_FILE), &restored.running);
+        }
+        if let Ok(mut timer) = app.state::<TimerState>().0.lock() {
+            *timer = restored;
+        }
+    }
+
+    thread::spawn(move || loop {
+        thread::sleep(TICK_INTERVAL);
+        if let Some(running) = running_timer(&app) {
+            let _ = TimerTick {
+                elapsed_seconds: now().saturating_sub(running.started_at),
+                id: running.id,
+            }
+            .emit(&app);
+        }
+    });
+}
+
+/// The running timer, for use outside of commands.
+pub fn running_timer(app: &tauri::AppHandle) -> Option<RunningTimer> {
+    app.state::<TimerState>().0.lock().ok()?.running.clone()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_timer(app: tauri::AppHandle) -> CommandResult<Option<RunningTimer>> {
+    Ok(lock_timer(&app.state::<TimerState>())?.running.clone())
+}
+
+/// Stopped time entries the frontend still has to create through the core API, oldest
+/// first. Also emitted as `PendingTimeEntriesChanged` whenever the outbox changes.
+#[tauri::command]
+#[specta::specta]
+pub fn get_pending_time_entries(app: tauri::AppHandle) -> CommandResult<Vec<StoppedTimeEntry>> {
+    Ok(lock_timer(&app.state::<TimerState>())?.pending.clone())
+}
+
+/// Remove a stopped time entry from the outbox once the frontend created it through the
+/// core API. Unknown ids are ignored, so acknowledging twice is harmless.
+#[tauri::command]
+#[specta::specta]
+pub fn acknowledge_time_entry(app: tauri::AppHandle, id: String) -> CommandResult<()> {
+    let data_dir = app.path().app_local_data_dir()?;
+    let state = app.state::<TimerState>();
+    let mut timer = lock_timer(&state)?;
+    if !timer.pending.iter().any(|entry| entry.id == id) {
+        return Ok(());
+    }
+    let mut pending = timer.pending.clone();
+    pending.retain(|entry| entry.id != id);
+    save(&data_dir.join(PENDING_ENTRIES_FILE), &pending)?;
+    timer.pending = pending.clone();
+    drop(timer);
+
+    let _ = PendingTimeEntriesChanged { entries: pending }.emit(&app);
+    Ok(())
+}
+
+/// Start a timer, now or at `started_at`. Fails while another timer runs; use
+/// `switch_timer` to move on to another project.
+#[tauri::command]
+#[specta::specta]
+pub fn start_timer(
+    app: tauri::AppHandle,
+    project_id: String,
+    task_id: Option<String>,
+    notes: Option<String>,
+    started_at: Option<u32>,
+) -> CommandResult<RunningTimer> {
+    transition(&app, |timer| {
+        timer.start(RunningTimer {
+            id: generate_id(),
+            project_id,
+            task_id,
+            started_at: started_at.map_or_else(now, u64::from),
+            notes,
+        })
+    })
+}
+
+/// Stop the running timer, now or at `stopped_at`, and return the finished time entry.
+/// The entry is also added to the outbox until it is acknowledged.
+#[tauri::command]
+#[specta::specta]
+pub fn stop_timer(
+    app: tauri::AppHandle,
+    stopped_at: Option<u32>,
+) -> CommandResult<StoppedTimeEntry> {
+    transition(&app, |timer| {
+        timer.stop(stopped_at.map_or_else(now, u64::from))
+    })
+}
+
+/// Stop the running timer, if any, and start one for another project at the same moment.
+/// Returns the time entry that was stopped, which is also added to the outbox.
+#[tauri::command]
+#[specta::specta]
+pub fn switch_timer(
+    app: tauri::AppHandle,
+    project_id: String,
+    task_id: Option<String>,
+    notes: Option<String>,
+) -> CommandResult<Option<StoppedTimeEntry>> {
+    transition(&app, |timer| {
+        timer.switch(RunningTimer {
+            id: generate_id(),
+            project_id,
+            task_id,
+            started_at: now(),
+            notes,
+        })
+    })
+}
+
+/// Throw the running timer away without producing a time entry.
+#[tauri::command]
+#[specta::specta]
+pub fn discard_timer(app: tauri::AppHandle) -> CommandResult<RunningTimer> {
+    transition(&app, Timer::discard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timer(id: &str, project_id: &str, started_at: u64) -> RunningTimer {
+        RunningTimer {
+            id: id.to_string(),
+            project_id: project_id.to_string(),
+            task_id: None,
+            started_at,
+            notes: None,
+        }
+    }
+
+    fn running(timer: RunningTimer) -> Timer {
+        Timer {
+            running: Some(timer),
+            pending: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn starts_only_when_no_timer_runs() {
+        let idle = Timer::default();
+        let started = idle.start(timer("a", "project", 100)).unwrap();
+        assert_eq!(idle.after(&started), running(timer("a", "project", 100)));
+
+        let busy = running(timer("a", "project", 100));
+        assert!(matches!(
+            busy.start(timer("b", "other", 200)),
+            Err(CommandError::TimerAlreadyRunning(_))
+        ));
+    }
+
+    #[test]
+    fn stops_into_the_outbox() {
+        assert!(matches!(
+            Timer::default().stop(200),
+            Err(CommandError::NoRunningTimer(_))
+        ));
+
+        let busy = running(timer("a", "project", 100));
+        assert!(matches!(
+            busy.stop(99),
+            Err(CommandError::StoppedBeforeStarted(_))
+        ));
+
+        let stopped = busy.stop(200).unwrap();
+        assert_eq!(stopped.result.stopped_at, 200);
+        let next = busy.after(&stopped);
+        assert_eq!(next.running, None);
+        assert_eq!(next.pending, [stopped.result]);
+    }
+
+    #[test]
+    fn switches_at_the_moment_the_next_timer_starts() {
+        let idle = Timer::default();
+        let switched = idle.switch(timer("a", "project", 100)).unwrap();
+        assert_eq!(switched.result, None);
+        assert_eq!(idle.after(&switched), running(timer("a", "project", 100)));
+
+        let busy = running(timer("a", "project", 100));
+        let switched = busy.switch(timer("b", "other", 300)).unwrap();
+        let stopped = switched.result.clone().unwrap();
+        assert_eq!((stopped.id.as_str(), stopped.stopped_at), ("a", 300));
+        let next = busy.after(&switched);
+        assert_eq!(next.running, Some(timer("b", "other", 300)));
+        assert_eq!(next.pending, [stopped]);
+    }
+
+    #[test]
+    fn discards_without_an_entry() {
+        assert!(matches!(
+            Timer::default().discard(),
+            Err(CommandError::NoRunningTimer(_))
+        ));
+
+        let busy = running(timer("a", "project", 100));
+        let discarded = busy.discard().unwrap();
+        assert_eq!(discarded.result, timer("a", "project", 100));
+        assert_eq!(busy.after(&discarded), Timer::default());
+    }
+
+    #[test]
+    fn transitions_the_timer_files_offline() {
+        let dir = std::env::temp_dir().join(format!("recount-timer-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(
+            switch_timer_offline(&dir, "project".to_string()).unwrap(),
+            None
+        );
+        let running = read_timer_file(&dir).unwrap().unwrap();
+        assert_eq!(running.project_id, "project");
+
+        let stopped = stop_timer_offline(&dir).unwrap();
+        assert_eq!(stopped.id, running.id);
+        assert_eq!(read_timer_file(&dir).unwrap(), None);
+        let pending: Vec<StoppedTimeEntry> = read_json(&dir.join(PENDING_ENTRIES_FILE)).unwrap();
+        assert_eq!(pending, [stopped]);
+        assert!(matches!(
+            stop_timer_offline(&dir),
+            Err(CommandError::NoRunningTimer(_))
+        ));
+    }
+
+    #[test]
+    fn restores_a_timer_stopped_before_a_crash_as_stopped() {
+        let stopped = running(timer("a", "project", 100))
+            .stop(200)
+            .unwrap()
+            .result;
+        let restored = Timer::restore(Some(timer("a", "project", 100)), vec![stopped.clone()]);
+        assert_eq!(restored.running, None);
+        assert_eq!(restored.pending, std::slice::from_ref(&stopped));
+
+        // Another timer started after the pending entry keeps running
+        let restored = Timer::restore(Some(timer("b", "other", 300)), vec![stopped]);
+        assert_eq!(restored.running, Some(timer("b", "other", 300)));
+    }
+}
@@ -0,0 +1,74 @@
+pub mod parsers;
+
+use std::{collections::HashMap, sync::Arc};
+
+use crate::commands::platform::ApplicationInfo;
+
+/// What an editor or terminal tells about the work in its window title.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, specta::Type)]
+pub struct TitleContext {
+    /// Name of the workspace or project folder.
+    pub project: Option<String>,
+    /// Path of the project folder or working directory, when the title has it.
+    pub directory: Option<String>,
+    pub file: Option<String>,
+    pub branch: Option<String>,
+    /// Host of a remote session, e.g. VS Code Remote-SSH or `ssh` in a terminal.
+    pub ssh_host: Option<String>,
+}
+
+/// Turns the window title of one kind of application into a `TitleContext`.
+pub trait TitleParser: Send + Sync {
+    /// `None` when the title does not have the expected shape.
+    fn parse(&self, title: &str) -> Option<TitleContext>;
+}
+
+/// Title parsers keyed by lowercase bundle id or application name.
+pub struct TitleParserRegistry {
+    parsers: HashMap<String, Arc<dyn TitleParser>>,
+}
+
+impl Default for TitleParserRegistry {
+    /// The registry with parsers for the common editors and terminals.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(parsers::VSCODE_APPS, parsers::VsCode);
+        registry.register(parsers::JETBRAINS_APPS, parsers::JetBrains);
+        registry.register(parsers::TERMINAL_APPS, parsers::Terminal);
+        registry
+    }
+}
+
+impl TitleParserRegistry {
+    pub fn empty() -> Self {
+        Self {
+            parsers: HashMap::new(),
+        }
+    }
+
+    /// Use `parser` for applications with any of the given bundle ids or names.
+    pub fn register(&mut self, apps: &[&str], parser: impl TitleParser + 'static) {
+        let parser: Arc<dyn TitleParser> = Arc::new(parser);
+        for app in apps {
+            self.parsers.insert(app.to_lowercase(), Arc::clone(&parser));
+        }
+    }
+
+    /// The parser for an application, preferring its bundle id over its name.
+    fn parser(&self, info: &ApplicationInfo) -> Option<&Arc<dyn TitleParser>> {
+        info.bundle_id
+            .as_ref()
+            .and_then(|id| self.parsers.get(&id.to_lowercase()))
+            .or_else(|| self.parsers.get(&info.app_name.to_lowercase()))
+    }
+
+    /// Whether the application is one of the registered editors or terminals.
+    pub fn handles(&self, info: &ApplicationInfo) -> bool {
+        self.parser(info).is_some()
+    }
+
+    /// Parse the window title of the focused application.
+    pub fn parse(&self, info: &ApplicationInfo) -> Option<TitleContext> {
+        self.parser(info)?.parse(&info.window_title)
+    }
+}
@@ -0,0 +1,454 @@
+use super::{TitleContext, TitleParser};
+
+/// Bundle ids (macOS), desktop file ids and `WM_CLASS`es (Linux) of VS Code and its forks.
+pub const VSCODE_APPS: &[&str] = &[
+    "com.microsoft.VSCode",
+    "com.microsoft.VSCodeInsiders",
+    "com.vscodium",
+    "com.todesktop.230313mzl4w4u92",
+    "com.exafunction.windsurf",
+    "code",
+    "code-oss",
+    "code - oss",
+    "code-insiders",
+    "code - insiders",
+    "codium",
+    "vscodium",
+    "cursor",
+    "windsurf",
+];
+
+pub const JETBRAINS_APPS: &[&str] = &[
+    "com.jetbrains.intellij",
+    "com.jetbrains.intellij.ce",
+    "com.jetbrains.pycharm",
+    "com.jetbrains.pycharm.ce",
+    "com.jetbrains.WebStorm",
+    "com.jetbrains.goland",
+    "com.jetbrains.CLion",
+    "com.jetbrains.rustrover",
+    "com.jetbrains.PhpStorm",
+    "com.jetbrains.rider",
+    "com.jetbrains.rubymine",
+    "com.google.android.studio",
+    "jetbrains-idea",
+    "jetbrains-idea-ce",
+    "jetbrains-pycharm",
+    "jetbrains-pycharm-ce",
+    "jetbrains-webstorm",
+    "jetbrains-goland",
+    "jetbrains-clion",
+    "jetbrains-rustrover",
+    "jetbrains-phpstorm",
+    "jetbrains-rider",
+    "jetbrains-rubymine",
+    "jetbrains-studio",
+];
+
+pub const TERMINAL_APPS: &[&str] = &[
+    "com.apple.Terminal",
+    "com.googlecode.iterm2",
+    "net.kovidgoyal.kitty",
+    "org.alacritty",
+    "com.github.wez.wezterm",
+    "com.mitchellh.ghostty",
+    "dev.warp.Warp-Stable",
+    "org.gnome.Terminal",
+    "org.gnome.Console",
+    "org.kde.konsole",
+    "gnome-terminal-server",
+    "kgx",
+    "konsole",
+    "kitty",
+    "alacritty",
+    "org.wezfurlong.wezterm",
+    "ghostty",
+    "tilix",
+    "terminator",
+    "xterm",
+    "foot",
+];
+
+/// Application names VS Code and its forks append to the title.
+const VSCODE_NAMES: [&str; 7] = [
+    "Visual Studio Code",
+    "Visual Studio Code - Insiders",
+    "Code - OSS",
+    "VSCodium",
+    "Cursor",
+    "Windsurf",
+    "Code",
+];
+
+const JETBRAINS_NAMES: [&str; 12] = [
+    "IntelliJ IDEA",
+    "PyCharm",
+    "WebStorm",
+    "GoLand",
+    "CLion",
+    "RustRover",
+    "PhpStorm",
+    "Rider",
+    "RubyMine",
+    "DataGrip",
+    "Android Studio",
+    "Fleet",
+];
+
+const SHELLS: [&str; 5] = ["zsh", "bash", "fish", "sh", "nu"];
+
+/// Last component of a path, e.g. the folder name of a project directory.
+fn path_name(path: &str) -> Option<String> {
+    path.trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\'])
+        .next()
+        .filter(|name| !name.is_empty() && *name != "~")
+        .map(str::to_string)
+}
+
+fn looks_like_path(text: &str) -> bool {
+    text.starts_with('~') || text.starts_with('/')
+}
+
+/// Split `text (inner)` into `text` and `inner`.
+fn trailing_group(text: &str, open: char, close: char) -> Option<(&str, &str)> {
+    let rest = text.strip_suffix(close)?;
+    let start = rest.rfind(open)?;
+    Some((rest[..start].trim_end(), &rest[start + open.len_utf8()..]))
+}
+
+/// VS Code and its forks, with the default `window.title`:
+/// `● main.rs — mason-app [SSH: devbox] — Visual Studio Code`.
+/// The separator is an em dash on macOS and a hyphen elsewhere.
+pub struct VsCode;
+
+impl TitleParser for VsCode {
+    fn parse(&self, title: &str) -> Option<TitleContext> {
+        let title = title.trim_start_matches(['●', '•']).trim();
+        // Just the application name: no folder is open
+        if VSCODE_NAMES.contains(&title) {
+            return None;
+        }
+        let separator = if title.contains(" — ") {
+            " — "
+        } else {
+            " - "
+        };
+        let title = VSCODE_NAMES
+            .iter()
+            .find_map(|name| title.strip_suffix(&format!("{}{}", separator, name)))
+            .unwrap_or(title);
+
+        let parts: Vec<&str> = title.split(separator).collect();
+        let (file, root) = match parts.as_slice() {
+            [] | [""] => return None,
+            [root] => (None, *root),
+            // Anything after the root is the profile name
+            [file, root, ..] => (Some(*file), *root),
+        };
+
+        let mut context = TitleContext {
+            file: file.map(str::to_string),
+            ..Default::default()
+        };
+        let mut root = root.trim();
+        if let Some(workspace) = root.strip_suffix(" (Workspace)") {
+            root = workspace;
+        }
+        if let Some((name, remote)) = trailing_group(root, '[', ']') {
+            root = name;
+            context.ssh_host = remote
+                .strip_prefix("SSH: ")
+                .map(|host| host.trim().to_string());
+        }
+        context.project = (!root.is_empty()).then(|| root.to_string());
+        Some(context)
+    }
+}
+
+/// JetBrains IDEs: `mason-app – main.rs` in the new UI, and
+/// `mason-app [~/code/mason-app] – …/src/main.rs [mason-app] - IntelliJ IDEA` in the old one.
+pub struct JetBrains;
+
+impl TitleParser for JetBrains {
+    fn parse(&self, title: &str) -> Option<TitleContext> {
+        // The old UI appends the IDE, also when no file is open
+        let title = title.trim();
+        let title = JETBRAINS_NAMES
+            .iter()
+            .find_map(|name| {
+                let (rest, app) = title.rsplit_once(" - ")?;
+                app.starts_with(name).then_some(rest)
+            })
+            .unwrap_or(title);
+        let mut parts = title.split(" – ");
+        let project = parts.next().filter(|project| !project.is_empty())?;
+        let mut context = TitleContext::default();
+
+        match trailing_group(project, '[', ']') {
+            Some((name, path)) if looks_like_path(path) => {
+                context.project = Some(name.to_string());
+                context.directory = Some(path.to_string());
+            }
+            _ => context.project = Some(project.to_string()),
+        }
+
+        if let Some(file) = parts.next() {
+            // The module name, when it differs from the file's project
+            let file = trailing_group(file, '[', ']').map_or(file, |(file, _)| file);
+            context.file = Some(file.trim_start_matches("…/").to_string());
+        }
+        Some(context)
+    }
+}
+
+/// Terminal emulators, whose title the shell or the running program sets:
+/// `main.rs + (~/code/mason-app) - NVIM`, `ssh deploy@devbox`, `user@host: ~/code/mason-app`,
+/// `~/code/mason-app (main)` and Terminal.app's `mason-app — -zsh — 80×24`.
+pub struct Terminal;
+
+impl Terminal {
+    /// The title vim and neovim set: `file [+] (directory) - VIM`.
+    fn parse_vim(title: &str) -> Option<TitleContext> {
+        let body = title
+            .strip_suffix(" - NVIM")
+            .or_else(|| title.strip_suffix(" - VIM"))?;
+        let (file, directory) = trailing_group(body, '(', ')')?;
+        let file = file.strip_suffix(" +").unwrap_or(file);
+        Some(TitleContext {
+            project: path_name(directory),
+            directory: Some(directory.to_string()),
+            file: Some(file.to_string()),
+            ..Default::default()
+        })
+    }
+
+    /// `ssh [options] [user@]host [command]`.
+    fn parse_ssh(title: &str) -> Option<TitleContext> {
+        let mut args = title.strip_prefix("ssh ")?.split_whitespace();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix('-') {
+                // Options that take a value
+                Some(option) if option.len() == 1 && "BbcDEeFIiJLlmOoPpQRSWw".contains(option) => {
+                    args.next();
+                }
+                Some(_) => {}
+                None => {
+                    let host = arg.rsplit('@').next()?;
+                    return Some(TitleContext {
+                        ssh_host: Some(host.to_string()),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// A working directory, optionally prefixed with `user@host:` and followed by the
+    /// git branch some prompts add. The host is not reported: it is usually this machine.
+    fn parse_directory(title: &str) -> Option<TitleContext> {
+        let directory = match title.split_once(':') {
+            Some((user_host, path)) if user_host.contains('@') && !user_host.contains(' ') => {
+                path.trim()
+            }
+            _ => title,
+        };
+        let (directory, branch) = match trailing_group(directory, '(', ')') {
+            Some((directory, branch)) if !branch.contains(' ') => (directory, Some(branch)),
+            _ => (directory, None),
+        };
+        if !looks_like_path(directory) || directory.contains(' ') {
+            return None;
+        }
+        Some(TitleContext {
+            project: path_name(directory),
+            directory: Some(directory.to_string()),
+            branch: branch.map(str::to_string),
+            ..Default::default()
+        })
+    }
+
+    /// Terminal.app: `directory name — shell — size`.
+    fn parse_terminal_app(title: &str) -> Option<TitleContext> {
+        let mut parts = title.split(" — ");
+        let name = parts.next()?;
+        let shell = parts.next()?.trim_start_matches('-');
+        SHELLS.contains(&shell).then(|| TitleContext {
+            project: Some(name.to_string()).filter(|name| !name.is_empty()),
+            ..Default::default()
+        })
+    }
+}
+
+impl TitleParser for Terminal {
+    fn parse(&self, title: &str) -> Option<TitleContext> {
+        // iTerm2 prefixes the title with the session number
+        let title = title.trim();
+        let title = match title.split_once(". ") {
+            Some((number, rest)) if number.chars().all(|c| c.is_ascii_digit()) => rest,
+            _ => title,
+        };
+        Self::parse_vim(title)
+            .or_else(|| Self::parse_ssh(title))
+            .or_else(|| Self::parse_directory(title))
+            .or_else(|| Self::parse_terminal_app(title))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(
+        project: Option<&str>,
+        directory: Option<&str>,
+        file: Option<&str>,
+        branch: Option<&str>,
+        ssh_host: Option<&str>,
+    ) -> Option<TitleContext> {
+        Some(TitleContext {
+            project: project.map(str::to_string),
+            directory: directory.map(str::to_string),
+            file: file.map(str::to_string),
+            branch: branch.map(str::to_string),
+            ssh_host: ssh_host.map(str::to_string),
+        })
+    }
+
+    fn check(parser: &dyn TitleParser, cases: &[(&str, Option<TitleContext>)]) {
+        for (title, expected) in cases {
+            assert_eq!(&parser.parse(title), expected, "{}", title);
+        }
+    }
+
+    #[test]
+    fn parses_vscode_titles() {
+        check(
+            &VsCode,
+            &[
+                (
+                    "● main.rs — mason-app — Visual Studio Code",
+                    context(Some("mason-app"), None, Some("main.rs"), None, None),
+                ),
+                (
+                    "main.rs - mason-app - Cursor",
+                    context(Some("mason-app"), None, Some("main.rs"), None, None),
+                ),
+                (
+                    "mason-app — Visual Studio Code",
+                    context(Some("mason-app"), None, None, None, None),
+                ),
+                (
+                    "main.rs — mason-app [SSH: devbox] — Visual Studio Code",
+                    context(
+                        Some("mason-app"),
+                        None,
+                        Some("main.rs"),
+                        None,
+                        Some("devbox"),
+                    ),
+                ),
+                (
+                    "main.rs - mason (Workspace) - Work - Visual Studio Code - Insiders",
+                    context(Some("mason"), None, Some("main.rs"), None, None),
+                ),
+                ("Visual Studio Code", None),
+                ("Visual Studio Code - Insiders", None),
+                ("", None),
+            ],
+        );
+    }
+
+    #[test]
+    fn parses_jetbrains_titles() {
+        check(
+            &JetBrains,
+            &[
+                (
+                    "mason-app – main.rs",
+                    context(Some("mason-app"), None, Some("main.rs"), None, None),
+                ),
+                (
+                    "mason-app [~/code/mason-app] – …/src/main.rs [mason-app] - IntelliJ IDEA",
+                    context(
+                        Some("mason-app"),
+                        Some("~/code/mason-app"),
+                        Some("src/main.rs"),
+                        None,
+                        None,
+                    ),
+                ),
+                (
+                    "mason-app [~/code/mason-app] - IntelliJ IDEA",
+                    context(
+                        Some("mason-app"),
+                        Some("~/code/mason-app"),
+                        None,
+                        None,
+                        None,
+                    ),
+                ),
+                (
+                    "mason-app - PyCharm 2023.2",
+                    context(Some("mason-app"), None, None, None, None),
+                ),
+                (
+                    "mason-app",
+                    context(Some("mason-app"), None, None, None, None),
+                ),
+                ("", None),
+            ],
+        );
+    }
+
+    #[test]
+    fn parses_terminal_titles() {
+        check(
+            &Terminal,
+            &[
+                (
+                    "main.rs + (~/code/mason-app) - NVIM",
+                    context(
+                        Some("mason-app"),
+                        Some("~/code/mason-app"),
+                        Some("main.rs"),
+                        None,
+                        None,
+                    ),
+                ),
+                (
+                    "ssh -p 2222 deploy@devbox",
+                    context(None, None, None, None, Some("devbox")),
+                ),
+                (
+                    "user@laptop: ~/code/mason-app",
+                    context(
+                        Some("mason-app"),
+                        Some("~/code/mason-app"),
+                        None,
+                        None,
+                        None,
+                    ),
+                ),
+                (
+                    "2. ~/code/mason-app (main)",
+                    context(
+                        Some("mason-app"),
+                        Some("~/code/mason-app"),
+                        None,
+                        Some("main"),
+                        None,
+                    ),
+                ),
+                (
+                    "mason-app — -zsh — 80×24",
+                    context(Some("mason-app"), None, None, None, None),
+                ),
+                ("~", context(None, Some("~"), None, None, None)),
+                ("htop", None),
+                ("Downloads — python3 — 80×24", None),
+            ],
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tauri::Manager;
+use tauri_specta::Event;
+
+use crate::commands::error::{CommandError, CommandResult};
+
+/// Activity tracking is paused: snapshots capture nothing until it resumes.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, specta::Type)]
+pub struct TrackingPause {
+    /// Seconds since the epoch at which tracking resumes by itself, if ever.
+    #[specta(type = Option<f64>)]
+    pub until: Option<u64>,
+}
+
+/// Emitted when tracking is paused or resumes, including when a pause runs out.
+#[derive(Debug, Clone, serde::Serialize, specta::Type, tauri_specta::Event)]
+pub struct TrackingPauseChanged {
+    pause: Option<TrackingPause>,
+}
+
+/// The current pause. Not persisted: a restart resumes tracking.
+#[derive(Default)]
+pub struct TrackingPauseState(Mutex<Option<TrackingPause>>);
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn set_pause(app: &tauri::AppHandle, pause: Option<TrackingPause>) -> CommandResult<()> {
+    let state = app.state::<TrackingPauseState>();
+    *state
+        .0
+        .lock()
+        .map_err(|_| CommandError::Internal("Tracking pause lock poisoned".to_string()))? =
+        pause.clone();
+    let _ = TrackingPauseChanged { pause }.emit(app);
+    Ok(())
+}
+
+/// The current pause, for use by other commands. A pause that ran out is cleared here.
+pub fn current_pause(app: &tauri::AppHandle) -> Option<TrackingPause> {
+    let state = app.state::<TrackingPauseState>();
+    let pause = state.0.lock().ok()?.clone()?;
+    if pause.until.is_some_and(|until| until <= now()) {
+        let _ = set_pause(app, None);
+        return None;
+    }
+    Some(pause)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_tracking_pause(app: tauri::AppHandle) -> Option<TrackingPause> {
+    current_pause(&app)
+}
+
+/// Pause tracking for `minutes`, or until `resume_tracking` when not given.
+#[tauri::command]
+#[specta::specta]
+pub fn pause_tracking(app: tauri::AppHandle, minutes: Option<u32>) -> CommandResult<TrackingPause> {
+    let pause = TrackingPause {
+        until: minutes.map(|minutes| now() + u64::from(minutes) * 60),
+    };
+    set_pause(&app, Some(pause.clone()))?;
+    Ok(pause)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn resume_tracking(app: tauri::AppHandle) -> CommandResult<()> {
+    set_pause(&app, None)
+}
@@ -0,0 +1,265 @@
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tauri::{
+    menu::{Menu, MenuBuilder, MenuEvent, MenuItem, SubmenuBuilder},
+    tray::TrayIconBuilder,
+    Manager,
+};
+
+use crate::commands::{
+    error::{CommandError, CommandResult},
+    timer::{running_timer, start_timer, stop_timer, switch_timer, RunningTimer},
+    tracking_pause::{current_pause, pause_tracking, resume_tracking},
+};
+
+const TRAY_ID: &str = "main";
+const MAIN_WINDOW: &str = "main";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+/// Pauses offered in the menu, in minutes.
+const PAUSE_OPTIONS: [(u32, &str); 3] = [(15, "15 minutes"), (60, "1 hour"), (240, "4 hours")];
+
+const START_PREFIX: &str = "timer_start:";
+const SWITCH_PREFIX: &str = "timer_switch:";
+const PAUSE_PREFIX: &str = "pause:";
+
+/// A project the tray offers to start or switch to.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, specta::Type)]
+pub struct TrayProject {
+    pub id: String,
+    pub name: String,
+}
+
+/// What the menu shows, apart from the elapsed time. The menu is rebuilt when it changes.
+#[derive(Debug, Clone, PartialEq)]
+struct MenuContents {
+    timer: Option<RunningTimer>,
+    paused: bool,
+    projects: Vec<TrayProject>,
+}
+
+/// The tray menu as last built, and the projects the frontend offers in it.
+#[derive(Default)]
+pub struct TrayState(Mutex<TrayMenu>);
+
+#[derive(Default)]
+struct TrayMenu {
+    projects: Vec<TrayProject>,
+    contents: Option<MenuContents>,
+    /// The first item, showing the running timer and its elapsed time.
+    status: Option<MenuItem<tauri::Wry>>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// `1:05:09`.
+pub fn format_elapsed(seconds: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn project_name<'a>(projects: &'a [TrayProject], id: &str) -> Option<&'a str> {
+    projects
+        .iter()
+        .find(|project| project.id == id)
+        .map(|project| project.name.as_str())
+}
+
+fn status_text(contents: &MenuContents, now: u64) -> String {
+    match &contents.timer {
+        Some(timer) => format!(
+            "{} — {}",
+            project_name(&contents.projects, &timer.project_id).unwrap_or("Timer running"),
+            format_elapsed(now.saturating_sub(timer.started_at))
+        ),
+        None => "No timer running".to_string(),
+    }
+}
+
+fn build_menu(
+    app: &tauri::AppHandle,
+    contents: &MenuContents,
+    now: u64,
+) -> tauri::Result<(Menu<tauri::Wry>, MenuItem<tauri::Wry>)> {
+    let status = MenuItem::with_id(
+        app,
+        "timer_status",
+        status_text(contents, now),
+        false,
+        None::<&str>,
+    )?;
+    let mut menu = MenuBuilder::new(app).item(&status).separator();
+
+    let running_project = contents.timer.as_ref().map(|timer| &timer.project_id);
+    let (prefix, title) = match running_project {
+        Some(_) => (SWITCH_PREFIX, "Switch to"),
+        None => (START_PREFIX, "Start timer"),
+    };
+    let mut projects = SubmenuBuilder::new(app, title);
+    for project in &contents.projects {
+        if Some(&project.id) != running_project {
+            projects = projects.text(format!("{}{}", prefix, project.id), &project.name);
+        }
+    }
+    let projects = projects
+        .enabled(
+            contents
+                .projects
+                .iter()
+                .any(|project| Some(&project.id) != running_project),
+        )
+        .build()?;
+    if running_project.is_some() {
+        menu = menu.text("timer_stop", "Stop timer");
+    }
+    menu = menu.item(&projects).separator();
+
+    if contents.paused {
+        menu = menu.text("resume", "Resume tracking");
+    } else {
+        let mut pause = SubmenuBuilder::new(app, "Pause tracking");
+        for (minutes, label) in PAUSE_OPTIONS {
+            pause = pause.text(format!("{}{}", PAUSE_PREFIX, minutes), label);
+        }
+        let pause = pause.separator().text("pause", "Until resumed").build()?;
+        menu = menu.item(&pause);
+    }
+
+    let menu = menu
+        .separator()
+        .text("open", "Open Recount")
+        .text("quit", "Quit Recount")
+        .build()?;
+    Ok((menu, status))
+}
+
+/// Bring the main window back, also when it was closed to the tray or minimised.
+pub fn show_main_window(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+fn handle_menu_event(app: &tauri::AppHandle, event: MenuEvent) {
+    let id = event.id().as_ref();
+    // Stopped entries wait in the timer's outbox until the frontend creates them
+    let _ = match id {
+        "timer_stop" => stop_timer(app.clone(), None).map(|_| ()),
+        "pause" => pause_tracking(app.clone(), None).map(|_| ()),
+        "resume" => resume_tracking(app.clone()),
+        "open" => {
+            show_main_window(app);
+            Ok(())
+        }
+        "quit" => {
+            app.exit(0);
+            Ok(())
+        }
+        _ => {
+            if let Some(project_id) = id.strip_prefix(START_PREFIX) {
+                start_timer(app.clone(), project_id.to_string(), None, None, None).map(|_| ())
+            } else if let Some(project_id) = id.strip_prefix(SWITCH_PREFIX) {
+                switch_timer(app.clone(), project_id.to_string(), None, None).map(|_| ())
+            } else if let Some(minutes) = id.strip_prefix(PAUSE_PREFIX) {
+                match minutes.parse() {
+                    Ok(minutes) => pause_tracking(app.clone(), Some(minutes)).map(|_| ()),
+                    Err(_) => Ok(()),
+                }
+            } else {
+                Ok(())
+            }
+        }
+    };
+    refresh_tray(app);
+}
+
+/// Bring the tray in line with the timer and tracking pause: rebuild the menu when they
+/// changed, otherwise only update the elapsed time.
+fn refresh_tray(app: &tauri::AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let state = app.state::<TrayState>();
+    let Ok(mut menu) = state.0.lock() else {
+        return;
+    };
+    let contents = MenuContents {
+        timer: running_timer(app),
+        paused: current_pause(app).is_some(),
+        projects: menu.projects.clone(),
+    };
+    let now = now();
+
+    if menu.contents.as_ref() != Some(&contents) {
+        if let Ok((built, status)) = build_menu(app, &contents, now) {
+            let _ = tray.set_menu(Some(built));
+            menu.status = Some(status);
+            menu.contents = Some(contents.clone());
+        }
+    } else if let Some(status) = &menu.status {
+        let _ = status.set_text(status_text(&contents, now));
+    }
+
+    let elapsed = contents
+        .timer
+        .as_ref()
+        .map(|timer| format_elapsed(now.saturating_sub(timer.started_at)));
+    // The title shows next to the icon in the macOS menu bar; other platforms ignore it
+    let _ = tray.set_title(elapsed.as_deref());
+    let _ = tray.set_tooltip(Some(status_text(&contents, now)));
+}
+
+/// Add the tray icon and keep it up to date with the timer service.
+pub fn create_tray(app: &tauri::AppHandle) -> tauri::Result<()> {
+    let mut tray = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("Recount")
+        .show_menu_on_left_click(true)
+        .on_menu_event(handle_menu_event);
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+
+    let app = app.clone();
+    thread::spawn(move || loop {
+        refresh_tray(&app);
+        thread::sleep(REFRESH_INTERVAL);
+    });
+    Ok(())
+}
+
+/// The projects the frontend last offered in the tray, most recent first.
+pub fn recent_projects(app: &tauri::AppHandle) -> Vec<TrayProject> {
+    app.state::<TrayState>()
+        .0
+        .lock()
+        .map(|menu| menu.projects.clone())
+        .unwrap_or_default()
+}
+
+/// Replace the projects the tray offers to start or switch to, most recent first.
+#[tauri::command]
+#[specta::specta]
+pub fn set_tray_projects(app: tauri::AppHandle, projects: Vec<TrayProject>) -> CommandResult<()> {
+    let state = app.state::<TrayState>();
+    state
+        .0
+        .lock()
+        .map_err(|_| CommandError::Internal("Tray lock poisoned".to_string()))?
+        .projects = projects;
+    refresh_tray(&app);
+    Ok(())
+}
@@ -0,0 +1,417 @@
+use argon2::Argon2;
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use iota_stronghold::{Client, KeyProvider, SnapshotPath, Stronghold};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tauri::Manager;
+use zeroize::Zeroizing;
+
+use crate::commands::error::{CommandError, CommandResult};
+
+const SNAPSHOT_FILE: &str = "recount.hold";
+const SALT_FILE: &str = "vault.salt";
+const VAULT_CLIENT: &[u8] = b"recount";
+const SALT_LENGTH: usize = 16;
+const KEY_LENGTH: usize = 32;
+const BEARER_TOKEN_SECRET: &str = "bearer_token";
+
+struct UnlockedVault {
+    stronghold: Stronghold,
+    client: Client,
+    snapshot_path: SnapshotPath,
+    key_provider: KeyProvider,
+}
+
+/// The Stronghold snapshot holding the app's secrets.
+/// Secrets are only readable between `unlock_vault` and `lock_vault`.
+///
+/// This drives `iota_stronghold` directly instead of the stronghold plugin: the plugin only
+/// opens a snapshot when the webview hands it the password, while the screenshot keys and the
+/// bearer token are needed from Rust, and it cannot re-encrypt a snapshot under a new password.
+#[derive(Default)]
+pub struct VaultState {
+    inner: Mutex<Option<UnlockedVault>>,
+}
+
+impl VaultState {
+    /// Create a new, empty snapshot in `dir` encrypted with the key derived from `password`.
+    pub fn create(&self, dir: &Path, password: &str) -> CommandResult<()> {
+        let paths = VaultPaths::resolve(dir)?;
+        if paths.snapshot.exists() {
+            return Err(CommandError::VaultExists(
+                "A vault already exists, unlock it instead".to_string(),
+            ));
+        }
+
+        let key_provider = key_provider(password, &paths.salt)?;
+        let stronghold = Stronghold::default();
+        let client = stronghold
+            .create_client(VAULT_CLIENT)
+            .map_err(vault_error)?;
+        stronghold
+            .commit_with_keyprovider(&paths.snapshot, &key_provider)
+            .map_err(vault_error)?;
+
+        self.set_unlocked(UnlockedVault {
+            stronghold,
+            client,
+            snapshot_path: paths.snapshot,
+            key_provider,
+        })
+    }
+
+    /// Open the existing snapshot in `dir` with the key derived from `password`.
+    pub fn unlock(&self, dir: &Path, password: &str) -> CommandResult<()> {
+        let paths = VaultPaths::resolve(dir)?;
+        if !paths.snapshot.exists() {
+            return Err(CommandError::VaultNotFound(
+                "No vault exists yet, create one first".to_string(),
+            ));
+        }
+
+        let key_provider = key_provider(password, &paths.salt)?;
+        let stronghold = Stronghold::default();
+        // A wrong password fails to decrypt the snapshot
+        let client = stronghold
+            .load_client_from_snapshot(VAULT_CLIENT, &key_provider, &paths.snapshot)
+            .map_err(|e| CommandError::InvalidPassword(e.to_string()))?;
+
+        self.set_unlocked(UnlockedVault {
+            stronghold,
+            client,
+            snapshot_path: paths.snapshot,
+            key_provider,
+        })
+    }
+
+    /// Re-encrypt the snapshot with a key derived from `new_password`.
+    /// The vault is left unlocked afterwards.
+    pub fn change_password(
+        &self,
+        dir: &Path,
+        old_password: &str,
+        new_password: &str,
+    ) -> CommandResult<()> {
+        // Always check the old password, even when the vault is already unlocked
+        self.unlock(dir, old_password)?;
+
+        let paths = VaultPaths::resolve(dir)?;
+        let mut inner = self.lock_inner()?;
+        let vault = inner.as_mut().ok_or_else(vault_locked)?;
+        let key_provider = key_provider(new_password, &paths.salt)?;
+        vault
+            .stronghold
+            .commit_with_keyprovider(&vault.snapshot_path, &key_provider)
+            .map_err(vault_error)?;
+        vault.key_provider = key_provider;
+        Ok(())
+    }
+
+    /// Drop the decrypted snapshot from memory.
+    pub fn lock(&self) -> CommandResult<()> {
+        *self.lock_inner()? = None;
+        Ok(())
+    }
+
+    pub fn status(&self, dir: &Path) -> CommandResult<VaultStatus> {
+        Ok(VaultStatus {
+            exists: VaultPaths::resolve(dir)?.snapshot.exists(),
+            unlocked: self.is_unlocked(),
+        })
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.inner.lock().map(|v| v.is_some()).unwrap_or(false)
+    }
+
+    pub fn get_secret(&self, key: &str) -> CommandResult<Option<Zeroizing<Vec<u8>>>> {
+        let inner = self.lock_inner()?;
+        let vault = unlocked(&inner)?;
+        Ok(vault
+            .client
+            .store()
+            .get(key.as_bytes())
+            .map_err(vault_error)?
+            .map(Zeroizing::new))
+    }
+
+    /// Store a secret and write the snapshot back to disk.
+    pub fn insert_secret(&self, key: &str, value: Vec<u8>) -> CommandResult<()> {
+        let inner = self.lock_inner()?;
+        let vault = unlocked(&inner)?;
+        vault
+            .client
+            .store()
+            .insert(key.as_bytes().to_vec(), value, None)
+            .map_err(vault_error)?;
+        vault.commit()
+    }
+
+    /// Remove a secret and write the snapshot back to disk.
+    pub fn remove_secret(&self, key: &str) -> CommandResult<()> {
+        let inner = self.lock_inner()?;
+        let vault = unlocked(&inner)?;
+        vault
+            .client
+            .store()
+            .delete(key.as_bytes())
+            .map_err(vault_error)?;
+        vault.commit()
+    }
+
+    fn set_unlocked(&self, vault: UnlockedVault) -> CommandResult<()> {
+        *self.lock_inner()? = Some(vault);
+        Ok(())
+    }
+
+    fn lock_inner(&self) -> CommandResult<std::sync::MutexGuard<'_, Option<UnlockedVault>>> {
+        self.inner
+            .lock()
+            .map_err(|_| CommandError::Internal("Vault lock poisoned".to_string()))
+    }
+}
+
+impl UnlockedVault {
+    fn commit(&self) -> CommandResult<()> {
+        self.stronghold
+            .commit_with_keyprovider(&self.snapshot_path, &self.key_provider)
+            .map_err(vault_error)
+    }
+}
+
+struct VaultPaths {
+    snapshot: SnapshotPath,
+    salt: Vec<u8>,
+}
+
+impl VaultPaths {
+    fn resolve(dir: &Path) -> CommandResult<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            snapshot: SnapshotPath::from_path(dir.join(SNAPSHOT_FILE)),
+            salt: load_or_create_salt(&dir.join(SALT_FILE))?,
+        })
+    }
+}
+
+#[derive(serde::Serialize, specta::Type)]
+pub struct VaultStatus {
+    exists: bool,
+    unlocked: bool,
+}
+
+fn unlocked(vault: &Option<UnlockedVault>) -> CommandResult<&UnlockedVault> {
+    vault.as_ref().ok_or_else(vault_locked)
+}
+
+fn vault_locked() -> CommandError {
+    CommandError::VaultLocked("Unlock the vault first".to_string())
+}
+
+fn key_provider(password: &str, salt: &[u8]) -> CommandResult<KeyProvider> {
+    KeyProvider::try_from(derive_key(password, salt)?).map_err(vault_error)
+}
+
+fn vault_error(error: impl std::fmt::Display) -> CommandError {
+    CommandError::Crypto(format!("Stronghold: {}", error))
+}
+
+/// Argon2id with the crate's default (OWASP recommended) parameters.
+pub fn derive_key(password: &str, salt: &[u8]) -> CommandResult<Zeroizing<Vec<u8>>> {
+    let mut key = Zeroizing::new(vec![0u8; KEY_LENGTH]);
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| CommandError::Crypto(e.to_string()))?;
+    Ok(key)
+}
+
+/// The directory holding the snapshot and its salt.
+fn vault_dir(app: &tauri::AppHandle) -> CommandResult<PathBuf> {
+    Ok(app.path().app_local_data_dir()?)
+}
+
+/// The salt is random per install and not secret, so it is stored next to the snapshot.
+pub fn load_or_create_salt(path: &Path) -> CommandResult<Vec<u8>> {
+    match fs::read(path) {
+        Ok(salt) if salt.len() == SALT_LENGTH => Ok(salt),
+        Ok(_) => Err(CommandError::Crypto(format!(
+            "{} is corrupt",
+            path.to_string_lossy()
+        ))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let mut salt = vec![0u8; SALT_LENGTH];
+            OsRng.fill_bytes(&mut salt);
+            fs::write(path, &salt)?;
+            Ok(salt)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn vault_status(app: tauri::AppHandle) -> CommandResult<VaultStatus> {
+    app.state::<VaultState>().status(&vault_dir(&app)?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn create_vault(app: tauri::AppHandle, password: String) -> CommandResult<()> {
+    let dir = vault_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || app.state::<VaultState>().create(&dir, &password))
+        .await?
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn unlock_vault(app: tauri::AppHandle, password: String) -> CommandResult<()> {
+    let dir = vault_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || app.state::<VaultState>().unlock(&dir, &password))
+        .await?
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn lock_vault(vault: tauri::State<'_, VaultState>) -> CommandResult<()> {
+    vault.lock()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn change_vault_password(
+    app: tauri::AppHandle,
+    old_password: String,
+    new_password: String,
+) -> CommandResult<()> {
+    let dir = vault_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<VaultState>()
+            .change_password(&dir, &old_password, &new_password)
+    })
+    .await?
+}
+
+/// Store the backend bearer token, or remove it when `token` is `None`.
+#[tauri::command]
+#[specta::specta]
+pub fn set_bearer_token(
+    vault: tauri::State<'_, VaultState>,
+    token: Option<String>,
+) -> CommandResult<()> {
+    match token {
+        Some(token) => vault.insert_secret(BEARER_TOKEN_SECRET, token.into_bytes()),
+        None => vault.remove_secret(BEARER_TOKEN_SECRET),
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_bearer_token(vault: tauri::State<'_, VaultState>) -> CommandResult<Option<String>> {
+    vault
+        .get_secret(BEARER_TOKEN_SECRET)?
+        .map(|bytes| {
+            String::from_utf8(bytes.to_vec())
+                .map_err(|_| CommandError::Crypto("Stored bearer token is not UTF-8".to_string()))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "secret";
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("recount-vault-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn derives_the_same_key_for_the_same_salt_only() {
+        let dir = temp_dir("salt");
+        fs::create_dir_all(&dir).unwrap();
+        let salt = load_or_create_salt(&dir.join(SALT_FILE)).unwrap();
+        assert_eq!(salt.len(), SALT_LENGTH);
+        assert_eq!(load_or_create_salt(&dir.join(SALT_FILE)).unwrap(), salt);
+
+        let key = derive_key("password", &salt).unwrap();
+        assert_eq!(key.len(), KEY_LENGTH);
+        assert_eq!(derive_key("password", &salt).unwrap(), key);
+        assert_ne!(derive_key("other password", &salt).unwrap(), key);
+        assert_ne!(derive_key("password", &[0u8; SALT_LENGTH]).unwrap(), key);
+
+        fs::write(dir.join(SALT_FILE), b"short").unwrap();
+        assert!(matches!(
+            load_or_create_salt(&dir.join(SALT_FILE)),
+            Err(CommandError::Crypto(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unlocks_only_with_the_current_password() {
+        // The snapshot's own scrypt work factor makes every commit take minutes in debug builds
+        iota_stronghold::engine::snapshot::try_set_encrypt_work_factor(0).unwrap();
+        let dir = temp_dir("password");
+        let vault = VaultState::default();
+
+        vault.create(&dir, "first").unwrap();
+        assert!(matches!(
+            vault.create(&dir, "first"),
+            Err(CommandError::VaultExists(_))
+        ));
+        vault.insert_secret(SECRET, b"value".to_vec()).unwrap();
+        vault.lock().unwrap();
+        assert!(!vault.is_unlocked());
+        assert!(matches!(
+            vault.get_secret(SECRET),
+            Err(CommandError::VaultLocked(_))
+        ));
+
+        assert!(matches!(
+            vault.unlock(&dir, "wrong"),
+            Err(CommandError::InvalidPassword(_))
+        ));
+        assert!(!vault.is_unlocked());
+        vault.unlock(&dir, "first").unwrap();
+        assert_eq!(
+            vault.get_secret(SECRET).unwrap().unwrap().as_slice(),
+            b"value"
+        );
+
+        assert!(matches!(
+            vault.change_password(&dir, "wrong", "second"),
+            Err(CommandError::InvalidPassword(_))
+        ));
+        vault.change_password(&dir, "first", "second").unwrap();
+        vault.lock().unwrap();
+        assert!(matches!(
+            vault.unlock(&dir, "first"),
+            Err(CommandError::InvalidPassword(_))
+        ));
+        vault.unlock(&dir, "second").unwrap();
+        assert_eq!(
+            vault.get_secret(SECRET).unwrap().unwrap().as_slice(),
+            b"value"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unlock_needs_an_existing_vault() {
+        let dir = temp_dir("missing");
+        let vault = VaultState::default();
+        assert!(matches!(
+            vault.unlock(&dir, "password"),
+            Err(CommandError::VaultNotFound(_))
+        ));
+        assert!(!vault.status(&dir).unwrap().exists);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+//! Local socket through which other processes talk to the running app.
+//!
+//! A second launch of the app hands its command line to the running instance and exits
+//! with the outcome, and the `recount` CLI controls tracking through it. Requests and
+//! responses are single lines of JSON; the app answers each request and closes the
+//! connection. The socket lives in app local data and is only accessible to the user.
+
+use std::{
+    io::{self, BufRead, BufReader},
+    path::PathBuf,
+    time::Duration,
+};
+
+/// Must match `identifier` in tauri.conf.json, which names the app local data directory.
+const APP_IDENTIFIER: &str = "recount";
+const SOCKET_FILE: &str = "instance.sock";
+/// How long a client waits for the app to answer.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Exit codes, also used as `InstanceResponse::exit_code`.
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FAILED: i32 = 1;
+/// The arguments or link were malformed.
+pub const EXIT_USAGE: i32 = 2;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InstanceRequest {
+    /// The command line of a second launch, e.g. `recount --start "Project X"`.
+    Launch {
+        args: Vec<String>,
+        cwd: PathBuf,
+    },
+    /// The running timer and tracking pause.
+    Status,
+    /// Start a timer, or switch the running one, to a project given by name or id.
+    Start {
+        project: String,
+    },
+    Stop,
+    /// Pause tracking for `minutes`, or until resumed.
+    Pause {
+        minutes: Option<u32>,
+    },
+    Resume,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct InstanceResponse {
+    pub exit_code: i32,
+    /// Shown by the client, on stderr when the request failed.
+    pub output: String,
+    /// The result as JSON, for requests that have one.
+    #[serde(default)]
+    pub data: Option<serde_json::Value>,
+}
+
+impl InstanceResponse {
+    pub fn ok(output: impl Into<String>) -> Self {
+        Self {
+            exit_code: EXIT_OK,
+            output: output.into(),
+            data: None,
+        }
+    }
+
+    pub fn failed(exit_code: i32, output: impl Into<String>) -> Self {
+        Self {
+            exit_code,
+            output: output.into(),
+            data: None,
+        }
+    }
+}
+
+/// App local data, as Tauri resolves it, for processes without an app handle.
+pub fn app_local_data_dir() -> io::Result<PathBuf> {
+    dirs::data_local_dir()
+        .map(|dir| dir.join(APP_IDENTIFIER))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No local data directory"))
+}
+
+/// Where the running app listens.
+pub fn socket_path() -> io::Result<PathBuf> {
+    Ok(app_local_data_dir()?.join(SOCKET_FILE))
+}
+
+/// Read one line of JSON.
+pub fn read_message<T: serde::de::DeserializeOwned>(reader: impl io::Read) -> io::Result<T> {
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line)?;
+    serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Write one line of JSON.
+pub fn write_message<T: serde::Serialize>(
+    mut writer: impl io::Write,
+    message: &T,
+) -> io::Result<()> {
+    let mut line =
+        serde_json::to_vec(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    line.push(b'\n');
+    writer.write_all(&line)?;
+    writer.flush()
+}
+
+/// Send a request to the running app. Fails when the app is not running.
+#[cfg(unix)]
+pub fn send(request: &InstanceRequest) -> io::Result<InstanceResponse> {
+    let stream = std::os::unix::net::UnixStream::connect(socket_path()?)?;
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    write_message(&stream, request)?;
+    read_message(&stream)
+}
+
+#[cfg(not(unix))]
+pub fn send(_request: &InstanceRequest) -> io::Result<InstanceResponse> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Only supported on macOS and Linux",
+    ))
+}
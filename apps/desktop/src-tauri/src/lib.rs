@@ -1,14 +1,101 @@
+pub mod activity_log;
+pub mod cli;
 mod commands;
-use commands::capture_window_activity;
+pub mod instance_ipc;
+pub mod native_host;
+use commands::{
+    acknowledge_time_entry, ai::runtime::AiRuntime, align_calendar_events, apply_retention,
+    browser_activity::manifest::install_browser_host, calendar_events, call_ai,
+    capture_window_activity, change_vault_password, check_permissions, classify_project,
+    create_tray, create_vault, discard_timer, get_bearer_token, get_pending_time_entries,
+    get_settings, get_shortcuts, get_timer, get_tracking_pause, hand_off_to_running_instance,
+    handle_second_instance, import_calendar, index_screenshots, list_calendars,
+    listen_for_instances, lock_vault, migrate_plaintext_screenshots, pause_tracking,
+    read_screenshot, read_screenshot_thumbnail, rebind_shortcut, refresh_calendars,
+    register_shortcuts, remove_calendar, request_permission, restore_timer, resume_tracking,
+    rotate_screenshot_key, route_deep_links, route_launch_arguments, schedule_retention,
+    search_activity, set_bearer_token, set_billed_ranges, set_tray_projects, shortcut_plugin,
+    start_timer, stop_timer, storage_usage, subscribe_calendar, summarize_day, switch_timer,
+    take_pending_deep_link, unlock_vault, update_settings, vault_status, watch_meetings,
+    watch_permissions, DeepLinkOpened, DeepLinkRejected, EmbeddingIndexState, MeetingEnded,
+    MeetingStarted, MeetingState, PendingDeepLink, PendingTimeEntriesChanged, PermissionsChanged,
+    RetentionCompleted, ScreenshotHistory, SettingsState, TimerState, TimerStateChanged, TimerTick,
+    TitleParserRegistry, TrackingPauseChanged, TrackingPauseState, TrayState, VaultState,
+};
 
 use specta_typescript::Typescript;
-use tauri_specta::{collect_commands, Builder};
+use tauri_specta::{collect_commands, collect_events, Builder};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // A second launch hands its command line to the running instance and exits with the
+    // outcome, before anything else starts
+    if let Some(exit_code) = hand_off_to_running_instance() {
+        std::process::exit(exit_code);
+    }
+
     // Build Specta command registry
-    let specta_builder =
-        Builder::<tauri::Wry>::new().commands(collect_commands![capture_window_activity]);
+    let specta_builder = Builder::<tauri::Wry>::new()
+        .commands(collect_commands![
+            acknowledge_time_entry,
+            align_calendar_events,
+            apply_retention,
+            calendar_events,
+            capture_window_activity,
+            call_ai,
+            change_vault_password,
+            check_permissions,
+            classify_project,
+            create_vault,
+            discard_timer,
+            get_bearer_token,
+            get_pending_time_entries,
+            get_settings,
+            get_shortcuts,
+            get_timer,
+            get_tracking_pause,
+            import_calendar,
+            index_screenshots,
+            install_browser_host,
+            list_calendars,
+            lock_vault,
+            migrate_plaintext_screenshots,
+            pause_tracking,
+            read_screenshot,
+            read_screenshot_thumbnail,
+            rebind_shortcut,
+            refresh_calendars,
+            remove_calendar,
+            request_permission,
+            resume_tracking,
+            rotate_screenshot_key,
+            search_activity,
+            set_bearer_token,
+            set_billed_ranges,
+            set_tray_projects,
+            start_timer,
+            stop_timer,
+            storage_usage,
+            subscribe_calendar,
+            summarize_day,
+            switch_timer,
+            take_pending_deep_link,
+            unlock_vault,
+            update_settings,
+            vault_status
+        ])
+        .events(collect_events![
+            DeepLinkOpened,
+            DeepLinkRejected,
+            MeetingEnded,
+            MeetingStarted,
+            PendingTimeEntriesChanged,
+            PermissionsChanged,
+            RetentionCompleted,
+            TimerStateChanged,
+            TimerTick,
+            TrackingPauseChanged
+        ]);
 
     // Export TypeScript bindings in debug builds
     #[cfg(debug_assertions)]
@@ -18,21 +105,63 @@ pub fn run() {
 
     // Build and run Tauri app
     tauri::Builder::default()
-        .plugin(tauri_plugin_stronghold::Builder::new(|pass| todo!()).build())
         // The single_instance plugin should always be first
-        .plugin(tauri_plugin_single_instance::init(|_app, argv, _cwd| {
-            println!("a new app instance was opened with {argv:?} and the deep link event was already triggered");
+        .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
+            handle_second_instance(app, argv, cwd);
         }))
         .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_os::init())
+        .manage(AiRuntime::default())
+        .manage(EmbeddingIndexState::default())
+        .manage(VaultState::default())
+        .manage(SettingsState::default())
+        .manage(ScreenshotHistory::default())
+        .manage(TitleParserRegistry::default())
+        .manage(MeetingState::default())
+        .manage(TimerState::default())
+        .manage(TrackingPauseState::default())
+        .manage(TrayState::default())
+        .manage(PendingDeepLink::default())
         .invoke_handler(specta_builder.invoke_handler())
+        // Closing a window hides it, tracking carries on from the tray
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::CloseRequested { api, .. } => {
+                api.prevent_close();
+                let _ = window.hide();
+            }
+            // The quick-entry popup goes away once the user clicks elsewhere
+            tauri::WindowEvent::Focused(false) if window.label() == "quick-entry" => {
+                let _ = window.hide();
+            }
+            _ => {}
+        })
         .setup(move |app| {
             specta_builder.mount_events(app);
+
+            watch_permissions(app.handle().clone());
+            schedule_retention(app.handle().clone());
+            watch_meetings(app.handle().clone());
+            restore_timer(app.handle().clone());
+            create_tray(app.handle())?;
+            app.handle().plugin(shortcut_plugin())?;
+            register_shortcuts(app.handle());
+            route_deep_links(app.handle());
+            listen_for_instances(app.handle().clone());
+            route_launch_arguments(app.handle());
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Clicking the Dock icon brings back a window that was closed to the tray
+            #[cfg(target_os = "macos")]
+            if let tauri::RunEvent::Reopen { .. } = event {
+                commands::show_main_window(app);
+            }
+            #[cfg(not(target_os = "macos"))]
+            let _ = (app, event);
+        });
 }
@@ -5,5 +5,14 @@
 )]
 
 fn main() {
+    // Browsers start the app as the native messaging host of the extension
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if tauri_app_lib::native_host::is_browser_launch(&args) {
+        if let Err(error) = tauri_app_lib::native_host::run() {
+            eprintln!("recount: {error}");
+            std::process::exit(1);
+        }
+        return;
+    }
     tauri_app_lib::run()
 }
@@ -0,0 +1,487 @@
+//! Native messaging host for the browser extensions.
+//!
+//! Chrome and Firefox start the app binary itself as the host, see `is_browser_launch`,
+//! and exchange JSON messages over stdio, each prefixed with its length as a native-endian
+//! u32. The host records the active tab of the browser that started it in app local data,
+//! where `capture_window_activity` picks it up. The per-domain privacy rules are applied
+//! before anything is written, so hidden domains never reach the disk.
+//! docs: https://developer.chrome.com/docs/extensions/develop/concepts/native-messaging
+
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    commands::{
+        browser_activity::private_tab,
+        settings::{read_settings, BrowserSettings},
+    },
+    instance_ipc::app_local_data_dir,
+};
+
+/// Name the extensions connect to with `runtime.connectNative`.
+pub const HOST_NAME: &str = "app.recount.native_host";
+/// One state file per browser process, named after its pid.
+const BROWSER_TABS_DIR: &str = "browser_tabs";
+/// State the extension has not refreshed for this long belongs to a browser that quit
+/// or hung, and is ignored.
+pub const STATE_MAX_AGE_SECONDS: u64 = 2 * 60;
+/// Browsers refuse messages from a host larger than 1 MB; incoming ones are capped at 64 MB.
+const MAX_MESSAGE_LENGTH: usize = 64 * 1024 * 1024;
+
+/// A message sent by the extension.
+#[derive(Debug, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExtensionMessage {
+    /// The active tab of the focused browser window changed, or its URL did.
+    ActiveTab {
+        url: String,
+        title: String,
+        incognito: bool,
+    },
+    /// No browser window has focus any more.
+    Blur,
+    /// Sent every minute while the browser runs, to keep the stored state fresh.
+    Ping,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct HostResponse {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The last state reported by the extension, as stored on disk.
+/// Incognito tabs and hidden domains are stored without their URL and title.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct BrowserTabState {
+    /// The browser process that started the host; `None` where that is not known.
+    pub browser_pid: Option<u32>,
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub incognito: bool,
+    /// Whether a browser window has focus.
+    pub focused: bool,
+    pub updated_at: u64,
+}
+
+impl BrowserTabState {
+    pub fn is_fresh(&self, now: u64) -> bool {
+        now.saturating_sub(self.updated_at) < STATE_MAX_AGE_SECONDS
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Whether the browser started this process as its native messaging host. Chromium passes
+/// the extension's origin, Firefox the path of the host manifest.
+pub fn is_browser_launch(args: &[String]) -> bool {
+    args.first().is_some_and(|arg| {
+        arg.starts_with("chrome-extension://") || arg.ends_with(&format!("{}.json", HOST_NAME))
+    })
+}
+
+/// Where the hosts store the active tabs; the same directory as the app's app local data.
+pub fn browser_tabs_dir() -> io::Result<PathBuf> {
+    Ok(app_local_data_dir()?.join(BROWSER_TABS_DIR))
+}
+
+/// The states stored by every host that is, or was, running.
+pub fn browser_tab_states(dir: &Path) -> io::Result<Vec<BrowserTabState>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        // The extension is not installed or has not reported anything yet
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut states = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        // A host can remove its file while we list them
+        if let Some(state) = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        {
+            states.push(state);
+        }
+    }
+    Ok(states)
+}
+
+/// The browser that started the host.
+fn browser_pid() -> Option<u32> {
+    #[cfg(unix)]
+    {
+        Some(std::os::unix::process::parent_id())
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// Read one length-prefixed message. `None` when the browser closed the pipe.
+pub fn read_message(input: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut length = [0u8; 4];
+    match input.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let length = u32::from_ne_bytes(length) as usize;
+    if length > MAX_MESSAGE_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Message of {} bytes is too large", length),
+        ));
+    }
+    let mut message = vec![0u8; length];
+    input.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+pub fn write_message(output: &mut impl Write, message: &[u8]) -> io::Result<()> {
+    output.write_all(&(message.len() as u32).to_ne_bytes())?;
+    output.write_all(message)?;
+    output.flush()
+}
+
+/// The URL without credentials, which some sites put in links.
+pub fn strip_credentials(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    match rest[..authority_end].rfind('@') {
+        Some(at) => format!("{}://{}", scheme, &rest[at + 1..]),
+        None => url.to_string(),
+    }
+}
+
+fn store_state(path: &Path, state: &BrowserTabState) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_vec(state)?)?;
+    fs::rename(&tmp_path, path)
+}
+
+/// The privacy rules as the app saved them. Read for every tab, since they can change
+/// while the browser runs; without them, nothing about a tab is stored.
+fn saved_browser_settings() -> Option<BrowserSettings> {
+    let settings = read_settings(&app_local_data_dir().ok()?).ok()?;
+    Some(settings.browser)
+}
+
+fn handle_message(
+    message: &[u8],
+    state: &mut Option<BrowserTabState>,
+    browser_pid: Option<u32>,
+    browser_settings: &impl Fn() -> Option<BrowserSettings>,
+) -> Result<(), String> {
+    let message: ExtensionMessage = serde_json::from_slice(message).map_err(|e| e.to_string())?;
+    let updated_at = now();
+
+    *state = Some(match (message, state.take()) {
+        (ExtensionMessage::Ping, Some(state)) => BrowserTabState {
+            updated_at,
+            ..state
+        },
+        // Nothing to refresh before the first report
+        (ExtensionMessage::Ping, None) => return Ok(()),
+        (
+            ExtensionMessage::ActiveTab {
+                url,
+                title,
+                incognito,
+            },
+            _,
+        ) => {
+            let (url, title) = match browser_settings().filter(|_| !incognito) {
+                Some(settings) => private_tab(&settings, &strip_credentials(&url), title),
+                None => (None, None),
+            };
+            BrowserTabState {
+                browser_pid,
+                url,
+                title,
+                incognito,
+                focused: true,
+                updated_at,
+            }
+        }
+        (ExtensionMessage::Blur, _) => BrowserTabState {
+            browser_pid,
+            focused: false,
+            updated_at,
+            ..Default::default()
+        },
+    });
+    Ok(())
+}
+
+/// Answer messages until the browser closes stdin, keeping the state in `state_path`.
+/// The state is removed when the browser goes away.
+pub fn serve(
+    input: &mut impl Read,
+    output: &mut impl Write,
+    state_path: &Path,
+    browser_pid: Option<u32>,
+    browser_settings: impl Fn() -> Option<BrowserSettings>,
+) -> io::Result<()> {
+    let mut state = None;
+    let served = serve_messages(
+        input,
+        output,
+        state_path,
+        browser_pid,
+        &browser_settings,
+        &mut state,
+    );
+    if state.is_some() {
+        let _ = fs::remove_file(state_path);
+    }
+    served
+}
+
+fn serve_messages(
+    input: &mut impl Read,
+    output: &mut impl Write,
+    state_path: &Path,
+    browser_pid: Option<u32>,
+    browser_settings: &impl Fn() -> Option<BrowserSettings>,
+    state: &mut Option<BrowserTabState>,
+) -> io::Result<()> {
+    while let Some(message) = read_message(input)? {
+        let handled = handle_message(&message, state, browser_pid, browser_settings).and_then(
+            |()| match state {
+                Some(state) => store_state(state_path, state).map_err(|e| e.to_string()),
+                None => Ok(()),
+            },
+        );
+        let response = match handled {
+            Ok(()) => HostResponse {
+                ok: true,
+                error: None,
+            },
+            Err(error) => HostResponse {
+                ok: false,
+                error: Some(error),
+            },
+        };
+        write_message(output, &serde_json::to_vec(&response)?)?;
+    }
+    Ok(())
+}
+
+/// Remove the files of hosts that quit without cleaning up.
+fn remove_stale_states(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let max_age = std::time::Duration::from_secs(STATE_MAX_AGE_SECONDS);
+    for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        let stale = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > max_age));
+        if stale {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Entry point when a browser starts the app binary, see `is_browser_launch`.
+pub fn run() -> io::Result<()> {
+    let dir = browser_tabs_dir()?;
+    remove_stale_states(&dir);
+    let browser_pid = browser_pid();
+    let file_name = match browser_pid {
+        Some(pid) => format!("{}.json", pid),
+        None => format!("host-{}.json", std::process::id()),
+    };
+    serve(
+        &mut io::stdin().lock(),
+        &mut io::stdout().lock(),
+        &dir.join(file_name),
+        browser_pid,
+        saved_browser_settings,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::commands::settings::{DomainRule, UrlPrivacy};
+
+    fn full_urls() -> Option<BrowserSettings> {
+        Some(BrowserSettings::default())
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("recount-host-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Messages framed the way the browser writes them.
+    fn framed(messages: &[&str]) -> Vec<u8> {
+        let mut input = Vec::new();
+        for message in messages {
+            write_message(&mut input, message.as_bytes()).unwrap();
+        }
+        input
+    }
+
+    /// A fake extension on the other end of the pipes: answers the host wrote back.
+    fn responses(output: &[u8]) -> Vec<serde_json::Value> {
+        let mut output = output;
+        let mut responses = Vec::new();
+        while let Some(response) = read_message(&mut output).unwrap() {
+            responses.push(serde_json::from_slice(&response).unwrap());
+        }
+        responses
+    }
+
+    #[test]
+    fn records_the_active_tab_until_the_browser_closes_the_pipe() {
+        let dir = temp_dir("serve");
+        let state_path = dir.join("42.json");
+        let input = framed(&[
+            r#"{"type":"ping"}"#,
+            r#"{"type":"active_tab","url":"https://user:pw@example.com/a","title":"A","incognito":false}"#,
+            r#"{"type":"unknown"}"#,
+            r#"{"type":"ping"}"#,
+        ]);
+
+        // `serve` without the cleanup, to look at the stored state
+        let mut output = Vec::new();
+        let mut state = None;
+        serve_messages(
+            &mut &input[..],
+            &mut output,
+            &state_path,
+            Some(42),
+            &full_urls,
+            &mut state,
+        )
+        .unwrap();
+
+        let stored = &browser_tab_states(&dir).unwrap()[0];
+        assert_eq!(stored.browser_pid, Some(42));
+        assert_eq!(stored.url.as_deref(), Some("https://example.com/a"));
+        assert!(stored.focused && stored.is_fresh(now()));
+
+        let answers = responses(&output);
+        let ok: Vec<bool> = answers.iter().map(|r| r["ok"] == true).collect();
+        assert_eq!(ok, [true, true, false, true]);
+        assert!(answers[2]["error"].is_string());
+
+        // A closed pipe ends the host and removes its state
+        let input = framed(&[r#"{"type":"blur"}"#]);
+        let mut output = Vec::new();
+        serve(
+            &mut &input[..],
+            &mut output,
+            &state_path,
+            Some(42),
+            full_urls,
+        )
+        .unwrap();
+        assert_eq!(responses(&output).len(), 1);
+        assert!(browser_tab_states(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn keeps_incognito_tabs_out_of_the_state() {
+        let mut state = None;
+        let message =
+            r#"{"type":"active_tab","url":"https://example.com","title":"A","incognito":true}"#;
+        handle_message(message.as_bytes(), &mut state, None, &full_urls).unwrap();
+        let state = state.unwrap();
+        assert!(state.incognito && state.url.is_none() && state.title.is_none());
+    }
+
+    #[test]
+    fn applies_the_privacy_rules_before_storing_a_tab() {
+        let settings = || {
+            Some(BrowserSettings {
+                default_privacy: UrlPrivacy::FullUrl,
+                domain_rules: vec![
+                    DomainRule {
+                        domain: "bank.example".to_string(),
+                        privacy: UrlPrivacy::Hidden,
+                    },
+                    DomainRule {
+                        domain: "mail.example".to_string(),
+                        privacy: UrlPrivacy::DomainOnly,
+                    },
+                ],
+            })
+        };
+        let stored = |url: &str, settings: &dyn Fn() -> Option<BrowserSettings>| {
+            let message = serde_json::json!({
+                "type": "active_tab", "url": url, "title": "Secret", "incognito": false,
+            });
+            let mut state = None;
+            handle_message(message.to_string().as_bytes(), &mut state, None, &settings).unwrap();
+            let state = state.unwrap();
+            (state.url, state.title)
+        };
+
+        assert_eq!(
+            stored("https://docs.example/a", &settings),
+            (Some("https://docs.example/a".into()), Some("Secret".into()))
+        );
+        assert_eq!(
+            stored("https://mail.example/inbox/42", &settings),
+            (Some("https://mail.example".into()), None)
+        );
+        assert_eq!(
+            stored("https://login.bank.example/", &settings),
+            (None, None)
+        );
+        // Unreadable settings store nothing rather than everything
+        assert_eq!(stored("https://docs.example/a", &|| None), (None, None));
+    }
+
+    #[test]
+    fn rejects_truncated_and_oversized_frames() {
+        // EOF between messages ends the stream, inside one is an error
+        assert!(read_message(&mut &[][..]).unwrap().is_none());
+        let mut truncated = framed(&["{}"]);
+        truncated.pop();
+        assert!(read_message(&mut &truncated[..]).is_err());
+
+        let oversized = ((MAX_MESSAGE_LENGTH + 1) as u32).to_ne_bytes();
+        let error = read_message(&mut &oversized[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn recognises_browser_launches() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert!(is_browser_launch(&args(&["chrome-extension://abcdef/"])));
+        assert!(is_browser_launch(&args(&[
+            "/home/me/.mozilla/native-messaging-hosts/app.recount.native_host.json",
+            "extension@recount.app",
+        ])));
+        assert!(!is_browser_launch(&args(&[])));
+        assert!(!is_browser_launch(&args(&["recount://timer"])));
+    }
+}
@@ -7,6 +7,8 @@ interface DesktopPlatform {
   ) => Promise<void>;
   getCurrent: () => Promise<Array<string> | null>;
   onOpenUrl: (handler: (urls: Array<string>) => void) => Promise<() => void>;
+  invoke: <T>(command: string, args?: Record<string, unknown>) => Promise<T>;
+  hideWindow: () => Promise<void>;
 }
 
 interface WebPlatform {
@@ -23,5 +25,7 @@ export const PLATFORM: Platform =
         openUrl: window.__TAURI__.opener.openUrl,
         getCurrent: window.__TAURI__.deepLink.getCurrent,
         onOpenUrl: window.__TAURI__.deepLink.onOpenUrl,
+        invoke: window.__TAURI__.core.invoke,
+        hideWindow: () => window.__TAURI__.window.getCurrentWindow().hide(),
       } as const)
     : ({ platform: "web" } as const);
@@ -14,6 +14,7 @@ import { Route as AppRouteRouteImport } from './routes/_app/route'
 import { Route as AppIndexRouteImport } from './routes/_app/index'
 import { Route as AppOnboardingRouteRouteImport } from './routes/_app/_onboarding/route'
 import { Route as AppWorkspaceSlugRouteRouteImport } from './routes/_app/$workspaceSlug/route'
+import { Route as AppQuickEntryIndexRouteImport } from './routes/_app/quick-entry/index'
 import { Route as AuthSignUpIndexRouteImport } from './routes/_auth/sign-up/index'
 import { Route as AuthSignInIndexRouteImport } from './routes/_auth/sign-in/index'
 import { Route as AppWorkspaceSlugSidebarRouteRouteImport } from './routes/_app/$workspaceSlug/_sidebar/route'
@@ -44,6 +45,11 @@ const AppWorkspaceSlugRouteRoute = AppWorkspaceSlugRouteRouteImport.update({
   path: '/$workspaceSlug',
   getParentRoute: () => AppRouteRoute,
 } as any)
+const AppQuickEntryIndexRoute = AppQuickEntryIndexRouteImport.update({
+  id: '/quick-entry/',
+  path: '/quick-entry/',
+  getParentRoute: () => AppRouteRoute,
+} as any)
 const AuthSignUpIndexRoute = AuthSignUpIndexRouteImport.update({
   id: '/sign-up/',
   path: '/sign-up/',
@@ -87,6 +93,7 @@ const AppWorkspaceSlugSidebarProjectsIndexRoute =
 export interface FileRoutesByFullPath {
   '/': typeof AppIndexRoute
   '/$workspaceSlug': typeof AppWorkspaceSlugSidebarRouteRouteWithChildren
+  '/quick-entry/': typeof AppQuickEntryIndexRoute
   '/sign-in/': typeof AuthSignInIndexRoute
   '/sign-up/': typeof AuthSignUpIndexRoute
   '/$workspaceSlug/': typeof AppWorkspaceSlugSidebarIndexRoute
@@ -97,6 +104,7 @@ export interface FileRoutesByFullPath {
 export interface FileRoutesByTo {
   '/': typeof AppIndexRoute
   '/$workspaceSlug': typeof AppWorkspaceSlugSidebarIndexRoute
+  '/quick-entry': typeof AppQuickEntryIndexRoute
   '/sign-in': typeof AuthSignInIndexRoute
   '/sign-up': typeof AuthSignUpIndexRoute
   '/create-workspace': typeof AppOnboardingCreateWorkspaceIndexRoute
@@ -111,6 +119,7 @@ export interface FileRoutesById {
   '/_app/_onboarding': typeof AppOnboardingRouteRouteWithChildren
   '/_app/': typeof AppIndexRoute
   '/_app/$workspaceSlug/_sidebar': typeof AppWorkspaceSlugSidebarRouteRouteWithChildren
+  '/_app/quick-entry/': typeof AppQuickEntryIndexRoute
   '/_auth/sign-in/': typeof AuthSignInIndexRoute
   '/_auth/sign-up/': typeof AuthSignUpIndexRoute
   '/_app/$workspaceSlug/_sidebar/': typeof AppWorkspaceSlugSidebarIndexRoute
@@ -123,6 +132,7 @@ export interface FileRouteTypes {
   fullPaths:
     | '/'
     | '/$workspaceSlug'
+    | '/quick-entry/'
     | '/sign-in/'
     | '/sign-up/'
     | '/$workspaceSlug/'
@@ -133,6 +143,7 @@ export interface FileRouteTypes {
   to:
     | '/'
     | '/$workspaceSlug'
+    | '/quick-entry'
     | '/sign-in'
     | '/sign-up'
     | '/create-workspace'
@@ -146,6 +157,7 @@ export interface FileRouteTypes {
     | '/_app/_onboarding'
     | '/_app/'
     | '/_app/$workspaceSlug/_sidebar'
+    | '/_app/quick-entry/'
     | '/_auth/sign-in/'
     | '/_auth/sign-up/'
     | '/_app/$workspaceSlug/_sidebar/'
@@ -196,6 +208,13 @@ declare module '@tanstack/react-router' {
       preLoaderRoute: typeof AppWorkspaceSlugRouteRouteImport
       parentRoute: typeof AppRouteRoute
     }
+    '/_app/quick-entry/': {
+      id: '/_app/quick-entry/'
+      path: '/quick-entry'
+      fullPath: '/quick-entry/'
+      preLoaderRoute: typeof AppQuickEntryIndexRouteImport
+      parentRoute: typeof AppRouteRoute
+    }
     '/_auth/sign-up/': {
       id: '/_auth/sign-up/'
       path: '/sign-up'
@@ -297,12 +316,14 @@ interface AppRouteRouteChildren {
   AppWorkspaceSlugRouteRoute: typeof AppWorkspaceSlugRouteRouteWithChildren
   AppOnboardingRouteRoute: typeof AppOnboardingRouteRouteWithChildren
   AppIndexRoute: typeof AppIndexRoute
+  AppQuickEntryIndexRoute: typeof AppQuickEntryIndexRoute
 }
 
 const AppRouteRouteChildren: AppRouteRouteChildren = {
   AppWorkspaceSlugRouteRoute: AppWorkspaceSlugRouteRouteWithChildren,
   AppOnboardingRouteRoute: AppOnboardingRouteRouteWithChildren,
   AppIndexRoute: AppIndexRoute,
+  AppQuickEntryIndexRoute: AppQuickEntryIndexRoute,
 }
 
 const AppRouteRouteWithChildren = AppRouteRoute._addFileChildren(
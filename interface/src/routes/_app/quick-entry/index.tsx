@@ -0,0 +1,95 @@
+import {
+  Command,
+  CommandEmpty,
+  CommandInput,
+  CommandItem,
+  CommandList,
+} from "@recount/ui/command";
+import { useLiveQuery } from "@tanstack/react-db";
+import { createFileRoute, redirect } from "@tanstack/react-router";
+import { Option } from "effect";
+import { use } from "react";
+import { useHotkeys } from "react-hotkeys-hook";
+
+import { getWorkspaceCollections } from "~/db/workspace-collections";
+import { PLATFORM } from "~/lib/utils/constants";
+
+// The always-on-top popup the desktop app opens with its quick entry shortcut,
+// see `quick-entry` in tauri.conf.json
+export const Route = createFileRoute("/_app/quick-entry/")({
+  beforeLoad: ({ context }) => {
+    if (PLATFORM.platform !== "desktop") {
+      throw redirect({ to: "/" });
+    }
+
+    const lastActiveWorkspaceId = Option.getOrUndefined(
+      context.auth.session.lastActiveWorkspaceId
+    );
+    const workspace =
+      context.workspaces.find((w) => w.id === lastActiveWorkspaceId) ??
+      context.workspaces[0];
+
+    return { desktop: PLATFORM, workspace };
+  },
+  component: RouteComponent,
+});
+
+function RouteComponent() {
+  const { desktop, workspace } = Route.useRouteContext();
+
+  useHotkeys("escape", () => desktop.hideWindow(), {
+    enableOnFormTags: true,
+  });
+
+  if (!workspace) {
+    return (
+      <div className="p-4 text-muted-foreground text-sm">
+        Create a workspace in Recount first.
+      </div>
+    );
+  }
+
+  return <ProjectPicker workspaceId={workspace.id} />;
+}
+
+function ProjectPicker({ workspaceId }: { workspaceId: string }) {
+  const { desktop } = Route.useRouteContext();
+  const db = use(getWorkspaceCollections(workspaceId));
+  const { data: projects } = useLiveQuery((q) =>
+    q.from({ project: db.projectsCollection })
+  );
+
+  // Stops the running timer, if any, and starts one for the project
+  const startTimer = async (projectId: string) => {
+    await desktop.invoke("switch_timer", {
+      projectId,
+      taskId: null,
+      notes: null,
+    });
+    await desktop.hideWindow();
+  };
+
+  return (
+    <Command>
+      <CommandInput placeholder="Start a timer for..." />
+      <CommandList>
+        <CommandEmpty>No project found.</CommandEmpty>
+        {projects
+          ?.filter((project) => !project.archivedAt)
+          .map((project) => (
+            <CommandItem
+              key={project.id}
+              onSelect={() => startTimer(project.id)}
+              value={project.name}
+            >
+              <span
+                className="size-2 rounded-full"
+                style={{ backgroundColor: project.hexColor }}
+              />
+              <span>{project.name}</span>
+            </CommandItem>
+          ))}
+      </CommandList>
+    </Command>
+  );
+}